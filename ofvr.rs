@@ -1,7 +1,24 @@
+use std::process::ExitCode;
+
 use clap::Parser;
-use ofvr::{go, Cli, Result};
+use ofvr::{go, Cli, Format};
 
-fn main() -> Result<()> {
-    let args = Cli::parse();
-    go(args)
+fn main() -> ExitCode {
+    let args = match Cli::try_parse() {
+        Ok(args) => args,
+        Err(error) if !error.use_stderr() => error.exit(),
+        Err(error) => {
+            let format = Format::from_args(&std::env::args().collect::<Vec<String>>());
+            format.print_error(&error.into());
+            return ExitCode::FAILURE;
+        },
+    };
+    let format = args.format;
+    match go(args) {
+        Ok(code) => code,
+        Err(error) => {
+            format.print_error(&error);
+            ExitCode::FAILURE
+        },
+    }
 }
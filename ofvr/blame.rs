@@ -0,0 +1,145 @@
+//! Attribution of the bytes of a version to the commits that last
+//! changed them, see [`OFVRState::blame`]
+use std::fmt::Display;
+use std::ops::Range;
+
+use crate::errors::{Error, Result};
+use crate::models::author::Author;
+use crate::models::commit::Commit;
+use crate::models::state::OFVRState;
+use crate::traits::PlainBytes;
+
+/// `BlameRun` is a contiguous run of bytes last changed by the same commit
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BlameRun {
+    pub start: usize,
+    pub end: usize,
+    pub commit: Commit,
+    pub author: Author,
+}
+impl BlameRun {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn to_json(&self, ofvr: &OFVRState) -> Result<serde_json::Value> {
+        let data = self.commit.data(ofvr)?;
+        Ok(serde_json::json!({
+            "start": self.start,
+            "end": self.end,
+            "commit": self.commit.id.to_hex(),
+            "author": self.author,
+            "date": data.date_rfc3339(),
+            "message": data.message(),
+        }))
+    }
+}
+impl Display for BlameRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{:#010x}..{:#010x} {} {}",
+            self.start,
+            self.end,
+            self.commit.id.to_hex(),
+            self.author
+        )
+    }
+}
+
+/// `blame` attributes each byte of the version of `commit` within
+/// `range`, or within the whole version when `None`, to the commit
+/// that last changed it, replaying the delta of every commit from the
+/// first commit up to `commit` through first parents like
+/// [`OFVRState::version`], so changes brought by merges are
+/// attributed to the merge commit
+pub fn blame(
+    ofvr: &OFVRState,
+    commit: &Commit,
+    range: Option<Range<usize>>,
+) -> Result<Vec<BlameRun>> {
+    let mut chain = Vec::<Commit>::new();
+    let mut current = Some(commit.clone());
+    while let Some(commit) = current {
+        current = match commit.data(ofvr)?.parents().first() {
+            Some(parent) => Some(ofvr.get_commit(parent)?),
+            None => None,
+        };
+        chain.push(commit);
+    }
+    chain.reverse();
+
+    let length = commit.data(ofvr)?.delta().length();
+    let range = match range {
+        Some(range) if range.start >= range.end =>
+            return Err(Error::BlameError(format!("empty range {}..{}", range.start, range.end))),
+        Some(range) if range.start >= length =>
+            return Err(Error::BlameError(format!(
+                "range starts at {}, past the end of the version of {} ({} bytes)",
+                range.start, commit.id, length
+            ))),
+        Some(range) => range.start..range.end.min(length),
+        None => 0..length,
+    };
+
+    // position in `chain` of the commit that last changed each byte of the range
+    let mut owners = vec![0usize; range.len()];
+    let mut anterior = Vec::<u8>::new();
+    for (position, commit) in chain.iter().enumerate() {
+        let delta = ofvr.delta(commit)?;
+        delta.check()?;
+        let current = delta.apply(&anterior);
+        for hunk in delta.hunks().iter() {
+            let start = hunk.offset.max(range.start);
+            let end = hunk.end().min(range.end);
+            for offset in start..end {
+                if anterior.get(offset) != Some(&current[offset]) {
+                    owners[offset - range.start] = position;
+                }
+            }
+        }
+        anterior = current;
+    }
+
+    let mut runs = Vec::<BlameRun>::new();
+    for (index, owner) in owners.iter().enumerate() {
+        let offset = range.start + index;
+        match runs.last_mut() {
+            Some(run) if run.commit.id == chain[*owner].id => run.end = offset + 1,
+            _ => runs.push(BlameRun {
+                start: offset,
+                end: offset + 1,
+                commit: chain[*owner].clone(),
+                author: chain[*owner].author(ofvr)?,
+            }),
+        }
+    }
+    Ok(runs)
+}
+
+/// `parse_byte_range` parses ranges of the form `start:end` whose
+/// offsets are decimal or hexadecimal with a `0x` prefix, where an
+/// empty end stands for the end of the version
+pub fn parse_byte_range(range: &str) -> Result<Range<usize>> {
+    let (start, end) = range.split_once(':').ok_or_else(|| {
+        Error::BlameError(format!("invalid range {:#?}, expected START:END", range))
+    })?;
+    let start = parse_offset(start)?;
+    let end = if end.is_empty() { usize::MAX } else { parse_offset(end)? };
+    if start >= end {
+        return Err(Error::BlameError(format!("empty range {:#?}", range)));
+    }
+    Ok(start..end)
+}
+
+fn parse_offset(offset: &str) -> Result<usize> {
+    let parsed = match offset.strip_prefix("0x").or_else(|| offset.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => offset.parse::<usize>(),
+    };
+    parsed.map_err(|_| Error::BlameError(format!("invalid offset {:#?}", offset)))
+}
@@ -0,0 +1,159 @@
+//! Content-defined chunking of versions, an alternative [`Storage`]
+//! to deltas where each version is a list of chunks stored once by
+//! keccak256 in the pack, see [`crate::pack`] and
+//! [`crate::OFVRState::set_storage`]
+//!
+//! Chunk boundaries depend on the bytes preceding them rather than on
+//! their offset so that inserting bytes in a version only changes the
+//! chunks around the insertion
+use std::fmt::Display;
+use std::io::{ErrorKind, Read};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{Error, Result};
+
+/// Chunks are at least this long unless they end the version
+pub const MIN_CHUNK_SIZE: usize = 2 << 10;
+/// Chunks are this long on average past [`MIN_CHUNK_SIZE`]
+pub const AVERAGE_CHUNK_SIZE: usize = 8 << 10;
+/// Chunks are cut at this length when no boundary was found
+pub const MAX_CHUNK_SIZE: usize = 64 << 10;
+
+/// boundaries are found where the top bits of the rolling hash are
+/// zero, as many as needed for one in [`AVERAGE_CHUNK_SIZE`] bytes
+const MASK: u64 = !(u64::MAX >> AVERAGE_CHUNK_SIZE.trailing_zeros());
+const GEAR: [u64; 256] = gear();
+
+/// `Storage` of the version of new commits in a state file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Storage {
+    /// the delta from the version of the first parent, see [`crate::Delta`]
+    #[default]
+    Deltas,
+    /// the list of chunks of the version, see [`split`]
+    Chunks,
+}
+impl Display for Storage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Storage::Deltas => "deltas",
+                Storage::Chunks => "chunks",
+            }
+        )
+    }
+}
+impl FromStr for Storage {
+    type Err = Error;
+
+    fn from_str(storage: &str) -> Result<Storage> {
+        match storage {
+            "deltas" => Ok(Storage::Deltas),
+            "chunks" => Ok(Storage::Chunks),
+            storage => Err(Error::StateError(format!(
+                "invalid storage {:#?}, expected deltas or chunks",
+                storage
+            ))),
+        }
+    }
+}
+
+/// `cut` returns the length of the first chunk of `bytes` according
+/// to a gear rolling hash of the bytes after [`MIN_CHUNK_SIZE`]
+pub fn cut(bytes: &[u8]) -> usize {
+    let end = bytes.len().min(MAX_CHUNK_SIZE);
+    if end <= MIN_CHUNK_SIZE {
+        return end;
+    }
+    let mut hash = 0u64;
+    for (offset, byte) in bytes[..end].iter().enumerate().skip(MIN_CHUNK_SIZE) {
+        hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+        if hash & MASK == 0 {
+            return offset + 1;
+        }
+    }
+    end
+}
+
+/// `split` returns the chunks of the given version in order, see [`cut`]
+pub fn split(version: &[u8]) -> Vec<&[u8]> {
+    let mut chunks = Vec::<&[u8]>::new();
+    let mut rest = version;
+    while !rest.is_empty() {
+        let (chunk, next) = rest.split_at(cut(rest));
+        chunks.push(chunk);
+        rest = next;
+    }
+    chunks
+}
+
+/// `ChunkReader` splits the bytes of a reader into the same chunks
+/// as [`split`] while holding at most [`MAX_CHUNK_SIZE`] bytes of it
+/// besides the chunk being returned
+#[derive(Debug)]
+pub struct ChunkReader<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+    filled: usize,
+    exhausted: bool,
+}
+impl<R: Read> ChunkReader<R> {
+    pub fn new(reader: R) -> ChunkReader<R> {
+        ChunkReader {
+            reader,
+            buffer: vec![0u8; MAX_CHUNK_SIZE],
+            filled: 0,
+            exhausted: false,
+        }
+    }
+
+    /// `fill` reads until the buffer is full or the reader is exhausted
+    fn fill(&mut self) -> Result<()> {
+        while !self.exhausted && self.filled < self.buffer.len() {
+            match self.reader.read(&mut self.buffer[self.filled..]) {
+                Ok(0) => self.exhausted = true,
+                Ok(read) => self.filled += read,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {},
+                Err(error) => return Err(error.into()),
+            }
+        }
+        Ok(())
+    }
+}
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Result<Vec<u8>>> {
+        if let Err(error) = self.fill() {
+            return Some(Err(error));
+        }
+        if self.filled == 0 {
+            return None;
+        }
+        let end = cut(&self.buffer[..self.filled]);
+        let chunk = self.buffer[..end].to_vec();
+        self.buffer.copy_within(end..self.filled, 0);
+        self.filled -= end;
+        Some(Ok(chunk))
+    }
+}
+
+/// `gear` returns the table of the rolling hash, generated by splitmix64
+const fn gear() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut seed = 0x6F66_7672_6368_756Eu64;
+    let mut index = 0;
+    while index < table.len() {
+        seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut value = seed;
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[index] = value ^ (value >> 31);
+        index += 1;
+    }
+    table
+}
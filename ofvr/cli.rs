@@ -1,8 +1,24 @@
-use clap::{Args, Parser, Subcommand};
+use std::io::Write;
+use std::ops::Range;
+use std::process::ExitCode;
+use std::time::Duration;
+
 use bt_diff::{AxisBoundary, Diff};
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use iocore::Path;
+use serde::Serialize;
+use serde_json::json;
 
-use crate::{Author, Conf, FileSystemBytes, OFVRState, Result};
+use crate::blame::parse_byte_range;
+use crate::format::FORMAT_VERSION;
+use crate::lock::DEFAULT_LOCK_TIMEOUT;
+use crate::revision::{parse_date, parse_range, parse_until};
+use crate::{
+    read_data, write_atomic, Author, CommitIndex, Conf, Conflict, Error, FileSystemBytes,
+    FsckReport, Identity, KeyframePolicy, LogQuery, OFVRState, Pattern, PlainBytes, Result, Side,
+    Status, StatusReport, Storage,
+};
 
 #[derive(Parser, Debug)]
 #[command()]
@@ -12,11 +28,75 @@ pub struct Cli {
 
     #[arg(short, long)]
     pub conf_path: Option<Path>,
+
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+
+    /// Seconds to wait for other processes modifying the same state
+    /// file, 0 fails right away when the state file is locked
+    #[arg(long, global = true, env = "OFVR_LOCK_TIMEOUT", value_parser = parse_seconds)]
+    pub lock_timeout: Option<Duration>,
 }
 impl Cli {
     pub fn conf_path(&self) -> Path {
         self.conf_path.clone().unwrap_or_else(|| Conf::default_path())
     }
+
+    pub fn lock_timeout(&self) -> Duration {
+        self.lock_timeout.unwrap_or(DEFAULT_LOCK_TIMEOUT)
+    }
+}
+
+fn parse_seconds(seconds: &str) -> std::result::Result<Duration, String> {
+    seconds
+        .parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("invalid amount of seconds: {}", seconds))
+}
+
+/// `Format` of the output of every command.
+///
+/// With `Format::Json` successful results are printed to stdout and
+/// errors are printed to stderr as `{"variant": ..., "message": ...}`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+impl Format {
+    /// `from_args` returns the format requested by the given command
+    /// line arguments, for reporting errors of arguments that clap
+    /// could not parse
+    pub fn from_args<T: AsRef<str>>(args: &[T]) -> Format {
+        let json = args
+            .windows(2)
+            .any(|pair| pair[0].as_ref() == "--format" && pair[1].as_ref() == "json")
+            || args.iter().any(|arg| arg.as_ref() == "--format=json");
+        if json {
+            Format::Json
+        } else {
+            Format::Text
+        }
+    }
+
+    pub fn print<T: Serialize>(&self, value: &T) -> Result<()> {
+        println!("{}", serde_json::to_string_pretty(value)?);
+        Ok(())
+    }
+
+    pub fn print_error(&self, error: &Error) {
+        match self {
+            Format::Text => match error {
+                Error::ArgumentError(usage) => eprintln!("{}", usage),
+                error => eprintln!("Error: {:?}", error),
+            },
+            Format::Json => eprintln!(
+                "{}",
+                serde_json::to_string_pretty(error).unwrap_or_else(|_| format!("{:?}", error))
+            ),
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
@@ -26,6 +106,27 @@ pub enum Command {
     Log(LogOpt),
     Diff(DiffOpt),
     Matches(MatchesOpt),
+    Status(StatusOpt),
+    #[command(alias = "restore")]
+    Checkout(CheckoutOpt),
+    Upgrade(UpgradeOpt),
+    Compact(CompactOpt),
+    Repack(RepackOpt),
+    Stats(StatsOpt),
+    Storage(StorageOpt),
+    Fsck(FsckOpt),
+    Branch(BranchOpt),
+    Merge(MergeOpt),
+    Tag(TagOpt),
+    Revert(RevertOpt),
+    Amend(AmendOpt),
+    Reset(ResetOpt),
+    Squash(SquashOpt),
+    Oplog(OplogOpt),
+    Undo(UndoOpt),
+    Blame(BlameOpt),
+    Search(SearchOpt),
+    Identify(IdentifyOpt),
 }
 
 #[derive(Args, Debug)]
@@ -38,10 +139,41 @@ pub struct ConfOpt {
 pub enum ConfCommand {
     Get(ConfGetOpt),
     Init(ConfInitOpt),
+    Set(ConfSetOpt),
 }
 #[derive(Args, Debug)]
 pub struct ConfGetOpt {}
 
+/// Changes the settings of an existing config
+#[derive(Args, Debug)]
+pub struct ConfSetOpt {
+    #[command(flatten)]
+    pub keyframes: KeyframeOpt,
+}
+
+/// `KeyframeOpt` overrides the settings of a [`KeyframePolicy`]
+#[derive(Args, Debug)]
+pub struct KeyframeOpt {
+    /// Amount of deltas after which a commit gets a keyframe, 0
+    /// disables it
+    #[arg(long)]
+    pub keyframe_interval: Option<usize>,
+
+    /// Total size in bytes of the deltas after which a commit gets a
+    /// keyframe, 0 disables it
+    #[arg(long)]
+    pub keyframe_threshold: Option<u64>,
+}
+impl KeyframeOpt {
+    pub fn policy(&self, policy: KeyframePolicy) -> KeyframePolicy {
+        KeyframePolicy::new(
+            self.keyframe_interval.unwrap_or(policy.interval),
+            self.keyframe_threshold.unwrap_or(policy.threshold),
+        )
+    }
+}
+
+/// Creates a config holding the author of commits and the keyframe settings
 #[derive(Args, Debug)]
 pub struct ConfInitOpt {
     #[arg()]
@@ -52,6 +184,9 @@ pub struct ConfInitOpt {
 
     #[arg(short = 'f', long)]
     pub overwrite: bool,
+
+    #[command(flatten)]
+    pub keyframes: KeyframeOpt,
 }
 impl ConfInitOpt {
     pub fn author_email(&self) -> String {
@@ -73,6 +208,16 @@ pub struct CommitOpt {
 
     #[arg(short, long)]
     pub ofvr_state_path: Option<Path>,
+
+    /// Branch to commit to instead of the current branch
+    #[arg(short, long)]
+    pub branch: Option<String>,
+
+    /// Storage of the version of this and later commits, deltas or
+    /// chunks, see `ofvr storage`. Files of 64 MiB or more are
+    /// streamed and stored as chunks whatever the storage
+    #[arg(long)]
+    pub storage: Option<Storage>,
 }
 impl CommitOpt {
     pub fn ofvr_state_path(&self) -> Path {
@@ -83,23 +228,32 @@ impl CommitOpt {
     }
 
     pub fn commit_author(&self, conf_path: &Path) -> Result<Author> {
-        if !conf_path.exists() {
-            eprintln!(
-                "{} does not exist. Initialize a new config with `ofvr conf init'",
-                &conf_path
-            );
-            std::process::exit(1);
-        }
-        let conf = Conf::load_from_file(conf_path)?;
-        Ok(conf.author())
+        conf_author(conf_path)
     }
 }
 
+fn conf_author(conf_path: &Path) -> Result<Author> {
+    Ok(load_conf(conf_path)?.author())
+}
+
+fn load_conf(conf_path: &Path) -> Result<Conf> {
+    if !conf_path.exists() {
+        return Err(Error::IOError(format!(
+            "{} does not exist. Initialize a new config with `ofvr conf init'",
+            &conf_path
+        )));
+    }
+    Conf::load_from_file(conf_path)
+}
+
 #[derive(Args, Debug)]
 pub struct MatchesOpt {
     #[arg()]
     pub from_file: Path,
 
+    #[arg()]
+    pub revision: Option<String>,
+
     #[arg(short, long)]
     pub ofvr_state_path: Option<Path>,
 }
@@ -111,15 +265,77 @@ impl MatchesOpt {
             .unwrap()
     }
 }
+
+/// Compares a file with a commit of its state file (defaults to
+/// `HEAD`). Exits with 0 when clean, 1 on errors, 2 when modified, 3
+/// when untracked and 4 when the state file is missing
+#[derive(Args, Debug)]
+pub struct StatusOpt {
+    #[arg()]
+    pub from_file: Path,
+
+    #[arg()]
+    pub revision: Option<String>,
+
+    #[arg(short, long)]
+    pub ofvr_state_path: Option<Path>,
+}
+impl StatusOpt {
+    pub fn ofvr_state_path(&self) -> Path {
+        self.ofvr_state_path
+            .clone()
+            .or_else(|| Some(self.from_file.with_extension(".ofvr")))
+            .unwrap()
+    }
+}
 #[derive(Args, Debug)]
 pub struct LogOpt {
     #[arg()]
     pub ofvr_state_path: Path,
+
+    #[arg(short = 'n', long)]
+    pub max_count: Option<usize>,
+
+    #[arg(long)]
+    pub reverse: bool,
+
+    #[arg(long)]
+    pub author: Option<String>,
+
+    #[arg(long, value_parser = parse_date)]
+    pub since: Option<DateTime<Utc>>,
+
+    #[arg(long, value_parser = parse_until)]
+    pub until: Option<DateTime<Utc>>,
+
+    #[arg(long)]
+    pub grep: Option<String>,
+
+    /// Branch whose history is shown instead of the current branch
+    #[arg(short, long, conflicts_with = "all")]
+    pub branch: Option<String>,
+
+    /// Show the commits of every branch
+    #[arg(long)]
+    pub all: bool,
 }
 impl LogOpt {
     pub fn ofvr_state_path(&self) -> Path {
         self.ofvr_state_path.clone()
     }
+
+    pub fn query(&self) -> LogQuery {
+        LogQuery {
+            max_count: self.max_count,
+            reverse: self.reverse,
+            author: self.author.clone(),
+            since: self.since,
+            until: self.until,
+            grep: self.grep.clone(),
+            branch: self.branch.clone(),
+            all: self.all,
+        }
+    }
 }
 #[derive(Args, Debug)]
 pub struct DiffOpt {
@@ -128,6 +344,10 @@ pub struct DiffOpt {
 
     #[arg(short, long)]
     pub ofvr_state_path: Option<Path>,
+
+    /// Branch to compare with instead of the current branch
+    #[arg(short, long)]
+    pub branch: Option<String>,
 }
 impl DiffOpt {
     pub fn ofvr_state_path(&self) -> Path {
@@ -138,86 +358,1116 @@ impl DiffOpt {
     }
 }
 
-pub fn go(args: Cli) -> Result<()> {
+#[derive(Args, Debug)]
+pub struct CheckoutOpt {
+    #[arg()]
+    pub from_file: Path,
+
+    #[arg()]
+    pub revision: Option<String>,
+
+    #[arg(short, long)]
+    pub ofvr_state_path: Option<Path>,
+
+    #[arg(short, long = "to", conflicts_with = "stdout")]
+    pub target_path: Option<Path>,
+
+    /// Writes the version to stdout, streamed unless it is stored as a
+    /// delta, which only versions shorter than the stream threshold are
+    #[arg(long)]
+    pub stdout: bool,
+
+    #[arg(short, long)]
+    pub force: bool,
+}
+impl CheckoutOpt {
+    pub fn ofvr_state_path(&self) -> Path {
+        self.ofvr_state_path
+            .clone()
+            .or_else(|| Some(self.from_file.with_extension(".ofvr")))
+            .unwrap()
+    }
+
+    pub fn revision(&self) -> String {
+        self.revision.clone().unwrap_or_else(|| String::from("HEAD"))
+    }
+
+    pub fn target_path(&self) -> Path {
+        self.target_path.clone().unwrap_or_else(|| self.from_file.clone())
+    }
+}
+
+/// Rewrites a state file in the current on-disk format
+#[derive(Args, Debug)]
+pub struct UpgradeOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+}
+impl UpgradeOpt {
+    pub fn ofvr_state_path(&self) -> Path {
+        self.ofvr_state_path.clone()
+    }
+}
+
+/// Rewrites the journal of a state file as a single snapshot, after
+/// backing it up, see `undo`
+#[derive(Args, Debug)]
+pub struct CompactOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+}
+impl CompactOpt {
+    pub fn ofvr_state_path(&self) -> Path {
+        self.ofvr_state_path.clone()
+    }
+}
+
+/// Stores keyframes in a state file for the commits due one according
+/// to the keyframe settings of the config, see `ofvr conf set`
+#[derive(Args, Debug)]
+pub struct RepackOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+
+    #[command(flatten)]
+    pub keyframes: KeyframeOpt,
+}
+
+/// Shows how many commits of a state file reference the version of
+/// an earlier commit rather than storing it again
+#[derive(Args, Debug)]
+pub struct StatsOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+}
+
+/// Shows or changes how a state file stores the version of new
+/// commits: as deltas from their parent or as chunks stored once,
+/// which suits large files whose bytes shift between versions
+#[derive(Args, Debug)]
+pub struct StorageOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+
+    /// deltas or chunks
+    #[arg()]
+    pub storage: Option<Storage>,
+}
+
+/// Verifies the integrity of a state file. Exits with 0 when no
+/// problems were found, 1 when the state file cannot be loaded and 2
+/// when commits are corrupted
+#[derive(Args, Debug)]
+pub struct FsckOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+}
+impl FsckOpt {
+    pub fn ofvr_state_path(&self) -> Path {
+        self.ofvr_state_path.clone()
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct BranchOpt {
+    #[command(subcommand)]
+    pub command: BranchCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BranchCommand {
+    Create(BranchCreateOpt),
+    List(BranchListOpt),
+    Delete(BranchDeleteOpt),
+    Switch(BranchSwitchOpt),
+}
+
+/// Creates a branch starting at a revision (defaults to `HEAD`)
+#[derive(Args, Debug)]
+pub struct BranchCreateOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+
+    #[arg()]
+    pub name: String,
+
+    #[arg()]
+    pub revision: Option<String>,
+}
+impl BranchCreateOpt {
+    pub fn revision(&self) -> String {
+        self.revision.clone().unwrap_or_else(|| String::from("HEAD"))
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct BranchListOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+}
+
+#[derive(Args, Debug)]
+pub struct BranchDeleteOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+
+    #[arg()]
+    pub name: String,
+}
+
+/// Makes a branch the current branch of a state file, the working
+/// file is left untouched, see `checkout`
+#[derive(Args, Debug)]
+pub struct BranchSwitchOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+
+    #[arg()]
+    pub name: String,
+}
+
+/// Undoes a revision with a new commit restoring the version of its
+/// parent, or of the revision itself with --to, and writes it into
+/// the file
+#[derive(Args, Debug)]
+pub struct RevertOpt {
+    #[arg()]
+    pub from_file: Path,
+
+    #[arg()]
+    pub revision: String,
+
+    #[arg(short, long)]
+    pub ofvr_state_path: Option<Path>,
+
+    /// Restore the version of the revision instead of its parent
+    #[arg(long)]
+    pub to: bool,
+
+    /// Overwrite the file even when its contents were never committed
+    #[arg(short, long)]
+    pub force: bool,
+}
+impl RevertOpt {
+    pub fn ofvr_state_path(&self) -> Path {
+        self.ofvr_state_path
+            .clone()
+            .or_else(|| Some(self.from_file.with_extension(".ofvr")))
+            .unwrap()
+    }
+}
+
+/// Changes the message and/or the author of the head of the current
+/// branch of a state file after backing it up
+#[derive(Args, Debug)]
+pub struct AmendOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+
+    #[arg(short = 'm', long = "message")]
+    pub commit_message: Option<String>,
+
+    /// Replace the author with the author of the configuration
+    #[arg(long)]
+    pub reset_author: bool,
+}
+
+/// Drops the commits of the current branch of a state file after a
+/// revision after backing it up, the file is left untouched, see
+/// `checkout`
+#[derive(Args, Debug)]
+pub struct ResetOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+
+    #[arg()]
+    pub revision: String,
+}
+
+/// Collapses the commits of the current branch of a state file in a
+/// range `A..B`, after `A` up to and including `B`, into a single
+/// commit after backing it up
+#[derive(Args, Debug)]
+pub struct SquashOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+
+    #[arg()]
+    pub range: String,
+
+    #[arg(short = 'm', long = "message", env = "OFVR_COMMIT_MESSAGE")]
+    pub commit_message: String,
+}
+
+/// Lists the operations logged for a state file from the oldest to
+/// the latest along with their index, see `undo`
+#[derive(Args, Debug)]
+pub struct OplogOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+}
+
+/// Restores a state file as it was before a logged operation, undoing
+/// it along with every later operation, after backing it up
+#[derive(Args, Debug)]
+pub struct UndoOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+
+    /// Index of the operation in `oplog`, defaults to the latest
+    #[arg()]
+    pub index: Option<usize>,
+}
+
+/// Shows the commit and author that last changed each contiguous run
+/// of bytes of a version
+#[derive(Args, Debug)]
+pub struct BlameOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+
+    #[arg(default_value = "HEAD")]
+    pub revision: String,
+
+    /// Byte range as START:END, e.g. 0x4000:0x4100, END defaults to
+    /// the end of the version
+    #[arg(short, long, value_parser = parse_byte_range)]
+    pub range: Option<Range<usize>>,
+}
+
+/// Lists the commits that changed the amount of occurrences of a
+/// pattern along with the offsets of the occurrences they added or
+/// removed
+#[derive(Args, Debug)]
+pub struct SearchOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+
+    /// Hex bytes, e.g. 7f454c46, unless --text or --regex is given
+    #[arg()]
+    pub pattern: String,
+
+    /// Search the UTF-8 bytes of the pattern
+    #[arg(long, conflicts_with = "regex")]
+    pub text: bool,
+
+    /// Search a regular expression
+    #[arg(long)]
+    pub regex: bool,
+}
+impl SearchOpt {
+    pub fn pattern(&self) -> Result<Pattern> {
+        if self.text {
+            Pattern::text(&self.pattern)
+        } else if self.regex {
+            Pattern::regex(&self.pattern)
+        } else {
+            Pattern::hex(&self.pattern)
+        }
+    }
+}
+
+/// Finds the commits whose version is identical to a file or else the
+/// nearest version, exits with 2 when no version is identical
+#[derive(Args, Debug)]
+pub struct IdentifyOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+
+    #[arg()]
+    pub file: Path,
+}
+
+#[derive(Args, Debug)]
+pub struct TagOpt {
+    #[command(subcommand)]
+    pub command: TagCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TagCommand {
+    Add(TagAddOpt),
+    List(TagListOpt),
+    Delete(TagDeleteOpt),
+}
+
+/// Tags a revision (defaults to `HEAD`), tags with a message also
+/// record the author of the configuration as their tagger
+#[derive(Args, Debug)]
+pub struct TagAddOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+
+    #[arg()]
+    pub name: String,
+
+    #[arg()]
+    pub revision: Option<String>,
+
+    #[arg(short = 'm', long = "message")]
+    pub message: Option<String>,
+}
+impl TagAddOpt {
+    pub fn revision(&self) -> String {
+        self.revision.clone().unwrap_or_else(|| String::from("HEAD"))
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct TagListOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+}
+
+#[derive(Args, Debug)]
+pub struct TagDeleteOpt {
+    #[arg()]
+    pub ofvr_state_path: Path,
+
+    #[arg()]
+    pub name: String,
+}
+
+/// Merges a revision, usually a branch, into the current branch of a
+/// state file with a merge commit and writes the merged version into
+/// the file. Exits with 2 without committing when conflicts are left
+/// unresolved
+#[derive(Args, Debug)]
+pub struct MergeOpt {
+    #[arg()]
+    pub from_file: Path,
+
+    #[arg()]
+    pub revision: String,
+
+    #[arg(short = 'm', long = "message", env = "OFVR_COMMIT_MESSAGE")]
+    pub commit_message: Option<String>,
+
+    #[arg(short, long)]
+    pub ofvr_state_path: Option<Path>,
+
+    /// Side resolving the conflicts not resolved with --pick
+    #[arg(long)]
+    pub take: Option<Side>,
+
+    /// Side resolving the conflict starting at an offset, e.g. 1024=theirs
+    #[arg(long, value_parser = parse_pick)]
+    pub pick: Vec<(usize, Side)>,
+
+    /// File holding the merged version, conflicts are then ignored
+    #[arg(long, conflicts_with_all = ["take", "pick"])]
+    pub resolved: Option<Path>,
+
+    /// Overwrite the file even when its contents were never committed
+    #[arg(short, long)]
+    pub force: bool,
+}
+impl MergeOpt {
+    pub fn ofvr_state_path(&self) -> Path {
+        self.ofvr_state_path
+            .clone()
+            .or_else(|| Some(self.from_file.with_extension(".ofvr")))
+            .unwrap()
+    }
+
+    pub fn commit_message(&self, branch: &str) -> String {
+        self.commit_message
+            .clone()
+            .unwrap_or_else(|| format!("Merge {} into {}", self.revision, branch))
+    }
+}
+
+fn parse_pick(pick: &str) -> std::result::Result<(usize, Side), String> {
+    let (offset, side) = pick
+        .split_once('=')
+        .ok_or_else(|| format!("invalid pick {:#?}, expected OFFSET=ours or OFFSET=theirs", pick))?;
+    let offset = offset.parse::<usize>().map_err(|_| format!("invalid offset: {}", offset))?;
+    let side = side.parse::<Side>().map_err(|error| error.to_string())?;
+    Ok((offset, side))
+}
+
+fn load_state(path: &Path) -> Result<OFVRState> {
+    if path.is_file() {
+        OFVRState::from_path(path)
+    } else {
+        Err(Error::IOError(format!("{} is not a file", path)))
+    }
+}
+
+/// `load_index` reads the commit index of the state file at `path`
+/// without loading the state file when the index is up to date, see
+/// [`CommitIndex::load`]
+fn load_index(path: &Path) -> Result<CommitIndex> {
+    if path.is_file() {
+        CommitIndex::load(path)
+    } else {
+        Err(Error::IOError(format!("{} is not a file", path)))
+    }
+}
+
+/// `check_working_file` refuses to let a command overwrite a file
+/// whose contents were never committed unless `force` is true
+fn check_working_file(ofvr: &OFVRState, path: &Path, force: bool) -> Result<()> {
+    if !force && path.is_file() && !ofvr.is_recorded(&read_data(path)?)? {
+        return Err(Error::CheckoutError(format!(
+            "{} has changes not committed to {}",
+            path,
+            ofvr.path()
+        )));
+    }
+    Ok(())
+}
+
+/// `HexWriter` writes the hexadecimal encoding of the bytes written
+/// into it
+struct HexWriter<W: Write>(W);
+impl<W: Write> Write for HexWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.write_all(hex::encode(bytes).as_bytes())?;
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+fn diff_to_json(diff: &Diff) -> serde_json::Value {
+    let mut runs = Vec::<(usize, usize, Vec<u8>, Vec<u8>)>::new();
+    for (index, unit) in diff.sequence.iter().enumerate() {
+        if unit.anterior() == unit.current() {
+            continue;
+        }
+        match runs.last_mut() {
+            Some((_, end, anterior, current)) if *end == index => {
+                *end += 1;
+                anterior.extend(unit.anterior());
+                current.extend(unit.current());
+            },
+            _ => runs.push((
+                index,
+                index + 1,
+                unit.anterior().into_iter().collect(),
+                unit.current().into_iter().collect(),
+            )),
+        }
+    }
+    json!({
+        "anterior_length": diff.anterior_version().len(),
+        "current_length": diff.current_version().len(),
+        "changes": runs
+            .iter()
+            .map(|(offset, _, anterior, current)| json!({
+                "offset": offset,
+                "anterior": hex::encode(anterior),
+                "current": hex::encode(current),
+            }))
+            .collect::<Vec<serde_json::Value>>(),
+    })
+}
+
+fn conflict_to_json(conflict: &Conflict) -> serde_json::Value {
+    json!({
+        "offset": conflict.offset,
+        "end": conflict.end,
+        "ancestor": hex::encode(&conflict.ancestor),
+        "ours": hex::encode(&conflict.ours),
+        "theirs": hex::encode(&conflict.theirs),
+        "resolution": conflict.resolution,
+    })
+}
+
+pub fn go(args: Cli) -> Result<ExitCode> {
     let path = args.conf_path();
+    let format = args.format;
+    let lock_timeout = args.lock_timeout();
     match args.command {
         Command::Conf(op) => match op.command {
             ConfCommand::Init(iop) => {
                 if !iop.overwrite && path.canonicalize()?.is_file() {
-                    eprintln!("{} exists", path);
-                    std::process::exit(1);
+                    return Err(Error::IOError(format!("{} exists", path)));
                 }
                 let author = Author::new(&iop.author_name(), &iop.author_email());
-                let conf = Conf::new(author);
+                let mut conf = Conf::new(author);
+                conf.set_keyframes(iop.keyframes.policy(conf.keyframes()));
                 conf.save_to_file(&path)?;
-                println!("initialized {}", path);
+                match format {
+                    Format::Text => println!("initialized {}", path),
+                    Format::Json => format.print(&json!({ "initialized": path.to_string() }))?,
+                }
             },
             ConfCommand::Get(_) => {
                 if !path.canonicalize()?.is_file() {
-                    eprintln!("{} does not exist", path);
-                    std::process::exit(1);
+                    return Err(Error::IOError(format!("{} does not exist", path)));
                 }
                 let conf = Conf::load_from_file(&path)?;
-                println!("{}", toml::to_string(&conf)?);
+                match format {
+                    Format::Text => println!("{}", toml::to_string(&conf)?),
+                    Format::Json => format.print(&conf)?,
+                }
+            },
+            ConfCommand::Set(sop) => {
+                let mut conf = load_conf(&path)?;
+                conf.set_keyframes(sop.keyframes.policy(conf.keyframes()));
+                conf.save_to_file(&path)?;
+                match format {
+                    Format::Text => println!("updated {}", path),
+                    Format::Json => format.print(&conf)?,
+                }
             },
         },
         Command::Commit(op) => {
-            let author = op.commit_author(&path)?;
+            let conf = load_conf(&path)?;
+            let author = conf.author();
             let mut ofvr = if op.ofvr_state_path().is_file() {
-                OFVRState::from_path(&op.ofvr_state_path()).expect("state from path")
+                OFVRState::from_path(&op.ofvr_state_path())?
             } else {
-                OFVRState::empty(&op.ofvr_state_path(), &author).expect("empty state")
+                OFVRState::empty(&op.ofvr_state_path(), &author)?
             };
-            ofvr.commit(&op.from_file, &author, &op.commit_message).expect("commit");
-            let commit = ofvr.latest_commit().expect("latest commit");
-            println!("{}", commit.log(&ofvr).expect("log"));
+            ofvr.set_lock_timeout(lock_timeout);
+            ofvr.set_keyframe_policy(conf.keyframes());
+            if let Some(storage) = op.storage {
+                ofvr.set_storage(storage)?;
+            }
+            let commit = match &op.branch {
+                Some(branch) =>
+                    ofvr.commit_on(branch, &op.from_file, &author, &op.commit_message)?,
+                None => ofvr.commit(&op.from_file, &author, &op.commit_message)?,
+            };
+            match format {
+                Format::Text => println!("{}", commit.log(&ofvr)?),
+                Format::Json => format.print(&commit.to_json(&ofvr)?)?,
+            }
         },
         Command::Matches(op) => {
-            let ofvr = if op.ofvr_state_path().is_file() {
-                OFVRState::from_path(&op.ofvr_state_path())?
-            } else {
-                eprintln!("{} is not a file", op.ofvr_state_path());
-                std::process::exit(1);
-            };
-            let plain_bytes = op.from_file.read_bytes()?;
-            let current_bytes = match ofvr.latest_commit() {
-                Some(commit) => commit.data(&ofvr)?.diff(),
-                None => Diff::new(AxisBoundary::default()),
+            let report =
+                StatusReport::new(&op.from_file, &op.ofvr_state_path(), op.revision.as_deref())?;
+            match format {
+                Format::Text =>
+                    if report.status == Status::Clean {
+                        println!(
+                            "{} matches {} in {}",
+                            op.from_file,
+                            report.commit.clone().unwrap_or_default(),
+                            op.ofvr_state_path()
+                        );
+                    } else {
+                        eprintln!("{}", report.summary());
+                    },
+                Format::Json => format.print(&report)?,
             }
-            .current_version();
-
-            if plain_bytes == current_bytes {
-                println!("{} matches latest version in {}", op.from_file, op.ofvr_state_path());
-            } else {
-                panic!("{} mismatch", op.from_file);
+            return Ok(ExitCode::from(report.status.exit_code()));
+        },
+        Command::Status(op) => {
+            let report =
+                StatusReport::new(&op.from_file, &op.ofvr_state_path(), op.revision.as_deref())?;
+            match format {
+                Format::Text => println!("{}", report.summary()),
+                Format::Json => format.print(&report)?,
             }
+            return Ok(ExitCode::from(report.status.exit_code()));
         },
         Command::Log(op) => {
-            let ofvr = if op.ofvr_state_path().is_file() {
-                OFVRState::from_path(&op.ofvr_state_path())?
+            let index = load_index(&op.ofvr_state_path())?;
+            let entries = index.log(&op.query())?;
+            match format {
+                Format::Text =>
+                    for entry in entries.iter() {
+                        println!("{}", entry.log(&index)?);
+                    },
+                Format::Json => format.print(
+                    &entries
+                        .iter()
+                        .map(|entry| entry.to_json(&index))
+                        .collect::<Result<Vec<serde_json::Value>>>()?,
+                )?,
+            }
+        },
+        Command::Diff(op) => {
+            let ofvr = load_state(&op.ofvr_state_path())?;
+            let anterior = match &op.branch {
+                Some(branch) => match ofvr.branch_head(branch)? {
+                    Some(head) => ofvr.version(&head)?,
+                    None => Vec::new(),
+                },
+                None => ofvr.latest_version()?,
+            };
+            let diff = bt_diff::diff(
+                &anterior,
+                &op.from_file.read_bytes()?,
+                AxisBoundary::default(),
+            )?;
+            match format {
+                Format::Text => println!("{}", diff.render()),
+                Format::Json => format.print(&diff_to_json(&diff))?,
+            }
+        },
+        Command::Checkout(op) => {
+            let ofvr = load_state(&op.ofvr_state_path())?;
+            let commit = ofvr.resolve(&op.revision())?;
+            match (op.stdout, format) {
+                (true, Format::Text) => {
+                    let mut stdout = std::io::stdout().lock();
+                    ofvr.write_version(&commit, &mut stdout)?;
+                    stdout.flush()?;
+                },
+                (true, Format::Json) => {
+                    // prints what `format.print` would, without holding the version
+                    let mut stdout = std::io::stdout().lock();
+                    stdout.write_all(b"{\n  \"data\": \"")?;
+                    ofvr.write_version(&commit, &mut HexWriter(&mut stdout))?;
+                    let id = serde_json::to_string(&commit.id.to_hex())?;
+                    writeln!(stdout, "\",\n  \"id\": {}\n}}", id)?;
+                    stdout.flush()?;
+                },
+                (false, _) => {
+                    ofvr.checkout_to(&commit, &op.target_path(), op.force)?;
+                    match format {
+                        Format::Text =>
+                            println!("checked out {} into {}", commit.id, op.target_path()),
+                        Format::Json => format.print(&json!({
+                            "id": commit.id.to_hex(),
+                            "path": op.target_path().to_string(),
+                        }))?,
+                    }
+                },
+            }
+        },
+        Command::Upgrade(op) => {
+            if !op.ofvr_state_path().is_file() {
+                return Err(Error::IOError(format!("{} is not a file", op.ofvr_state_path())));
+            }
+            let previous = OFVRState::upgrade(&op.ofvr_state_path(), lock_timeout)?;
+            match format {
+                Format::Text =>
+                    if previous == FORMAT_VERSION {
+                        println!(
+                            "{} already in format version {}",
+                            op.ofvr_state_path(),
+                            FORMAT_VERSION
+                        );
+                    } else {
+                        println!(
+                            "upgraded {} from format version {} to {}",
+                            op.ofvr_state_path(),
+                            previous,
+                            FORMAT_VERSION
+                        );
+                    },
+                Format::Json => format.print(&json!({
+                    "path": op.ofvr_state_path().to_string(),
+                    "previous_version": previous,
+                    "version": FORMAT_VERSION,
+                }))?,
+            }
+        },
+        Command::Compact(op) => {
+            let mut ofvr = load_state(&op.ofvr_state_path())?;
+            ofvr.set_lock_timeout(lock_timeout);
+            let (before, after) = ofvr.compact()?;
+            match format {
+                Format::Text => println!(
+                    "compacted {} from {} to {} bytes",
+                    op.ofvr_state_path(),
+                    before,
+                    after
+                ),
+                Format::Json => format.print(&json!({
+                    "path": op.ofvr_state_path().to_string(),
+                    "before": before,
+                    "after": after,
+                }))?,
+            }
+        },
+        Command::Repack(op) => {
+            let mut ofvr = load_state(&op.ofvr_state_path)?;
+            ofvr.set_lock_timeout(lock_timeout);
+            let policy = if path.is_file() {
+                load_conf(&path)?.keyframes()
             } else {
-                eprintln!("{} is not a file", op.ofvr_state_path());
-                std::process::exit(1);
+                KeyframePolicy::default()
             };
-            for commit in ofvr.commits().iter() {
-                println!("{}", commit.log(&ofvr)?);
+            ofvr.set_keyframe_policy(op.keyframes.policy(policy));
+            let stored = ofvr.repack()?;
+            match format {
+                Format::Text =>
+                    println!("stored {} keyframes in {}", stored, op.ofvr_state_path),
+                Format::Json => format.print(&json!({
+                    "path": op.ofvr_state_path.to_string(),
+                    "stored": stored,
+                    "keyframes": ofvr.keyframes().len(),
+                }))?,
             }
         },
-        Command::Diff(op) => {
-            let ofvr = if op.ofvr_state_path().is_file() {
-                OFVRState::from_path(&op.ofvr_state_path())?
+        Command::Stats(op) => {
+            let stats = load_state(&op.ofvr_state_path)?.dedup_stats()?;
+            match format {
+                Format::Text => println!("{}: {}", op.ofvr_state_path, stats),
+                Format::Json => format.print(&stats)?,
+            }
+        },
+        Command::Storage(op) => {
+            let mut ofvr = load_state(&op.ofvr_state_path)?;
+            ofvr.set_lock_timeout(lock_timeout);
+            if let Some(storage) = op.storage {
+                ofvr.set_storage(storage)?;
+            }
+            match format {
+                Format::Text => println!("{}: {}", op.ofvr_state_path, ofvr.storage()),
+                Format::Json => format.print(&json!({
+                    "path": op.ofvr_state_path.to_string(),
+                    "storage": ofvr.storage().to_string(),
+                }))?,
+            }
+        },
+        Command::Fsck(op) => {
+            if !op.ofvr_state_path().is_file() {
+                return Err(Error::IOError(format!("{} is not a file", op.ofvr_state_path())));
+            }
+            let report = FsckReport::new(&op.ofvr_state_path())?;
+            match format {
+                Format::Text => {
+                    for problem in report.problems.iter() {
+                        eprintln!("{}", problem);
+                    }
+                    println!("{}", report.summary());
+                },
+                Format::Json => format.print(&report)?,
+            }
+            return Ok(ExitCode::from(report.exit_code()));
+        },
+        Command::Branch(op) => match op.command {
+            BranchCommand::List(lop) => {
+                let ofvr = load_state(&lop.ofvr_state_path)?;
+                let current = ofvr.current_branch();
+                match format {
+                    Format::Text =>
+                        for (name, head) in ofvr.branches().iter() {
+                            let marker = if *name == current { "*" } else { " " };
+                            println!("{} {} {}", marker, name, head);
+                        },
+                    Format::Json => format.print(
+                        &ofvr
+                            .branches()
+                            .iter()
+                            .map(|(name, head)| {
+                                json!({
+                                    "name": name,
+                                    "head": head.to_hex(),
+                                    "current": *name == current,
+                                })
+                            })
+                            .collect::<Vec<serde_json::Value>>(),
+                    )?,
+                }
+            },
+            BranchCommand::Create(cop) => {
+                let mut ofvr = load_state(&cop.ofvr_state_path)?;
+                ofvr.set_lock_timeout(lock_timeout);
+                let head = ofvr.resolve(&cop.revision())?;
+                ofvr.create_branch(&cop.name, &head)?;
+                match format {
+                    Format::Text => println!("created branch {} at {}", cop.name, head.id),
+                    Format::Json => format.print(&json!({
+                        "name": cop.name,
+                        "head": head.id.to_hex(),
+                    }))?,
+                }
+            },
+            BranchCommand::Delete(dop) => {
+                let mut ofvr = load_state(&dop.ofvr_state_path)?;
+                ofvr.set_lock_timeout(lock_timeout);
+                let head = ofvr.delete_branch(&dop.name)?;
+                match format {
+                    Format::Text => println!("deleted branch {} (was {})", dop.name, head),
+                    Format::Json => format.print(&json!({
+                        "name": dop.name,
+                        "head": head.to_hex(),
+                    }))?,
+                }
+            },
+            BranchCommand::Switch(sop) => {
+                let mut ofvr = load_state(&sop.ofvr_state_path)?;
+                ofvr.set_lock_timeout(lock_timeout);
+                ofvr.switch_branch(&sop.name)?;
+                let head = ofvr.latest_commit().map(|commit| commit.id.to_hex()).unwrap_or_default();
+                match format {
+                    Format::Text => println!("switched to branch {} at {}", sop.name, head),
+                    Format::Json => format.print(&json!({
+                        "name": sop.name,
+                        "head": head,
+                    }))?,
+                }
+            },
+        },
+        Command::Merge(op) => {
+            let conf = load_conf(&path)?;
+            let author = conf.author();
+            let mut ofvr = load_state(&op.ofvr_state_path())?;
+            ofvr.set_lock_timeout(lock_timeout);
+            ofvr.set_keyframe_policy(conf.keyframes());
+            let theirs = ofvr.resolve(&op.revision)?;
+            let data = match &op.resolved {
+                Some(resolved) => read_data(resolved)?,
+                None => {
+                    let mut merge = ofvr.merge(&theirs)?;
+                    for (offset, side) in op.pick.iter() {
+                        merge.resolve(*offset, *side)?;
+                    }
+                    if let Some(side) = op.take {
+                        merge.resolve_all(side);
+                    }
+                    if !merge.is_resolved() {
+                        let conflicts = merge
+                            .conflicts()
+                            .iter()
+                            .filter(|conflict| conflict.resolution.is_none())
+                            .collect::<Vec<&Conflict>>();
+                        match format {
+                            Format::Text => {
+                                for conflict in conflicts.iter() {
+                                    println!("{}", conflict);
+                                }
+                                eprintln!(
+                                    "{} conflicts left unresolved, see --take, --pick and --resolved",
+                                    conflicts.len()
+                                );
+                            },
+                            Format::Json => format.print(&json!({
+                                "conflicts": conflicts
+                                    .iter()
+                                    .map(|conflict| conflict_to_json(conflict))
+                                    .collect::<Vec<serde_json::Value>>(),
+                            }))?,
+                        }
+                        return Ok(ExitCode::from(2));
+                    }
+                    merge.result()?
+                },
+            };
+            check_working_file(&ofvr, &op.from_file, op.force)?;
+            let message = op.commit_message(&ofvr.current_branch());
+            let commit = ofvr.commit_merge(&theirs, &data, &author, &message)?;
+            write_atomic(&op.from_file, &data)?;
+            match format {
+                Format::Text => println!("{}", commit.log(&ofvr)?),
+                Format::Json => format.print(&commit.to_json(&ofvr)?)?,
+            }
+        },
+        Command::Tag(op) => match op.command {
+            TagCommand::List(lop) => {
+                let ofvr = load_state(&lop.ofvr_state_path)?;
+                match format {
+                    Format::Text =>
+                        for tag in ofvr.tags().values() {
+                            match tag.message() {
+                                Some(message) =>
+                                    println!("{} {} {}", tag.name(), tag.commit(), message),
+                                None => println!("{} {}", tag.name(), tag.commit()),
+                            }
+                        },
+                    Format::Json => format.print(
+                        &ofvr
+                            .tags()
+                            .values()
+                            .map(|tag| tag.to_json(&ofvr))
+                            .collect::<Result<Vec<serde_json::Value>>>()?,
+                    )?,
+                }
+            },
+            TagCommand::Add(aop) => {
+                let tagger = match &aop.message {
+                    Some(_) => Some(conf_author(&path)?),
+                    None => None,
+                };
+                let mut ofvr = load_state(&aop.ofvr_state_path)?;
+                ofvr.set_lock_timeout(lock_timeout);
+                let commit = ofvr.resolve(&aop.revision())?;
+                let tag =
+                    ofvr.add_tag(&aop.name, &commit, aop.message.as_deref(), tagger.as_ref())?;
+                match format {
+                    Format::Text => println!("tagged {} as {}", commit.id, tag.name()),
+                    Format::Json => format.print(&tag.to_json(&ofvr)?)?,
+                }
+            },
+            TagCommand::Delete(dop) => {
+                let mut ofvr = load_state(&dop.ofvr_state_path)?;
+                ofvr.set_lock_timeout(lock_timeout);
+                let tag = ofvr.delete_tag(&dop.name)?;
+                match format {
+                    Format::Text => println!("deleted tag {} (was {})", tag.name(), tag.commit()),
+                    Format::Json => format.print(&tag.to_json(&ofvr)?)?,
+                }
+            },
+        },
+        Command::Revert(op) => {
+            let conf = load_conf(&path)?;
+            let author = conf.author();
+            let mut ofvr = load_state(&op.ofvr_state_path())?;
+            ofvr.set_lock_timeout(lock_timeout);
+            ofvr.set_keyframe_policy(conf.keyframes());
+            let target = ofvr.resolve(&op.revision)?;
+            check_working_file(&ofvr, &op.from_file, op.force)?;
+            let commit = if op.to {
+                ofvr.revert_to(&target, &author)?
             } else {
-                eprintln!("{} is not a file", op.ofvr_state_path());
-                std::process::exit(1);
+                ofvr.revert(&target, &author)?
             };
-
-            let mut diff = match ofvr.latest_commit() {
-                Some(commit) => commit.data(&ofvr)?.diff(),
-                None => Diff::new(AxisBoundary::default()),
+            ofvr.checkout_to(&commit, &op.from_file, true)?;
+            match format {
+                Format::Text => println!("{}", commit.log(&ofvr)?),
+                Format::Json => format.print(&commit.to_json(&ofvr)?)?,
+            }
+        },
+        Command::Amend(op) => {
+            let author = if op.reset_author { Some(conf_author(&path)?) } else { None };
+            let mut ofvr = load_state(&op.ofvr_state_path)?;
+            ofvr.set_lock_timeout(lock_timeout);
+            let (commit, backup) = ofvr.amend(op.commit_message.as_deref(), author.as_ref())?;
+            match format {
+                Format::Text => {
+                    println!("{}", commit.log(&ofvr)?);
+                    eprintln!("backed up {} to {}", op.ofvr_state_path, backup);
+                },
+                Format::Json => format.print(&json!({
+                    "commit": commit.to_json(&ofvr)?,
+                    "backup": backup.to_string(),
+                }))?,
+            }
+        },
+        Command::Reset(op) => {
+            let mut ofvr = load_state(&op.ofvr_state_path)?;
+            ofvr.set_lock_timeout(lock_timeout);
+            let commit = ofvr.resolve(&op.revision)?;
+            let backup = ofvr.reset(&commit)?;
+            match format {
+                Format::Text => {
+                    println!("reset branch {} to {}", ofvr.current_branch(), commit.id);
+                    eprintln!("backed up {} to {}", op.ofvr_state_path, backup);
+                },
+                Format::Json => format.print(&json!({
+                    "branch": ofvr.current_branch(),
+                    "head": commit.id.to_hex(),
+                    "backup": backup.to_string(),
+                }))?,
+            }
+        },
+        Command::Squash(op) => {
+            let author = conf_author(&path)?;
+            let mut ofvr = load_state(&op.ofvr_state_path)?;
+            ofvr.set_lock_timeout(lock_timeout);
+            let (from, to) = parse_range(&op.range)?;
+            let (from, to) = (from.resolve(&ofvr)?, to.resolve(&ofvr)?);
+            let (commit, backup) = ofvr.squash(&from, &to, &author, &op.commit_message)?;
+            match format {
+                Format::Text => {
+                    println!("{}", commit.log(&ofvr)?);
+                    eprintln!("backed up {} to {}", op.ofvr_state_path, backup);
+                },
+                Format::Json => format.print(&json!({
+                    "commit": commit.to_json(&ofvr)?,
+                    "backup": backup.to_string(),
+                }))?,
+            }
+        },
+        Command::Oplog(op) => {
+            let operations = load_state(&op.ofvr_state_path)?.operations()?;
+            match format {
+                Format::Text => {
+                    for (index, operation) in operations.iter().enumerate() {
+                        println!("{} {}", index, operation);
+                    }
+                },
+                Format::Json => format.print(&operations)?,
+            }
+        },
+        Command::Undo(op) => {
+            let mut ofvr = load_state(&op.ofvr_state_path)?;
+            ofvr.set_lock_timeout(lock_timeout);
+            let index = match op.index {
+                Some(index) => index,
+                None => ofvr.operations()?.len().checked_sub(1).ok_or_else(|| {
+                    Error::StateError(format!("no operations logged for {}", op.ofvr_state_path))
+                })?,
             };
-            diff.update(&op.from_file.read_bytes()?)?;
-            println!("{}", diff.render());
+            let backup = ofvr.undo(index)?;
+            let head = ofvr.latest_commit().map(|commit| commit.id.to_hex());
+            match format {
+                Format::Text => {
+                    println!(
+                        "undid operation {} of {}, branch {} is at {}",
+                        index,
+                        op.ofvr_state_path,
+                        ofvr.current_branch(),
+                        head.as_deref().unwrap_or("(none)")
+                    );
+                    eprintln!("backed up {} to {}", op.ofvr_state_path, backup);
+                },
+                Format::Json => format.print(&json!({
+                    "undone": index,
+                    "branch": ofvr.current_branch(),
+                    "head": head,
+                    "backup": backup.to_string(),
+                }))?,
+            }
+        },
+        Command::Blame(op) => {
+            let ofvr = load_state(&op.ofvr_state_path)?;
+            let commit = ofvr.resolve(&op.revision)?;
+            let runs = ofvr.blame(&commit, op.range.clone())?;
+            match format {
+                Format::Text =>
+                    for run in runs.iter() {
+                        let message = run.commit.data(&ofvr)?.message();
+                        println!("{} {}", run, message.lines().next().unwrap_or_default());
+                    },
+                Format::Json => format.print(
+                    &runs
+                        .iter()
+                        .map(|run| run.to_json(&ofvr))
+                        .collect::<Result<Vec<serde_json::Value>>>()?,
+                )?,
+            }
+        },
+        Command::Identify(op) => {
+            let ofvr = load_state(&op.ofvr_state_path)?;
+            let identity = ofvr.identify_file(&op.file)?;
+            match format {
+                Format::Text => match &identity {
+                    Identity::Exact(commits) =>
+                        for commit in commits.iter() {
+                            println!("{}", commit.log(&ofvr)?);
+                        },
+                    Identity::Nearest(commit, distance) => {
+                        eprintln!(
+                            "{} matches no version, the nearest differs by {} bytes",
+                            op.file, distance
+                        );
+                        println!("{}", commit.log(&ofvr)?);
+                    },
+                    Identity::Unknown => eprintln!("{} holds no commits", op.ofvr_state_path),
+                },
+                Format::Json => format.print(&identity.to_json(&ofvr)?)?,
+            }
+            return Ok(ExitCode::from(identity.exit_code()));
+        },
+        Command::Search(op) => {
+            let ofvr = load_state(&op.ofvr_state_path)?;
+            let hits = ofvr.search(&op.pattern()?)?;
+            match format {
+                Format::Text =>
+                    for hit in hits.iter() {
+                        let message = hit.commit.data(&ofvr)?.message();
+                        println!("{} {}", hit, message.lines().next().unwrap_or_default());
+                    },
+                Format::Json => format.print(
+                    &hits
+                        .iter()
+                        .map(|hit| hit.to_json(&ofvr))
+                        .collect::<Result<Vec<serde_json::Value>>>()?,
+                )?,
+            }
         },
     }
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
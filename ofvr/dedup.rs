@@ -0,0 +1,72 @@
+//! Statistics of the content-addressed table of versions, see
+//! [`OFVRState::objects`]
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::errors::Result;
+use crate::models::state::OFVRState;
+
+/// `DedupStats` tells how much the content-addressed table of
+/// versions of a state saved
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct DedupStats {
+    pub commits: usize,
+    /// amount of distinct versions
+    pub objects: usize,
+    /// commits whose version is held by an earlier commit
+    pub deduplicated: usize,
+    /// total length of the versions of the deduplicated commits
+    pub deduplicated_bytes: u64,
+    /// total size of the hunks of the delta of every commit
+    pub stored_bytes: u64,
+    /// amount of distinct chunks in the pack, see [`crate::pack`]
+    pub chunks: usize,
+    /// total length of the distinct chunks
+    pub chunk_bytes: u64,
+}
+impl Display for DedupStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} commits, {} distinct versions, {} deduplicated commits referencing {} bytes, {} \
+             bytes of deltas, {} chunks of {} bytes",
+            self.commits,
+            self.objects,
+            self.deduplicated,
+            self.deduplicated_bytes,
+            self.stored_bytes,
+            self.chunks,
+            self.chunk_bytes
+        )
+    }
+}
+
+/// `stats` counts the commits of the state whose version is held by
+/// an earlier commit in the content-addressed table
+pub fn stats(ofvr: &OFVRState) -> Result<DedupStats> {
+    let mut stats = DedupStats {
+        commits: ofvr.commits().len(),
+        objects: ofvr.objects().len(),
+        deduplicated: 0,
+        deduplicated_bytes: 0,
+        stored_bytes: 0,
+        chunks: 0,
+        chunk_bytes: 0,
+    };
+    for (_, length) in ofvr.pack()?.iter() {
+        stats.chunks += 1;
+        stats.chunk_bytes += length as u64;
+    }
+    for commit in ofvr.commits().iter() {
+        stats.stored_bytes += commit.data(ofvr)?.delta().size() as u64;
+        let object = ofvr.digests().get(&commit.id).and_then(|digest| ofvr.objects().get(digest));
+        if let Some(object) = object {
+            if object.commit() != commit.id {
+                stats.deduplicated += 1;
+                stats.deduplicated_bytes += object.length();
+            }
+        }
+    }
+    Ok(stats)
+}
@@ -14,6 +14,16 @@ pub enum Error {
     BincodeError(String),
     TomlError(String),
     StateError(String),
+    CheckoutError(String),
+    RevisionError(String),
+    FormatError(String),
+    LockError(String),
+    BranchError(String),
+    MergeError(String),
+    TagError(String),
+    BlameError(String),
+    SearchError(String),
+    ArgumentError(String),
 }
 
 impl Serialize for Error {
@@ -43,6 +53,16 @@ impl Display for Error {
                 Self::BincodeError(e) => e.to_string(),
                 Self::TomlError(e) => e.to_string(),
                 Self::StateError(e) => e.to_string(),
+                Self::CheckoutError(e) => e.to_string(),
+                Self::RevisionError(e) => e.to_string(),
+                Self::FormatError(e) => e.to_string(),
+                Self::LockError(e) => e.to_string(),
+                Self::BranchError(e) => e.to_string(),
+                Self::MergeError(e) => e.to_string(),
+                Self::TagError(e) => e.to_string(),
+                Self::BlameError(e) => e.to_string(),
+                Self::SearchError(e) => e.to_string(),
+                Self::ArgumentError(e) => e.to_string(),
             }
         )
     }
@@ -59,6 +79,16 @@ impl Error {
             Error::BincodeError(_) => "BincodeError",
             Error::TomlError(_) => "TomlError",
             Error::StateError(_) => "StateError",
+            Error::CheckoutError(_) => "CheckoutError",
+            Error::RevisionError(_) => "RevisionError",
+            Error::FormatError(_) => "FormatError",
+            Error::LockError(_) => "LockError",
+            Error::BranchError(_) => "BranchError",
+            Error::MergeError(_) => "MergeError",
+            Error::TagError(_) => "TagError",
+            Error::BlameError(_) => "BlameError",
+            Error::SearchError(_) => "SearchError",
+            Error::ArgumentError(_) => "ArgumentError",
         }
         .to_string()
     }
@@ -98,6 +128,21 @@ impl From<toml::de::Error> for Error {
         Error::TomlError(format!("{}", e))
     }
 }
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::EncodeError(format!("{}", e))
+    }
+}
+impl From<regex::Error> for Error {
+    fn from(e: regex::Error) -> Self {
+        Error::SearchError(format!("{}", e))
+    }
+}
+impl From<clap::Error> for Error {
+    fn from(e: clap::Error) -> Self {
+        Error::ArgumentError(e.render().to_string().trim_end().to_string())
+    }
+}
 impl From<TryFromIntError> for Error {
     fn from(e: TryFromIntError) -> Self {
         Error::DecodeError(format!("{}", e))
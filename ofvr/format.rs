@@ -0,0 +1,557 @@
+//! On-disk format of state files.
+//!
+//! A state file starts with a [`Header`] of [`HEADER_LEN`] bytes:
+//!
+//! | bytes  | field                                 |
+//! |--------|---------------------------------------|
+//! | 0..4   | [`MAGIC`]                             |
+//! | 4..6   | format version, little endian `u16`   |
+//! | 6..8   | flags, little endian `u16`            |
+//! | 8..40  | keccak256 checksum, see below         |
+//!
+//! The header is followed by an append-only journal of [`Record`]s
+//! and its checksum covers the first 8 bytes of the header. Each
+//! record is laid out as:
+//!
+//! | bytes          | field                                         |
+//! |----------------|-----------------------------------------------|
+//! | 0              | kind, see [`Record::kind`]                    |
+//! | 1..9           | payload length, little endian `u64`           |
+//! | 9..9+N         | payload                                       |
+//! | 9+N..9+N+8     | first 8 bytes of keccak256 of kind, length and payload |
+//!
+//! The journal starts with a [`Record::Snapshot`] of the whole state
+//! and every later transaction appends [`Record::Author`],
+//! [`Record::Commit`], [`Record::Digest`], [`Record::Keyframe`],
+//! [`Record::Manifest`], [`Record::Storage`], [`Record::Branch`],
+//! [`Record::Switch`] and [`Record::Tag`] records terminated by a
+//! [`Record::Index`]. Each index chains the checksum of the previous
+//! one to those of the records of its transaction, see
+//! [`Index::chain`], so that the last index identifies the whole
+//! journal, see [`Journal`].
+//! Records after the last index are the remains of an interrupted
+//! append: they are ignored when reading and truncated by the next
+//! append. A record that is not valid followed by an index is damage
+//! rather than the remains of an append and fails reading instead,
+//! see [`check_tail`]. [`crate::OFVRState::compact`] rewrites the
+//! journal as a single snapshot.
+//!
+//! The chunks of versions stored as chunks, see [`crate::chunk`], are
+//! left to the pack next to the state file, see [`crate::pack`].
+//!
+//! Files without a header were written by earlier versions of ofvr
+//! and are treated as format version 0, a bincode-serialized state
+//! whose commits are converted when read, see [`OFVRState::migrate`].
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use iocore::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::Storage;
+use crate::data::DataSeq;
+use crate::errors::{Error, Result};
+use crate::hash::keccak256;
+use crate::models::author::Author;
+use crate::models::commit::Commit;
+use crate::models::id::ID;
+use crate::models::legacy::UnbranchedState;
+use crate::models::state::OFVRState;
+use crate::models::tag::Tag;
+use crate::traits::PlainBytes;
+
+pub const MAGIC: [u8; 4] = *b"OFVR";
+pub const HEADER_LEN: usize = 40;
+/// Format version written by this version of ofvr
+pub const FORMAT_VERSION: u16 = 1;
+/// Snapshots are deflate-compressed
+pub const FLAG_DEFLATE: u16 = 0x0001;
+/// Length of the kind, length and checksum surrounding each record payload
+pub const RECORD_OVERHEAD: usize = 17;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Header {
+    pub version: u16,
+    pub flags: u16,
+    pub checksum: [u8; 32],
+}
+impl Header {
+    pub fn new(version: u16, flags: u16, payload: &[u8]) -> Header {
+        let mut checksum = [0u8; 32];
+        checksum.copy_from_slice(&keccak256(payload));
+        Header {
+            version,
+            flags,
+            checksum,
+        }
+    }
+
+    /// `journal` returns the header of a journal in the current [`FORMAT_VERSION`]
+    pub fn journal(flags: u16) -> Header {
+        let mut prefix = MAGIC.to_vec();
+        prefix.extend(FORMAT_VERSION.to_le_bytes());
+        prefix.extend(flags.to_le_bytes());
+        Header::new(FORMAT_VERSION, flags, &prefix)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::<u8>::with_capacity(HEADER_LEN);
+        bytes.extend(MAGIC);
+        bytes.extend(self.version.to_le_bytes());
+        bytes.extend(self.flags.to_le_bytes());
+        bytes.extend(self.checksum);
+        bytes
+    }
+
+    /// `parse` returns the header at the start of `bytes` or `None`
+    /// when `bytes` do not start with [`MAGIC`]
+    pub fn parse(bytes: &[u8]) -> Result<Option<Header>> {
+        if !bytes.starts_with(&MAGIC) {
+            return Ok(None);
+        }
+        if bytes.len() < HEADER_LEN {
+            return Err(Error::FormatError(format!(
+                "truncated header: {} bytes out of {}",
+                bytes.len(),
+                HEADER_LEN
+            )));
+        }
+        let mut checksum = [0u8; 32];
+        checksum.copy_from_slice(&bytes[8..HEADER_LEN]);
+        Ok(Some(Header {
+            version: u16::from_le_bytes([bytes[4], bytes[5]]),
+            flags: u16::from_le_bytes([bytes[6], bytes[7]]),
+            checksum,
+        }))
+    }
+
+    pub fn has_flag(&self, flag: u16) -> bool {
+        self.flags & flag == flag
+    }
+
+    pub fn verify(&self, payload: &[u8]) -> Result<()> {
+        if keccak256(payload) != self.checksum {
+            return Err(Error::FormatError(format!(
+                "checksum mismatch: payload of {} bytes is corrupted",
+                payload.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// `Journal` identifies the valid journal of a state file by its
+/// length, the checksum of its snapshot and the offset and checksum
+/// of its last record, the index of its last transaction: rewriting
+/// the journal changes the snapshot and appending to it changes the
+/// last record, see [`Journal::starts`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Journal {
+    pub length: u64,
+    pub snapshot: [u8; 8],
+    pub last: u64,
+    pub tail: [u8; 8],
+}
+impl Journal {
+    /// `new` identifies the journal of `length` bytes at the start of
+    /// `bytes` whose snapshot ends at `snapshot` and whose last record
+    /// starts at `last`
+    fn new(bytes: &[u8], snapshot: usize, last: usize, length: usize) -> Journal {
+        let mut journal = Journal {
+            length: length as u64,
+            snapshot: [0u8; 8],
+            last: last as u64,
+            tail: [0u8; 8],
+        };
+        journal.snapshot.copy_from_slice(&bytes[snapshot - 8..snapshot]);
+        journal.tail.copy_from_slice(&bytes[length - 8..length]);
+        journal
+    }
+
+    /// `starts` returns true when the state file at `path` starts with
+    /// this journal, reading only its header, the checksum of its
+    /// snapshot and its last record, whose checksum is checked, see
+    /// [`Record::span`]
+    pub fn starts(&self, path: &Path) -> Result<bool> {
+        let mut file = File::open(path.to_path_buf())?;
+        if file.metadata()?.len() < self.length
+            || self.last < (HEADER_LEN + RECORD_OVERHEAD) as u64
+            || self.length < self.last + RECORD_OVERHEAD as u64
+        {
+            return Ok(false);
+        }
+        let mut bytes = vec![0u8; HEADER_LEN + 9];
+        file.read_exact(&mut bytes)?;
+        match Header::parse(&bytes)? {
+            Some(header)
+                if header.version == FORMAT_VERSION && header.verify(&bytes[..8]).is_ok() => {},
+            _ => return Ok(false),
+        }
+        // the journal starts with the kind and length of a snapshot record
+        let length = u64::from_le_bytes(bytes[HEADER_LEN + 1..].try_into().expect("8 bytes"));
+        let snapshot = (HEADER_LEN as u64 + 9).checked_add(length);
+        let snapshot = match snapshot {
+            Some(end) if bytes[HEADER_LEN] == 1 && end + 8 <= self.last => end,
+            _ => return Ok(false),
+        };
+        let mut checksum = [0u8; 8];
+        file.seek(SeekFrom::Start(snapshot))?;
+        file.read_exact(&mut checksum)?;
+        if checksum != self.snapshot {
+            return Ok(false);
+        }
+        let mut last = vec![0u8; usize::try_from(self.length - self.last)?];
+        file.seek(SeekFrom::Start(self.last))?;
+        file.read_exact(&mut last)?;
+        Ok(last[0] == 4
+            && Record::span(&last, 0) == Some(last.len())
+            && last[last.len() - 8..] == self.tail)
+    }
+}
+
+/// `Index` terminates every transaction appended to a journal
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Index {
+    /// amount of commits in the state once the transaction is applied
+    pub commits: u64,
+    /// ids and offsets of the commit records within the transaction
+    pub entries: Vec<(ID, u64)>,
+    /// chains the checksum of the previous index to those of the
+    /// records of the transaction, see [`Index::chain`], so that the
+    /// last index of a journal identifies all of it
+    pub chain: [u8; 8],
+}
+impl Index {
+    /// `chain` returns the first 8 bytes of keccak256 of the checksum
+    /// of the previous index, empty for the first index of a journal,
+    /// followed by the checksums of the records of the transaction
+    pub fn chain<'a>(previous: &[u8], checksums: impl IntoIterator<Item = &'a [u8]>) -> [u8; 8] {
+        let mut bytes = previous.to_vec();
+        for checksum in checksums {
+            bytes.extend(checksum);
+        }
+        let mut chain = [0u8; 8];
+        chain.copy_from_slice(&keccak256(&bytes)[..8]);
+        chain
+    }
+}
+impl PlainBytes for Index {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Record {
+    Snapshot(Vec<u8>),
+    Author(Author),
+    Commit(Commit),
+    Index(Index),
+    /// sets the head of a branch or deletes it when `None`
+    Branch(String, Option<ID>),
+    /// sets the current branch
+    Switch(String),
+    /// adds a tag or deletes it when `None`
+    Tag(String, Option<Tag>),
+    /// sets the keccak256 of the version of the commit of the
+    /// preceding [`Record::Commit`], which holds the version in the
+    /// content-addressed table unless an earlier commit does
+    Digest(Vec<u8>),
+    /// sets the keyframe of the commit of the preceding
+    /// [`Record::Commit`], see [`crate::keyframe`]
+    Keyframe(Vec<u8>),
+    /// sets the chunks of the version of the commit of the preceding
+    /// [`Record::Commit`], see [`OFVRState::manifests`]
+    Manifest(DataSeq),
+    /// sets the storage of the version of new commits
+    Storage(Storage),
+}
+impl Record {
+    pub fn kind(&self) -> u8 {
+        match self {
+            Record::Snapshot(_) => 1,
+            Record::Author(_) => 2,
+            Record::Commit(_) => 3,
+            Record::Index(_) => 4,
+            Record::Branch(..) => 5,
+            Record::Switch(_) => 6,
+            Record::Tag(..) => 7,
+            Record::Digest(_) => 8,
+            Record::Keyframe(_) => 9,
+            Record::Manifest(_) => 10,
+            Record::Storage(_) => 11,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let payload = match self {
+            Record::Snapshot(payload) => payload.clone(),
+            Record::Author(author) => author.to_plain_bytes(),
+            Record::Commit(commit) => commit.to_plain_bytes(),
+            Record::Index(index) => index.to_plain_bytes(),
+            Record::Branch(name, head) => bincode::serialize(&(name, head)).expect("bytes"),
+            Record::Switch(name) => bincode::serialize(name).expect("bytes"),
+            Record::Tag(name, tag) => bincode::serialize(&(name, tag)).expect("bytes"),
+            Record::Digest(digest) => bincode::serialize(digest).expect("bytes"),
+            Record::Keyframe(keyframe) => keyframe.clone(),
+            Record::Manifest(manifest) => manifest.to_plain_bytes(),
+            Record::Storage(storage) => bincode::serialize(storage).expect("bytes"),
+        };
+        let mut bytes = Vec::<u8>::with_capacity(payload.len() + RECORD_OVERHEAD);
+        bytes.push(self.kind());
+        bytes.extend((payload.len() as u64).to_le_bytes());
+        bytes.extend(payload);
+        let checksum = keccak256(&bytes);
+        bytes.extend(&checksum[..8]);
+        bytes
+    }
+
+    /// `read` returns the record starting at `offset` along with the
+    /// offset of the next record or `None` when the bytes at `offset`
+    /// are not a complete and valid record
+    pub fn read(bytes: &[u8], offset: usize) -> Option<(Record, usize)> {
+        let next = Record::span(bytes, offset)?;
+        let payload = &bytes[offset + 9..next - 8];
+        let record = match bytes[offset] {
+            1 => Record::Snapshot(payload.to_vec()),
+            2 => Record::Author(crate::from_strict_bytes::<Author>(payload).ok()?),
+            3 => Record::Commit(crate::from_strict_bytes::<Commit>(payload).ok()?),
+            4 => Record::Index(crate::from_strict_bytes::<Index>(payload).ok()?),
+            5 => {
+                let (name, head) = crate::from_strict_bytes::<(String, Option<ID>)>(payload).ok()?;
+                Record::Branch(name, head)
+            },
+            6 => Record::Switch(crate::from_strict_bytes::<String>(payload).ok()?),
+            7 => {
+                let (name, tag) = crate::from_strict_bytes::<(String, Option<Tag>)>(payload).ok()?;
+                Record::Tag(name, tag)
+            },
+            8 => Record::Digest(crate::from_strict_bytes::<Vec<u8>>(payload).ok()?),
+            9 => Record::Keyframe(payload.to_vec()),
+            10 => Record::Manifest(crate::from_strict_bytes::<DataSeq>(payload).ok()?),
+            11 => Record::Storage(crate::from_strict_bytes::<Storage>(payload).ok()?),
+            _ => return None,
+        };
+        Some((record, next))
+    }
+
+    /// `span` returns the offset of the record following the one
+    /// starting at `offset` without decoding its payload, or `None`
+    /// when the bytes at `offset` are not a complete record matching
+    /// its checksum
+    pub fn span(bytes: &[u8], offset: usize) -> Option<usize> {
+        let next = Record::end(bytes, offset)?;
+        if keccak256(&bytes[offset..next - 8])[..8] != bytes[next - 8..next] {
+            return None;
+        }
+        Some(next)
+    }
+
+    /// `end` returns the offset of the record following the one
+    /// starting at `offset` according to its length alone, or `None`
+    /// when `bytes` end before it
+    fn end(bytes: &[u8], offset: usize) -> Option<usize> {
+        let length = u64::from_le_bytes(bytes.get(offset + 1..offset + 9)?.try_into().ok()?);
+        let next = offset.checked_add(RECORD_OVERHEAD)?.checked_add(usize::try_from(length).ok()?)?;
+        (next <= bytes.len()).then_some(next)
+    }
+}
+
+/// `version` returns the format version of the given state file bytes
+pub fn version(bytes: &[u8]) -> Result<u16> {
+    Ok(Header::parse(bytes)?.map(|header| header.version).unwrap_or(0))
+}
+
+/// `encode` serializes the state as a journal holding a single
+/// snapshot and returns it along with its [`Journal`]
+pub fn encode(state: &OFVRState) -> Result<(Vec<u8>, Journal)> {
+    let mut e = DeflateEncoder::new(Vec::new(), Compression::best());
+    e.write_all(&state.to_plain_bytes())?;
+    let mut bytes = Header::journal(FLAG_DEFLATE).to_bytes();
+    bytes.extend(Record::Snapshot(e.finish()?).to_bytes());
+    let snapshot = bytes.len();
+    bytes.extend(
+        Record::Index(Index {
+            commits: state.commits().len() as u64,
+            entries: Vec::new(),
+            chain: Index::chain(&[], [&bytes[snapshot - 8..snapshot]]),
+        })
+        .to_bytes(),
+    );
+    let journal = Journal::new(&bytes, snapshot, snapshot, bytes.len());
+    Ok((bytes, journal))
+}
+
+/// `decode` deserializes state files of any format version up to
+/// [`FORMAT_VERSION`], migrating older layouts in memory
+pub fn decode(bytes: &[u8]) -> Result<OFVRState> {
+    Ok(read(bytes)?.0)
+}
+
+/// `read` deserializes state files of any format version up to
+/// [`FORMAT_VERSION`] and returns the valid journal when the file is
+/// in the current format
+pub fn read(bytes: &[u8]) -> Result<(OFVRState, Option<Journal>)> {
+    let header = match Header::parse(bytes)? {
+        Some(header) => header,
+        None => return Ok((migrate(0, bytes)?, None)),
+    };
+    match header.version {
+        FORMAT_VERSION => {
+            header.verify(&bytes[..8])?;
+            let (state, journal) = read_journal(&header, bytes)?;
+            Ok((state, Some(journal)))
+        },
+        version => Ok((migrate(version, &[])?, None)),
+    }
+}
+
+/// `migrate` decodes the payload of a state file, or of a snapshot
+/// record, written in the given format version and upgrades it to
+/// [`FORMAT_VERSION`]
+pub fn migrate(version: u16, payload: &[u8]) -> Result<OFVRState> {
+    match version {
+        // headerless bincode whose commits carry a cumulative `bt_diff::Diff`
+        0 => {
+            let mut state = OFVRState::from(crate::from_strict_bytes::<UnbranchedState>(payload)?);
+            state.migrate()?;
+            Ok(state)
+        },
+        FORMAT_VERSION => crate::from_strict_bytes::<OFVRState>(payload),
+        version => Err(Error::FormatError(format!(
+            "format version {} is newer than the supported version {}",
+            version, FORMAT_VERSION
+        ))),
+    }
+}
+
+fn inflate(header: &Header, payload: &[u8]) -> Result<Vec<u8>> {
+    if header.has_flag(FLAG_DEFLATE) {
+        let mut inflated = Vec::<u8>::new();
+        DeflateDecoder::new(payload).read_to_end(&mut inflated)?;
+        Ok(inflated)
+    } else {
+        Ok(payload.to_vec())
+    }
+}
+
+fn read_journal(header: &Header, bytes: &[u8]) -> Result<(OFVRState, Journal)> {
+    let mut state: Option<OFVRState> = None;
+    let mut pending = Vec::<Record>::new();
+    let mut offset = HEADER_LEN;
+    let mut snapshot = HEADER_LEN;
+    let mut last = HEADER_LEN;
+    let mut length = HEADER_LEN;
+    let mut checksums = Vec::<&[u8]>::new();
+    while let Some((record, next)) = Record::read(bytes, offset) {
+        let start = offset;
+        offset = next;
+        match record {
+            Record::Snapshot(payload) => {
+                state = Some(crate::from_strict_bytes::<OFVRState>(&inflate(header, &payload)?)?);
+                snapshot = next;
+                pending.clear();
+                checksums = vec![&bytes[next - 8..next]];
+            },
+            Record::Index(index) => {
+                let current = state.as_mut().ok_or_else(|| {
+                    Error::FormatError(format!("journal index at {} precedes snapshot", offset))
+                })?;
+                let previous = if last > HEADER_LEN { &bytes[length - 8..length] } else { &[] };
+                if index.chain != Index::chain(previous, checksums.drain(..)) {
+                    return Err(Error::FormatError(format!(
+                        "journal index at {} does not chain the records before it",
+                        offset
+                    )));
+                }
+                for record in pending.drain(..) {
+                    current.apply(record)?;
+                }
+                if current.commits().len() as u64 != index.commits {
+                    return Err(Error::FormatError(format!(
+                        "journal index at {} expects {} commits, found {}",
+                        offset,
+                        index.commits,
+                        current.commits().len()
+                    )));
+                }
+                last = start;
+                length = offset;
+            },
+            record => {
+                pending.push(record);
+                checksums.push(&bytes[next - 8..next]);
+            },
+        }
+    }
+    check_tail(bytes, offset)?;
+    match state {
+        Some(state) if length > HEADER_LEN =>
+            Ok((state, Journal::new(bytes, snapshot, last, length))),
+        _ => Err(Error::FormatError("journal holds no complete snapshot".to_string())),
+    }
+}
+
+/// `check_tail` returns an error when a complete index follows the
+/// bytes at `offset` of a journal that are not a valid record, which
+/// means that the journal was damaged before its last transaction
+/// rather than torn by an interrupted append: truncating it to
+/// `offset` would discard the transactions following the damage.
+///
+/// Only the record boundaries reached by following the lengths of the
+/// records from `offset` are checked, so that indexes held by the
+/// payload of a record are not mistaken for the end of a transaction
+pub fn check_tail(bytes: &[u8], offset: usize) -> Result<()> {
+    let mut position = offset;
+    while let Some(next) = Record::end(bytes, position) {
+        if bytes[position] == 4
+            && matches!(Record::read(bytes, position), Some((Record::Index(_), _)))
+        {
+            return Err(Error::FormatError(format!(
+                "damaged journal record at {} precedes the journal index at {}",
+                offset, position
+            )));
+        }
+        position = next;
+    }
+    Ok(())
+}
+
+/// `append` writes the given records and an index terminating them
+/// at the end of the given valid journal, discarding records of
+/// interrupted appends, and returns the new journal
+pub fn append(path: &Path, journal: Journal, records: &[Record], commits: u64) -> Result<Journal> {
+    let length = journal.length;
+    let mut file = std::fs::OpenOptions::new().read(true).write(true).open(path.to_path_buf())?;
+    file.set_len(length)?;
+    file.seek(SeekFrom::Start(length))?;
+    let mut bytes = Vec::<u8>::new();
+    let mut entries = Vec::<(ID, u64)>::new();
+    let mut checksums = Vec::<[u8; 8]>::new();
+    for record in records.iter() {
+        if let Record::Commit(commit) = record {
+            entries.push((commit.id.clone(), length + bytes.len() as u64));
+        }
+        bytes.extend(record.to_bytes());
+        checksums.push(bytes[bytes.len() - 8..].try_into().expect("8 bytes"));
+    }
+    let last = bytes.len();
+    let chain = Index::chain(&journal.tail, checksums.iter().map(|checksum| &checksum[..]));
+    bytes.extend(
+        Record::Index(Index {
+            commits,
+            entries,
+            chain,
+        })
+        .to_bytes(),
+    );
+    file.write_all(&bytes)?;
+    file.sync_data()?;
+    let mut tail = [0u8; 8];
+    tail.copy_from_slice(&bytes[bytes.len() - 8..]);
+    Ok(Journal {
+        length: length + bytes.len() as u64,
+        last: length + last as u64,
+        tail,
+        ..journal
+    })
+}
@@ -1,5 +1,9 @@
+use std::io::{ErrorKind, Read};
+
 pub use sha3::{Digest, Keccak256, Keccak256Full};
 
+use crate::errors::Result;
+
 pub fn keccak256(bytes: &[u8]) -> Vec<u8> {
     let mut keccak256 = Keccak256::new();
     keccak256.update(bytes);
@@ -13,3 +17,19 @@ pub fn keccak256_full(bytes: &[u8]) -> Vec<u8> {
     let keccak256 = keccak256.finalize();
     keccak256.to_vec()
 }
+
+/// `keccak256_reader` hashes the bytes of `reader` without holding
+/// more than a buffer of them
+pub fn keccak256_reader<R: Read>(mut reader: R) -> Result<Vec<u8>> {
+    let mut keccak256 = Keccak256::new();
+    let mut buffer = vec![0u8; 64 << 10];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => keccak256.update(&buffer[..read]),
+            Err(error) if error.kind() == ErrorKind::Interrupted => {},
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(keccak256.finalize().to_vec())
+}
@@ -0,0 +1,112 @@
+//! Identification of the recorded version of a file, see [`OFVRState::identify`]
+use std::fs::File;
+
+use iocore::Path;
+
+use crate::errors::{Error, Result};
+use crate::hash::{keccak256, keccak256_reader};
+use crate::io::read_data;
+use crate::models::commit::Commit;
+use crate::models::delta::Delta;
+use crate::models::state::OFVRState;
+
+/// `Identity` of a file among the versions recorded in a state
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Identity {
+    /// commits whose version is identical to the file, in the order
+    /// they were committed
+    Exact(Vec<Commit>),
+    /// commit whose version is the nearest to the file along with the
+    /// size of the difference, see [`distance`]
+    Nearest(Commit, usize),
+    /// the state holds no commits
+    Unknown,
+}
+impl Identity {
+    /// `exit_code` is 0 for exact matches, 2 for nearest versions and
+    /// 3 when the state holds no commits
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Identity::Exact(_) => 0,
+            Identity::Nearest(..) => 2,
+            Identity::Unknown => 3,
+        }
+    }
+
+    pub fn to_json(&self, ofvr: &OFVRState) -> Result<serde_json::Value> {
+        Ok(match self {
+            Identity::Exact(commits) => serde_json::json!({
+                "exact": commits
+                    .iter()
+                    .map(|commit| commit.to_json(ofvr))
+                    .collect::<Result<Vec<serde_json::Value>>>()?,
+            }),
+            Identity::Nearest(commit, distance) => serde_json::json!({
+                "nearest": commit.to_json(ofvr)?,
+                "distance": distance,
+            }),
+            Identity::Unknown => serde_json::json!({}),
+        })
+    }
+}
+
+/// `identify` compares the digest of `data` with the digest of every
+/// commit, see [`OFVRState::digest`], and only rebuilds the versions
+/// of the commits to find the nearest one when none matches
+pub fn identify(ofvr: &OFVRState, data: &[u8]) -> Result<Identity> {
+    match exact(ofvr, &keccak256(data))? {
+        Some(identity) => Ok(identity),
+        None => nearest(ofvr, data),
+    }
+}
+
+/// `identify_file` is [`identify`] for the file at `path`, which is
+/// hashed as a stream and only read whole when no version matches
+pub fn identify_file(ofvr: &OFVRState, path: &Path) -> Result<Identity> {
+    if !path.is_file() {
+        return Err(Error::IOError(format!("{} does not exist", path)));
+    }
+    match exact(ofvr, &keccak256_reader(File::open(path.to_path_buf())?)?)? {
+        Some(identity) => Ok(identity),
+        None => nearest(ofvr, &read_data(path)?),
+    }
+}
+
+/// `exact` returns the commits whose version has the given digest,
+/// or `Unknown` when the state holds no commits
+fn exact(ofvr: &OFVRState, digest: &[u8]) -> Result<Option<Identity>> {
+    if ofvr.commits().is_empty() {
+        return Ok(Some(Identity::Unknown));
+    }
+    let mut exact = Vec::<Commit>::new();
+    for commit in ofvr.commits().iter() {
+        if ofvr.digest(commit)? == digest {
+            exact.push(commit.clone());
+        }
+    }
+    Ok(if exact.is_empty() { None } else { Some(Identity::Exact(exact)) })
+}
+
+/// `nearest` rebuilds the versions of the commits to find the one
+/// nearest to `data`
+fn nearest(ofvr: &OFVRState, data: &[u8]) -> Result<Identity> {
+    let mut nearest: Option<(Commit, usize)> = None;
+    ofvr.for_each_version(|commit, version| {
+        let size = distance(version, data);
+        if nearest.as_ref().map(|(_, nearest)| size < *nearest).unwrap_or(true) {
+            nearest = Some((commit.clone(), size));
+        }
+        Ok(true)
+    })?;
+    Ok(match nearest {
+        Some((commit, size)) => Identity::Nearest(commit, size),
+        None => Identity::Unknown,
+    })
+}
+
+/// `distance` returns the size of the [`Delta`] turning `version`
+/// into `data` plus the amount of bytes of `version` past the end of
+/// `data`
+pub fn distance(version: &[u8], data: &[u8]) -> usize {
+    Delta::new(version, data).size() + version.len().saturating_sub(data.len())
+}
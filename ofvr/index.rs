@@ -0,0 +1,592 @@
+//! Index of the commits of a state file, kept next to it, see
+//! [`index_path`], so that `log`, `status` and revision resolution
+//! read the metadata of commits without loading the state file.
+//!
+//! The index holds the id, parents, author, date, message and digest
+//! of every commit, see [`IndexEntry`], along with the branches, tags
+//! and authors of the state. Commits themselves are only read when
+//! needed, see [`CommitIndex::commit`], from the offset of their
+//! record in the journal of the state file, see [`crate::format`].
+//!
+//! The index remembers the journal it covers, see [`Journal`]: records
+//! appended since are indexed on their own and any other change of
+//! the state file rebuilds the index from the whole state.
+//!
+//! Revisions and log queries read commits through [`Commits`], held
+//! by the index as well as by the state itself.
+use std::borrow::Cow;
+use std::collections::{btree_map, BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::time::Duration;
+
+use iocore::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{Error, Result};
+use crate::format::{check_tail, Header, Journal, Record, FORMAT_VERSION, HEADER_LEN};
+use crate::io::write_atomic;
+use crate::lock::StateLock;
+use crate::models::author::Author;
+use crate::models::commit::Commit;
+use crate::models::commit_data::CommitData;
+use crate::models::id::ID;
+use crate::models::state::OFVRState;
+use crate::models::tag::Tag;
+use crate::query::LogQuery;
+use crate::revision::Revision;
+use crate::traits::PlainBytes;
+
+/// `IndexEntry` holds what `log`, `status` and revisions need of a commit
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct IndexEntry {
+    pub id: ID,
+    pub parents: Vec<ID>,
+    pub author: u16,
+    pub date: t16::Data,
+    pub message: String,
+    /// keccak256 of the version of the commit when recorded, see
+    /// [`OFVRState::digest`]
+    pub digest: Option<Vec<u8>>,
+    /// offset of the record of the commit in the journal of the state
+    /// file, `None` for commits held by its snapshot
+    pub offset: Option<u64>,
+}
+impl IndexEntry {
+    /// `new` reads the metadata of a commit of the given state
+    pub fn new(commit: &Commit, ofvr: &OFVRState) -> Result<IndexEntry> {
+        IndexEntry::of(commit, ofvr.digests().get(&commit.id).cloned(), None)
+    }
+
+    fn of(commit: &Commit, digest: Option<Vec<u8>>, offset: Option<u64>) -> Result<IndexEntry> {
+        let data = CommitData::from_plain_bytes(&commit.data_bytes())?;
+        Ok(IndexEntry {
+            id: commit.id.clone(),
+            parents: data.parents(),
+            author: data.author_id(),
+            date: data.date(),
+            message: data.message(),
+            digest,
+            offset,
+        })
+    }
+
+    /// `log` describes the commit as [`Commit::log`] does, without
+    /// decoding it again when it was selected from a state, see
+    /// [`LogQuery::select`]
+    pub fn log<C: Commits>(&self, commits: &C) -> Result<String> {
+        Ok(self.describe(&commits.get_author(self.author)?, &self.tags(commits)))
+    }
+
+    /// `to_json` describes the commit as [`Commit::to_json`] does
+    pub fn to_json<C: Commits>(&self, commits: &C) -> Result<serde_json::Value> {
+        Ok(self.json(&commits.get_author(self.author)?, &self.tags(commits)))
+    }
+
+    fn tags<C: Commits>(&self, commits: &C) -> Vec<Tag> {
+        commits
+            .tags()
+            .values()
+            .filter(|tag| tag.commit() == self.id)
+            .cloned()
+            .collect()
+    }
+
+    fn describe(&self, author: &Author, tags: &[Tag]) -> String {
+        let mut lines = vec![format!("Commit: {}", self.id.to_hex())];
+        if self.parents.len() > 1 {
+            lines.push(format!(
+                "Merge: {}",
+                self.parents.iter().map(ID::to_hex).collect::<Vec<String>>().join(" ")
+            ));
+        }
+        if !tags.is_empty() {
+            lines.push(format!(
+                "Tags: {}",
+                tags.iter().map(|tag| tag.name()).collect::<Vec<String>>().join(", ")
+            ));
+        }
+        lines.push(format!("Author: {}", author));
+        lines.push(format!("Date: {}", self.date.to_chrono().to_rfc2822()));
+        lines.push(format!("\t{}\n", self.message));
+        lines.join("\n")
+    }
+
+    fn json(&self, author: &Author, tags: &[Tag]) -> serde_json::Value {
+        serde_json::json!({
+            "id": self.id.to_hex(),
+            "author": author,
+            "date": self.date.to_chrono().to_rfc3339(),
+            "message": self.message,
+            "tags": tags.iter().map(|tag| tag.name()).collect::<Vec<String>>(),
+            "parents": self.parents.iter().map(ID::to_hex).collect::<Vec<String>>(),
+        })
+    }
+}
+
+/// `Commits` holds what revisions, see [`Revision`], and log queries,
+/// see [`LogQuery`], read of the commits of a state file: either the
+/// [`CommitIndex`] of the state file or the [`OFVRState`] itself,
+/// which reads the metadata of its commits as they are needed
+pub trait Commits {
+    fn path(&self) -> Path;
+    fn current_branch(&self) -> String;
+    fn branches(&self) -> &BTreeMap<String, ID>;
+    fn tags(&self) -> &BTreeMap<String, Tag>;
+    fn get_author(&self, author: u16) -> Result<Author>;
+    /// `ids` returns the id of every commit in the order they were committed
+    fn ids(&self) -> Vec<&ID>;
+    /// `entry` returns the metadata of the given commit
+    fn entry(&self, id: &ID) -> Result<Cow<'_, IndexEntry>>;
+    /// `every_entry` returns the metadata of every commit in the
+    /// order they were committed
+    fn every_entry(&self) -> Result<Vec<Cow<'_, IndexEntry>>>;
+    /// `history_of` returns the metadata of the commits reachable from
+    /// the head of the given branch in the order they were committed
+    fn history_of(&self, branch: &str) -> Result<Vec<Cow<'_, IndexEntry>>>;
+}
+impl Commits for CommitIndex {
+    fn path(&self) -> Path {
+        CommitIndex::path(self)
+    }
+
+    fn current_branch(&self) -> String {
+        CommitIndex::current_branch(self)
+    }
+
+    fn branches(&self) -> &BTreeMap<String, ID> {
+        CommitIndex::branches(self)
+    }
+
+    fn tags(&self) -> &BTreeMap<String, Tag> {
+        CommitIndex::tags(self)
+    }
+
+    fn get_author(&self, author: u16) -> Result<Author> {
+        CommitIndex::get_author(self, author)
+    }
+
+    fn ids(&self) -> Vec<&ID> {
+        self.entries.iter().map(|entry| &entry.id).collect()
+    }
+
+    fn entry(&self, id: &ID) -> Result<Cow<'_, IndexEntry>> {
+        self.get(id).map(Cow::Borrowed)
+    }
+
+    fn every_entry(&self) -> Result<Vec<Cow<'_, IndexEntry>>> {
+        Ok(self.entries.iter().map(Cow::Borrowed).collect())
+    }
+
+    fn history_of(&self, branch: &str) -> Result<Vec<Cow<'_, IndexEntry>>> {
+        Ok(self.history(branch)?.into_iter().map(Cow::Borrowed).collect())
+    }
+}
+impl Commits for OFVRState {
+    fn path(&self) -> Path {
+        OFVRState::path(self)
+    }
+
+    fn current_branch(&self) -> String {
+        OFVRState::current_branch(self)
+    }
+
+    fn branches(&self) -> &BTreeMap<String, ID> {
+        OFVRState::branches(self)
+    }
+
+    fn tags(&self) -> &BTreeMap<String, Tag> {
+        OFVRState::tags(self)
+    }
+
+    fn get_author(&self, author: u16) -> Result<Author> {
+        OFVRState::get_author(self, author)
+    }
+
+    fn ids(&self) -> Vec<&ID> {
+        self.commits().iter().map(|commit| &commit.id).collect()
+    }
+
+    fn entry(&self, id: &ID) -> Result<Cow<'_, IndexEntry>> {
+        Ok(Cow::Owned(IndexEntry::new(&self.get_commit(id)?, self)?))
+    }
+
+    fn every_entry(&self) -> Result<Vec<Cow<'_, IndexEntry>>> {
+        self.commits()
+            .iter()
+            .map(|commit| Ok(Cow::Owned(IndexEntry::new(commit, self)?)))
+            .collect()
+    }
+
+    /// `history_of` reads the metadata of each commit of the history
+    /// once, following the parents it holds
+    fn history_of(&self, branch: &str) -> Result<Vec<Cow<'_, IndexEntry>>> {
+        let head = match OFVRState::branches(self).get(branch) {
+            Some(head) => head,
+            None if branch == self.current_branch() => return Ok(Vec::new()),
+            None => return Err(Error::BranchError(format!("branch {} does not exist", branch))),
+        };
+        let positions = self
+            .commits()
+            .iter()
+            .enumerate()
+            .map(|(position, commit)| (&commit.id, position))
+            .collect::<HashMap<&ID, usize>>();
+        let mut reachable = BTreeMap::<usize, IndexEntry>::new();
+        let mut pending = vec![head.clone()];
+        while let Some(id) = pending.pop() {
+            let position = positions
+                .get(&id)
+                .copied()
+                .ok_or_else(|| Error::StateError(format!("commit {} NOT present in state", id)))?;
+            if let btree_map::Entry::Vacant(vacant) = reachable.entry(position) {
+                let entry = IndexEntry::new(&self.commits()[position], self)?;
+                pending.extend(entry.parents.iter().cloned());
+                vacant.insert(entry);
+            }
+        }
+        Ok(reachable.into_values().map(Cow::Owned).collect())
+    }
+}
+
+/// `CommitIndex` of the commits of a state file, see [`crate::index`]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CommitIndex {
+    path: Path,
+    /// valid journal of the state file once indexed, `None` when the
+    /// state file is not in the current format
+    journal: Option<Journal>,
+    authors: BTreeMap<u16, Author>,
+    branches: BTreeMap<String, ID>,
+    branch: String,
+    tags: BTreeMap<String, Tag>,
+    /// commits of every branch in the order they were committed
+    entries: Vec<IndexEntry>,
+    #[serde(skip)]
+    positions: HashMap<ID, usize>,
+}
+impl CommitIndex {
+    /// `from_state` indexes the commits of the given state
+    pub fn from_state(ofvr: &OFVRState) -> Result<CommitIndex> {
+        let entries = ofvr
+            .commits()
+            .iter()
+            .map(|commit| IndexEntry::new(commit, ofvr))
+            .collect::<Result<Vec<IndexEntry>>>()?;
+        Ok(CommitIndex::new(ofvr, entries))
+    }
+
+    fn new(ofvr: &OFVRState, entries: Vec<IndexEntry>) -> CommitIndex {
+        let mut index = CommitIndex {
+            path: ofvr.path(),
+            journal: None,
+            authors: ofvr.authors().clone(),
+            branches: ofvr.branches().clone(),
+            branch: ofvr.current_branch(),
+            tags: ofvr.tags().clone(),
+            entries,
+            positions: HashMap::new(),
+        };
+        index.locate();
+        index
+    }
+
+    /// `load` reads the index of the state file at `path`, indexing
+    /// the records appended to its journal since the index was written
+    /// or rebuilding it when the state file was otherwise changed,
+    /// e.g. damaged, in which case loading fails as loading the state
+    /// file does.
+    ///
+    /// The index is written next to the state file under its lock,
+    /// see [`StateLock`], unless another process holds it
+    pub fn load(path: &Path) -> Result<CommitIndex> {
+        if !probe(path)? {
+            // state files in earlier format versions are indexed
+            // whole every time until upgraded
+            return CommitIndex::from_state(&OFVRState::from_path(path)?);
+        }
+        let stored = index_path(path)
+            .read_bytes()
+            .ok()
+            .and_then(|bytes| crate::from_strict_bytes::<CommitIndex>(&bytes).ok());
+        let (index, changed) = match stored {
+            Some(mut index) if index.covers(path)? => {
+                index.path = path.clone();
+                index.locate();
+                let journal = index.journal;
+                match index.update() {
+                    Ok(()) => {
+                        let changed = index.journal != journal;
+                        (index, changed)
+                    },
+                    Err(_) => (CommitIndex::rebuild(path)?, true),
+                }
+            },
+            _ => (CommitIndex::rebuild(path)?, true),
+        };
+        if changed {
+            // the index is only a cache of the state file, failing to
+            // write it must not fail reading the state
+            let _ = index.store();
+        }
+        Ok(index)
+    }
+
+    /// `store` writes the index next to the state file when its lock
+    /// is free and the state file was not changed since it was indexed
+    /// other than by appending to its journal
+    fn store(&self) -> Result<()> {
+        let _lock = StateLock::acquire(&self.path, Duration::ZERO)?;
+        if probe(&self.path)? && self.covers(&self.path)? {
+            write_atomic(&index_path(&self.path), &self.to_plain_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// `rebuild` indexes the whole state file at `path` along with the
+    /// offset of the commits appended to its journal
+    fn rebuild(path: &Path) -> Result<CommitIndex> {
+        let bytes = path.read_bytes()?;
+        let ofvr = OFVRState::from_bytes(path, &bytes)?;
+        let mut offsets = HashMap::<ID, u64>::new();
+        let mut offset = HEADER_LEN;
+        while let Some((record, next)) = Record::read(&bytes, offset) {
+            if let Record::Index(index) = record {
+                offsets.extend(index.entries);
+            }
+            offset = next;
+        }
+        let entries = ofvr
+            .commits()
+            .iter()
+            .map(|commit| {
+                let digest = ofvr.digests().get(&commit.id).cloned();
+                IndexEntry::of(commit, digest, offsets.get(&commit.id).copied())
+            })
+            .collect::<Result<Vec<IndexEntry>>>()?;
+        let mut index = CommitIndex::new(&ofvr, entries);
+        index.journal = ofvr.journal();
+        Ok(index)
+    }
+
+    /// `covers` returns true when the state file at `path` starts with
+    /// the journal the index was built from, see [`Journal::starts`].
+    ///
+    /// Only the header, the checksum of the snapshot and the last index
+    /// are read: damage elsewhere in the journal is noticed by loading
+    /// the state file, e.g. by [`crate::FsckReport`]
+    fn covers(&self, path: &Path) -> Result<bool> {
+        match self.journal {
+            Some(journal) => journal.starts(path),
+            None => Ok(false),
+        }
+    }
+
+    /// `update` indexes the transactions appended to the journal of
+    /// the state file since it was indexed, ignoring the records of an
+    /// interrupted append
+    fn update(&mut self) -> Result<()> {
+        let mut journal = self.journal.ok_or_else(|| {
+            Error::FormatError(format!("{} is not indexed as a journal", self.path))
+        })?;
+        let mut bytes = Vec::<u8>::new();
+        let mut file = File::open(self.path.to_path_buf())?;
+        file.seek(SeekFrom::Start(journal.length))?;
+        file.read_to_end(&mut bytes)?;
+        let mut pending = Vec::<(u64, Record)>::new();
+        let mut offset = 0;
+        let mut indexed = 0;
+        while let Some((record, next)) = Record::read(&bytes, offset) {
+            match record {
+                Record::Snapshot(_) =>
+                    return Err(Error::FormatError(format!(
+                        "snapshot at {} follows the journal of {}",
+                        journal.length + offset as u64,
+                        self.path
+                    ))),
+                Record::Index(index) => {
+                    for (offset, record) in pending.drain(..) {
+                        self.apply(offset, record)?;
+                    }
+                    if self.entries.len() as u64 != index.commits {
+                        return Err(Error::FormatError(format!(
+                            "journal index of {} expects {} commits, found {}",
+                            self.path,
+                            index.commits,
+                            self.entries.len()
+                        )));
+                    }
+                    journal.last = journal.length + offset as u64;
+                    journal.tail.copy_from_slice(&bytes[next - 8..next]);
+                    indexed = next;
+                },
+                record => pending.push((journal.length + offset as u64, record)),
+            }
+            offset = next;
+        }
+        check_tail(&bytes, offset)?;
+        journal.length += indexed as u64;
+        self.journal = Some(journal);
+        Ok(())
+    }
+
+    /// `apply` indexes a record read at `offset` of the journal
+    fn apply(&mut self, offset: u64, record: Record) -> Result<()> {
+        match record {
+            Record::Author(author) => {
+                self.authors.insert(author.id(), author);
+            },
+            Record::Commit(commit) => {
+                self.positions.insert(commit.id.clone(), self.entries.len());
+                self.entries.push(IndexEntry::of(&commit, None, Some(offset))?);
+            },
+            Record::Digest(digest) =>
+                if let Some(entry) = self.entries.last_mut() {
+                    entry.digest = Some(digest);
+                },
+            Record::Branch(name, Some(head)) => {
+                self.branches.insert(name, head);
+            },
+            Record::Branch(name, None) => {
+                self.branches.remove(&name);
+            },
+            Record::Switch(name) => self.branch = name,
+            Record::Tag(name, Some(tag)) => {
+                self.tags.insert(name, tag);
+            },
+            Record::Tag(name, None) => {
+                self.tags.remove(&name);
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    fn locate(&mut self) {
+        self.positions = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| (entry.id.clone(), index))
+            .collect();
+    }
+
+    pub fn path(&self) -> Path {
+        self.path.clone()
+    }
+
+    /// `entries` returns the commits of every branch in the order
+    /// they were committed
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, id: &ID) -> Result<&IndexEntry> {
+        match self.positions.get(id) {
+            Some(position) => Ok(&self.entries[*position]),
+            None => Err(Error::StateError(format!("commit {} NOT present in state", id))),
+        }
+    }
+
+    pub fn get_author(&self, author: u16) -> Result<Author> {
+        match self.authors.get(&author) {
+            Some(author) => Ok(author.clone()),
+            None => Err(Error::StateError(format!("author {} NOT present in state", author))),
+        }
+    }
+
+    pub fn current_branch(&self) -> String {
+        self.branch.clone()
+    }
+
+    pub fn branches(&self) -> &BTreeMap<String, ID> {
+        &self.branches
+    }
+
+    pub fn tags(&self) -> &BTreeMap<String, Tag> {
+        &self.tags
+    }
+
+    pub fn tags_of(&self, id: &ID) -> Vec<Tag> {
+        self.tags.values().filter(|tag| tag.commit() == *id).cloned().collect()
+    }
+
+    /// `history` returns the commits reachable from the head of the
+    /// given branch in the order they were committed, see
+    /// [`OFVRState::history`]
+    pub fn history(&self, branch: &str) -> Result<Vec<&IndexEntry>> {
+        let head = match self.branches.get(branch) {
+            Some(head) => head,
+            None if branch == self.branch => return Ok(Vec::new()),
+            None => return Err(Error::BranchError(format!("branch {} does not exist", branch))),
+        };
+        let mut reachable = HashSet::<usize>::new();
+        let mut pending = vec![head.clone()];
+        while let Some(id) = pending.pop() {
+            let position =
+                self.positions.get(&id).copied().ok_or_else(|| {
+                    Error::StateError(format!("commit {} NOT present in state", id))
+                })?;
+            if reachable.insert(position) {
+                pending.extend(self.entries[position].parents.iter().cloned());
+            }
+        }
+        let mut positions = reachable.into_iter().collect::<Vec<usize>>();
+        positions.sort();
+        Ok(positions.into_iter().map(|position| &self.entries[position]).collect())
+    }
+
+    /// `resolve` finds the commit addressed by the given revision, see [`Revision`]
+    pub fn resolve(&self, revision: &str) -> Result<Cow<'_, IndexEntry>> {
+        Revision::parse(revision)?.resolve_entry(self)
+    }
+
+    /// `log` returns the commits selected by the given query, see [`LogQuery`]
+    pub fn log(&self, query: &LogQuery) -> Result<Vec<Cow<'_, IndexEntry>>> {
+        query.select(self)
+    }
+
+    /// `commit` reads the given commit from its record in the journal
+    /// of the state file or else from the whole state file
+    pub fn commit(&self, id: &ID) -> Result<Commit> {
+        let offset = match self.get(id)?.offset {
+            Some(offset) => offset,
+            None => return OFVRState::from_path(&self.path)?.get_commit(id),
+        };
+        let mut file = File::open(self.path.to_path_buf())?;
+        let mut bytes = vec![0u8; 9];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut bytes)?;
+        let length = u64::from_le_bytes(bytes[1..9].try_into().expect("8 bytes"));
+        file.take(length + 8).read_to_end(&mut bytes)?;
+        match Record::read(&bytes, 0) {
+            Some((Record::Commit(commit), _)) if commit.id == *id => Ok(commit),
+            _ => Err(Error::FormatError(format!(
+                "no record of commit {} at {} of {}",
+                id, offset, self.path
+            ))),
+        }
+    }
+}
+impl PlainBytes for CommitIndex {}
+
+/// `probe` returns true when the state file at `path` is in the
+/// current format without reading its journal
+fn probe(path: &Path) -> Result<bool> {
+    let mut file = File::open(path.to_path_buf())?;
+    let mut bytes = vec![0u8; HEADER_LEN + 9];
+    if file.metadata()?.len() < bytes.len() as u64 {
+        return Ok(false);
+    }
+    file.read_exact(&mut bytes)?;
+    // the journal starts with the kind of a snapshot record
+    Ok(matches!(
+        Header::parse(&bytes)?,
+        Some(header) if header.version == FORMAT_VERSION && bytes[HEADER_LEN] == 1
+    ))
+}
+
+/// `index_path` returns the path of the index of the state file at `path`
+pub fn index_path(path: &Path) -> Path {
+    path.with_filename(format!("{}.index", path.name()))
+}
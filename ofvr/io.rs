@@ -1,3 +1,5 @@
+use std::io::{BufWriter, Write};
+
 use crate::{Error, Result};
 use iocore::Path;
 
@@ -7,3 +9,60 @@ pub fn read_data(data_path: &Path) -> Result<Vec<u8>> {
     }
     Ok(data_path.read_bytes()?)
 }
+
+/// `write_atomic` writes `contents` to a temporary file next to
+/// `path`, syncs it to disk and renames it over `path` so that readers
+/// see either the previous or the new contents but never a partial
+/// write
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let temporary = temporary_path(path);
+    temporary.write(contents)?;
+    rename_over(&temporary, path)
+}
+
+/// `write_atomic_with` is [`write_atomic`] for contents written by
+/// `write` into the temporary file rather than held in memory,
+/// leaving `path` untouched when `write` fails
+pub fn write_atomic_with<T>(
+    path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> Result<T>,
+) -> Result<T> {
+    let temporary = temporary_path(path);
+    let written = std::fs::File::create(temporary.to_path_buf())
+        .map_err(Error::from)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            let written = write(&mut writer)?;
+            writer.into_inner().map_err(|error| error.into_error())?.sync_all()?;
+            Ok(written)
+        });
+    match written {
+        Ok(written) => {
+            rename_over(&temporary, path)?;
+            Ok(written)
+        },
+        Err(error) => {
+            let _ = std::fs::remove_file(temporary.to_path_buf());
+            Err(error)
+        },
+    }
+}
+
+fn temporary_path(path: &Path) -> Path {
+    path.with_filename(format!(".{}.{}.tmp", path.name(), std::process::id()))
+}
+
+/// `rename_over` renames `temporary` over `path` and syncs the directory
+fn rename_over(temporary: &Path, path: &Path) -> Result<()> {
+    if let Err(error) = std::fs::rename(temporary.to_path_buf(), path.to_path_buf()) {
+        let _ = std::fs::remove_file(temporary.to_path_buf());
+        return Err(Error::IOError(format!("renaming {} to {}: {}", temporary, path, error)));
+    }
+    if let Some(parent) = path.to_path_buf().parent() {
+        // persists the rename itself, not supported on every platform
+        if let Ok(directory) = std::fs::File::open(parent) {
+            let _ = directory.sync_all();
+        }
+    }
+    Ok(())
+}
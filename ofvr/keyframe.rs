@@ -0,0 +1,65 @@
+//! Keyframes holding the full version of some commits so that
+//! rebuilding a version starts from the nearest keyframe rather than
+//! from the first commit, see [`crate::OFVRState::version`] and
+//! [`crate::OFVRState::repack`]
+use std::io::{Read, Write};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{Error, Result};
+
+/// Amount of deltas after which a keyframe is stored by default
+pub const DEFAULT_KEYFRAME_INTERVAL: usize = 64;
+/// Total size of deltas after which a keyframe is stored by default
+pub const DEFAULT_KEYFRAME_THRESHOLD: u64 = 1 << 20;
+
+/// `KeyframePolicy` decides when the version of a new commit is
+/// stored as a keyframe, based on the chain of deltas applied on top
+/// of the nearest keyframe, or of the first commit, to rebuild it
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Hash, Deserialize, Serialize)]
+pub struct KeyframePolicy {
+    /// amount of deltas in the chain, zero disables it
+    pub interval: usize,
+    /// total size in bytes of the hunks of the deltas in the chain,
+    /// zero disables it
+    pub threshold: u64,
+}
+impl Default for KeyframePolicy {
+    fn default() -> KeyframePolicy {
+        KeyframePolicy::new(DEFAULT_KEYFRAME_INTERVAL, DEFAULT_KEYFRAME_THRESHOLD)
+    }
+}
+impl KeyframePolicy {
+    pub fn new(interval: usize, threshold: u64) -> KeyframePolicy {
+        KeyframePolicy {
+            interval,
+            threshold,
+        }
+    }
+
+    /// `is_due` returns true when a chain of `length` deltas totalling
+    /// `size` bytes reaches the interval or exceeds the threshold
+    pub fn is_due(&self, length: usize, size: u64) -> bool {
+        (self.interval > 0 && length >= self.interval)
+            || (self.threshold > 0 && length > 0 && size > self.threshold)
+    }
+}
+
+/// `compress` returns the keyframe of the given version
+pub fn compress(version: &[u8]) -> Result<Vec<u8>> {
+    let mut e = DeflateEncoder::new(Vec::new(), Compression::best());
+    e.write_all(version)?;
+    Ok(e.finish()?)
+}
+
+/// `decompress` returns the version held by the given keyframe
+pub fn decompress(keyframe: &[u8]) -> Result<Vec<u8>> {
+    let mut version = Vec::<u8>::new();
+    DeflateDecoder::new(keyframe)
+        .read_to_end(&mut version)
+        .map_err(|error| Error::StateError(format!("corrupted keyframe: {}", error)))?;
+    Ok(version)
+}
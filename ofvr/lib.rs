@@ -2,16 +2,48 @@ pub mod errors;
 pub mod io;
 pub mod models;
 pub mod utils;
-pub use utils::{to_flate_bytes, from_deflate_bytes};
+pub use utils::{to_flate_bytes, from_deflate_bytes, from_strict_bytes};
 
 pub use errors::{Error, Result};
-pub use io::read_data;
+pub use io::{read_data, write_atomic, write_atomic_with};
 pub use models::*;
 
+pub mod blame;
+pub use blame::BlameRun;
+pub mod chunk;
+pub use chunk::Storage;
 pub mod data;
 pub use data::{Data, DataSeq, DataSeqIterator};
+pub mod dedup;
+pub use dedup::DedupStats;
+pub mod format;
 pub mod hash;
-pub use hash::{keccak256, keccak256_full};
+pub use hash::{keccak256, keccak256_full, keccak256_reader};
+pub mod identify;
+pub use identify::Identity;
+pub mod index;
+pub use index::{CommitIndex, Commits, IndexEntry};
+pub mod keyframe;
+pub use keyframe::KeyframePolicy;
+pub mod lock;
+pub use lock::StateLock;
+
+pub mod merge;
+pub use merge::{Conflict, Merge, Side};
+pub mod oplog;
+pub use oplog::{Operation, OperationKind};
+pub mod pack;
+pub use pack::Pack;
+pub mod query;
+pub use query::LogQuery;
+pub mod revision;
+pub use revision::Revision;
+pub mod search;
+pub use search::{Pattern, SearchHit};
+pub mod status;
+pub use status::{Status, StatusReport};
+pub mod verify;
+pub use verify::{FsckReport, Problem, ProblemKind};
 
 pub mod traits;
 pub use traits::{FileSystemBytes, PlainBytes};
@@ -0,0 +1,70 @@
+//! Advisory locking of state files.
+//!
+//! Operations that modify a state file hold an exclusive lock on a
+//! sibling `.lock` file for their whole duration. The lock file is
+//! left in place once released since removing it would race with
+//! processes waiting on it. The lock is released by the operating
+//! system when its holder exits, so a crashed process never leaves a
+//! stale lock behind.
+use std::fs::{File, OpenOptions, TryLockError};
+use std::time::{Duration, Instant};
+
+use iocore::Path;
+
+use crate::errors::{Error, Result};
+
+/// Time waited for the lock of a state file before giving up
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Interval between attempts to take a lock held by another process
+pub const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(25);
+
+/// `StateLock` holds the exclusive lock of a state file until dropped
+#[derive(Debug)]
+pub struct StateLock {
+    path: Path,
+    file: File,
+}
+impl StateLock {
+    /// `acquire` locks the state file at `path`, waiting up to
+    /// `timeout` for another holder to release it
+    pub fn acquire(path: &Path, timeout: Duration) -> Result<StateLock> {
+        let path = StateLock::lock_path(path);
+        path.mkdir_parents()?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path.to_path_buf())?;
+        let started = Instant::now();
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(StateLock { path, file }),
+                Err(TryLockError::WouldBlock) =>
+                    if started.elapsed() >= timeout {
+                        return Err(Error::LockError(format!(
+                            "timed out after {:?} waiting for {}",
+                            timeout, path
+                        )));
+                    },
+                Err(TryLockError::Error(error)) =>
+                    return Err(Error::LockError(format!("locking {}: {}", path, error))),
+            }
+            std::thread::sleep(LOCK_RETRY_INTERVAL.min(timeout.saturating_sub(started.elapsed())));
+        }
+    }
+
+    /// `lock_path` returns the path of the lock file of the state file at `path`
+    pub fn lock_path(path: &Path) -> Path {
+        path.with_filename(format!("{}.lock", path.name()))
+    }
+
+    pub fn path(&self) -> Path {
+        self.path.clone()
+    }
+}
+impl Drop for StateLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
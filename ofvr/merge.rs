@@ -0,0 +1,205 @@
+//! Three-way merge of versions of a file, see [`Merge`]
+use std::fmt::Display;
+use std::str::FromStr;
+
+use bt_diff::{AxisBoundary, Diff, DiffUnit};
+use serde::Serialize;
+
+use crate::errors::{Error, Result};
+
+/// `Side` of a [`Merge`] whose bytes resolve a [`Conflict`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Side {
+    /// the version of the branch being merged into
+    Ours,
+    /// the version being merged
+    Theirs,
+}
+impl Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Side::Ours => "ours",
+                Side::Theirs => "theirs",
+            }
+        )
+    }
+}
+impl FromStr for Side {
+    type Err = Error;
+
+    fn from_str(side: &str) -> Result<Side> {
+        match side {
+            "ours" => Ok(Side::Ours),
+            "theirs" => Ok(Side::Theirs),
+            side =>
+                Err(Error::MergeError(format!("invalid side {:#?}, expected ours or theirs", side))),
+        }
+    }
+}
+
+/// `Conflict` is a range of bytes changed differently by both sides
+/// of a [`Merge`]
+///
+/// The bytes of each version are shorter than the range when that
+/// version ends within it
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct Conflict {
+    pub offset: usize,
+    pub end: usize,
+    pub ancestor: Vec<u8>,
+    pub ours: Vec<u8>,
+    pub theirs: Vec<u8>,
+    pub resolution: Option<Side>,
+}
+impl Conflict {
+    pub fn bytes(&self, side: Side) -> &[u8] {
+        match side {
+            Side::Ours => &self.ours,
+            Side::Theirs => &self.theirs,
+        }
+    }
+}
+impl Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "conflict at {}..{}: ancestor {} ours {} theirs {}",
+            self.offset,
+            self.end,
+            hex::encode(&self.ancestor),
+            hex::encode(&self.ours),
+            hex::encode(&self.theirs)
+        )?;
+        if let Some(side) = self.resolution {
+            write!(f, " (resolved with {})", side)?;
+        }
+        Ok(())
+    }
+}
+
+/// `Merge` combines the changes two versions made to their common
+/// ancestor byte by byte.
+///
+/// Bytes changed by a single side, or changed to the same value by
+/// both sides, are merged automatically. Contiguous bytes changed
+/// differently by each side form a [`Conflict`] which must be
+/// resolved before [`Merge::result`] rebuilds the merged version.
+/// When one version ends before the other, the bytes past its end
+/// form a single conflict unless either side kept them as they were
+/// in the ancestor.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Merge {
+    /// pairs each byte of the ancestor with the merged byte at the
+    /// same offset, which is the byte of ours within conflicts
+    diff: Diff,
+    conflicts: Vec<Conflict>,
+}
+impl Merge {
+    pub fn new(ancestor: &[u8], ours: &[u8], theirs: &[u8]) -> Result<Merge> {
+        let axis_boundary = AxisBoundary::default();
+        let AxisBoundary::Len(width) = axis_boundary;
+        // past the end of the shorter version only the longer one holds
+        // bytes, merging them offset by offset would leave a hole where
+        // the shorter one ends, so the whole tail conflicts unless one
+        // side left it as the ancestor had it
+        let short = ours.len().min(theirs.len());
+        let long = ours.len().max(theirs.len());
+        let longer = if ours.len() > theirs.len() { ours } else { theirs };
+        let tail = if short != ancestor.len() && longer.get(short..) != ancestor.get(short..long) {
+            short..long.max(ancestor.len())
+        } else {
+            0..0
+        };
+        let ours = bt_diff::diff(ancestor, ours, axis_boundary.clone())?;
+        let theirs = bt_diff::diff(ancestor, theirs, axis_boundary.clone())?;
+        let mut diff = Diff::new(axis_boundary);
+        let mut conflicts = Vec::<Conflict>::new();
+        for offset in 0..ours.sequence.len().max(theirs.sequence.len()) {
+            let base = ancestor.get(offset).copied();
+            let mine = current(&ours, offset);
+            let other = current(&theirs, offset);
+            let conflicting = tail.contains(&offset);
+            let merged = if !conflicting && (mine == other || other == base) {
+                mine
+            } else if !conflicting && mine == base {
+                other
+            } else {
+                match conflicts.last_mut() {
+                    Some(conflict) if conflict.end == offset => {
+                        conflict.end += 1;
+                        conflict.ancestor.extend(base);
+                        conflict.ours.extend(mine);
+                        conflict.theirs.extend(other);
+                    },
+                    _ => conflicts.push(Conflict {
+                        offset,
+                        end: offset + 1,
+                        ancestor: base.into_iter().collect(),
+                        ours: mine.into_iter().collect(),
+                        theirs: other.into_iter().collect(),
+                        resolution: None,
+                    }),
+                }
+                mine
+            };
+            diff.sequence.push(DiffUnit::new(base, merged, offset % width, offset / width));
+        }
+        Ok(Merge { diff, conflicts })
+    }
+
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    /// `is_resolved` returns true when every conflict has a resolution
+    pub fn is_resolved(&self) -> bool {
+        self.conflicts.iter().all(|conflict| conflict.resolution.is_some())
+    }
+
+    /// `resolve` resolves the conflict starting at `offset` with the
+    /// bytes of the given side
+    pub fn resolve(&mut self, offset: usize, side: Side) -> Result<()> {
+        match self.conflicts.iter_mut().find(|conflict| conflict.offset == offset) {
+            Some(conflict) => {
+                conflict.resolution = Some(side);
+                Ok(())
+            },
+            None => Err(Error::MergeError(format!("no conflict starts at offset {}", offset))),
+        }
+    }
+
+    /// `resolve_all` resolves the conflicts not resolved yet with the
+    /// bytes of the given side
+    pub fn resolve_all(&mut self, side: Side) {
+        for conflict in self.conflicts.iter_mut() {
+            conflict.resolution.get_or_insert(side);
+        }
+    }
+
+    /// `result` rebuilds the merged version, see [`Diff::current_version`]
+    pub fn result(&self) -> Result<Vec<u8>> {
+        let mut diff = self.diff.clone();
+        for conflict in self.conflicts.iter() {
+            let side = conflict.resolution.ok_or_else(|| {
+                Error::MergeError(format!(
+                    "conflict at {}..{} is not resolved",
+                    conflict.offset, conflict.end
+                ))
+            })?;
+            let bytes = conflict.bytes(side);
+            for (index, unit) in diff.sequence[conflict.offset..conflict.end].iter_mut().enumerate()
+            {
+                unit.current = bytes.get(index).copied().into_iter().collect();
+            }
+        }
+        Ok(diff.current_version())
+    }
+}
+
+fn current(diff: &Diff, offset: usize) -> Option<u8> {
+    diff.sequence.get(offset).and_then(DiffUnit::current)
+}
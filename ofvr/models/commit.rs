@@ -1,11 +1,12 @@
-use bt_diff::Diff;
 use iocore::Path;
 use serde::{Deserialize, Serialize};
 pub use sha3::{Digest, Keccak256, Keccak256Full};
 use crate::traits::PlainBytes;
 
+use crate::index::IndexEntry;
 use crate::models::author::Author;
 use crate::models::commit_data::CommitData;
+use crate::models::delta::Delta;
 use crate::models::state::OFVRState;
 use crate::models::id::ID;
 use crate::{Result, Data};
@@ -24,27 +25,48 @@ impl PartialEq for Commit {
 
 impl Commit {
     pub fn log(&self, ofvr: &OFVRState) -> Result<String> {
-        let data = self.data(ofvr)?;
-        Ok([
-            format!("Commit: {}", self.id.to_hex()),
-            format!("Author: {}", &self.author(&ofvr)?),
-            format!("Date: {}", data.date_rfc2822()),
-            format!("\t{}\n", data.message()),
-        ]
-        .join("\n"))
+        IndexEntry::new(self, ofvr)?.log(ofvr)
+    }
+
+    pub fn to_json(&self, ofvr: &OFVRState) -> Result<serde_json::Value> {
+        IndexEntry::new(self, ofvr)?.to_json(ofvr)
     }
 
     pub fn data(&self, _: &OFVRState) -> Result<CommitData> {
         Ok(CommitData::from_plain_bytes(&self.data.to_bytes())?)
     }
 
+    pub(crate) fn data_bytes(&self) -> Vec<u8> {
+        self.data.to_bytes()
+    }
+
+    /// `author_id` returns the id of the author as recorded in the
+    /// commit itself, see [`CommitData::author_id`]
+    pub fn author_id(&self) -> u16 {
+        self.author
+    }
+
     pub fn author(&self, ofvr: &OFVRState) -> Result<Author> {
         Ok(ofvr.get_author(self.data(ofvr)?.author_id())?)
     }
+    /// `new` links `commit_data` to the latest commit of `ofvr` and
+    /// computes the id of the resulting commit
     pub fn new(
         commit_data: CommitData,
         ofvr: &OFVRState,
     ) -> Result<Commit> {
+        let parents = ofvr.latest_commit().map(|commit| commit.id).into_iter().collect();
+        Commit::with_parents(commit_data, parents, ofvr)
+    }
+
+    /// `with_parents` links `commit_data` to the given parents and
+    /// computes the id of the resulting commit
+    pub fn with_parents(
+        mut commit_data: CommitData,
+        parents: Vec<ID>,
+        ofvr: &OFVRState,
+    ) -> Result<Commit> {
+        commit_data.set_parents(parents);
         let data = Data::from(commit_data.to_plain_bytes());
         let author = commit_data.author(ofvr)?.id();
         let id = commit_data.id()?;
@@ -57,14 +79,14 @@ impl Commit {
     }
 
     pub fn now(
-        diff: Diff,
+        delta: Delta,
         author: u16,
         message: &str,
         path: &Path,
         ofvr: &OFVRState,
     ) -> Result<Commit> {
         let date = t16::Data::now();
-        let commit_data = CommitData::new(&date, diff, author, message, path)?;
+        let commit_data = CommitData::new(&date, delta, author, message, path)?;
         Commit::new(commit_data, ofvr)
     }
 }
@@ -1,10 +1,10 @@
-use bt_diff::Diff;
 use iocore::Path;
 use serde::{Deserialize, Serialize};
 pub use sha3::{Digest, Keccak256, Keccak256Full};
 use crate::traits::PlainBytes;
 
 use crate::models::author::Author;
+use crate::models::delta::Delta;
 use crate::models::state::OFVRState;
 use crate::models::id::ID;
 use crate::Result;
@@ -12,14 +12,17 @@ use crate::Result;
 #[derive(Debug, Clone, PartialOrd, Eq, Ord, Hash, Deserialize, Serialize)]
 pub struct CommitData {
     date: t16::Data,
-    diff: Diff,
+    delta: Delta,
     message: String,
     path: Path,
     author: u16,
+    /// ids of the parent commits, empty for the first commit, folded
+    /// into [`CommitData::id`] so that the history forms a hash chain
+    parents: Vec<ID>,
 }
 impl PartialEq for CommitData {
     fn eq(&self, other: &Self) -> bool {
-        self.diff == other.diff
+        self.delta == other.delta
     }
 }
 impl CommitData {
@@ -27,8 +30,8 @@ impl CommitData {
         self.date.clone()
     }
 
-    pub fn diff(&self) -> Diff {
-        self.diff.clone()
+    pub fn delta(&self) -> Delta {
+        self.delta.clone()
     }
 
     pub fn message(&self) -> String {
@@ -53,7 +56,7 @@ impl CommitData {
 
     pub fn new(
         date: &t16::Data,
-        diff: Diff,
+        delta: Delta,
         author: u16,
         message: &str,
         path: &Path,
@@ -63,14 +66,32 @@ impl CommitData {
         let path = path.clone();
         let commit_data = CommitData {
             date,
-            diff,
+            delta,
             message,
             path,
             author,
+            parents: Vec::new(),
         };
         Ok(commit_data)
     }
 
+    pub fn parents(&self) -> Vec<ID> {
+        self.parents.clone()
+    }
+
+    /// `set_parents` links the commit to its parents, see [`crate::Commit::new`]
+    pub fn set_parents(&mut self, parents: Vec<ID>) {
+        self.parents = parents;
+    }
+
+    pub fn set_message(&mut self, message: &str) {
+        self.message = message.to_string();
+    }
+
+    pub fn set_author(&mut self, author: u16) {
+        self.author = author;
+    }
+
     pub fn id(&self) -> Result<ID> {
         let id = ID::new(crate::hash::keccak256(&self.to_flate_bytes().unwrap()));
         Ok(id)
@@ -80,4 +101,8 @@ impl CommitData {
         self.date().to_chrono().to_rfc3339()
     }
 }
-impl PlainBytes for CommitData {}
+impl PlainBytes for CommitData {
+    fn from_plain_bytes(bytes: &[u8]) -> Result<CommitData> {
+        crate::from_strict_bytes::<CommitData>(bytes)
+    }
+}
@@ -1,23 +1,51 @@
+use crate::keyframe::KeyframePolicy;
 use crate::models::author::Author;
+use crate::models::legacy::UnkeyedConf;
 use crate::traits::{FileSystemBytes, PlainBytes};
+use crate::Result;
 use iocore::Path;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Deserialize, Serialize)]
 pub struct Conf {
     author: Author,
+    /// when new commits get a keyframe, see [`crate::keyframe`]
+    keyframes: KeyframePolicy,
+}
+impl From<UnkeyedConf> for Conf {
+    /// `from` converts configurations of earlier versions of ofvr,
+    /// which get the default [`KeyframePolicy`]
+    fn from(conf: UnkeyedConf) -> Conf {
+        Conf::new(conf.author)
+    }
 }
 impl Conf {
     pub fn new(author: Author) -> Conf {
-        Conf { author }
+        Conf {
+            author,
+            keyframes: KeyframePolicy::default(),
+        }
     }
     pub fn author(&self) -> Author {
         self.author.clone()
     }
+    pub fn keyframes(&self) -> KeyframePolicy {
+        self.keyframes
+    }
+    pub fn set_keyframes(&mut self, keyframes: KeyframePolicy) {
+        self.keyframes = keyframes;
+    }
     pub fn default_path() -> Path {
         Path::raw("~/.ofvr").try_canonicalize()
     }
 
 }
-impl PlainBytes for Conf {}
+impl PlainBytes for Conf {
+    fn from_plain_bytes(bytes: &[u8]) -> Result<Conf> {
+        match crate::from_strict_bytes::<Conf>(bytes) {
+            Ok(conf) => Ok(conf),
+            Err(_) => Ok(Conf::from(crate::from_strict_bytes::<UnkeyedConf>(bytes)?)),
+        }
+    }
+}
 impl FileSystemBytes for Conf {}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{Error, Result};
+use crate::traits::PlainBytes;
+use crate::Data;
+
+/// Unchanged runs shorter than `HUNK_GAP` bytes are folded into the
+/// surrounding hunks since storing them costs less than the overhead
+/// of a new [`Hunk`]
+pub const HUNK_GAP: usize = 16;
+
+/// `Hunk` is a contiguous run of bytes written at `offset`
+#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Deserialize, Serialize)]
+pub struct Hunk {
+    pub offset: usize,
+    pub data: Data,
+}
+impl Hunk {
+    pub fn new(offset: usize, data: &[u8]) -> Hunk {
+        Hunk {
+            offset,
+            data: Data::from(data),
+        }
+    }
+
+    pub fn end(&self) -> usize {
+        self.offset + self.data.len()
+    }
+}
+
+/// `Delta` holds only the bytes that changed between two versions of a file
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Eq, Ord, Hash, Deserialize, Serialize)]
+pub struct Delta {
+    length: usize,
+    hunks: Vec<Hunk>,
+}
+impl Delta {
+    /// `new` computes the delta that turns `anterior` into `current`
+    pub fn new(anterior: &[u8], current: &[u8]) -> Delta {
+        let mut hunks = Vec::<Hunk>::new();
+        let mut start: Option<usize> = None;
+        let mut end = 0;
+        for (offset, byte) in current.iter().enumerate() {
+            if anterior.get(offset) == Some(byte) {
+                continue;
+            }
+            match start {
+                Some(_) if offset - end < HUNK_GAP => {},
+                Some(begin) => {
+                    hunks.push(Hunk::new(begin, &current[begin..end]));
+                    start = Some(offset);
+                },
+                None => start = Some(offset),
+            }
+            end = offset + 1;
+        }
+        if let Some(begin) = start {
+            hunks.push(Hunk::new(begin, &current[begin..end]));
+        }
+        Delta {
+            length: current.len(),
+            hunks,
+        }
+    }
+
+    /// `with_length` returns the delta that only sets the length of a
+    /// version, for versions rebuilt from elsewhere, see
+    /// [`crate::OFVRState::whole`]
+    pub fn with_length(length: usize) -> Delta {
+        Delta {
+            length,
+            hunks: Vec::new(),
+        }
+    }
+
+    /// `apply` rebuilds the version this delta was computed for from its anterior version
+    pub fn apply(&self, anterior: &[u8]) -> Vec<u8> {
+        let mut current = anterior.to_vec();
+        current.resize(self.length, 0);
+        for hunk in self.hunks.iter() {
+            current[hunk.offset..hunk.end()].copy_from_slice(&hunk.data.to_bytes());
+        }
+        current
+    }
+
+    /// `check` returns an error when the hunks of this delta are out
+    /// of order, overlap or extend past its length, in which case
+    /// [`Delta::apply`] would panic
+    pub fn check(&self) -> Result<()> {
+        let mut end = 0;
+        for (index, hunk) in self.hunks.iter().enumerate() {
+            let hunk_end = hunk.offset.saturating_add(hunk.data.len());
+            if hunk.offset < end || hunk_end > self.length {
+                return Err(Error::DiffError(format!(
+                    "hunk {} at {}..{} is out of order or past length {}",
+                    index,
+                    hunk.offset,
+                    hunk_end,
+                    self.length
+                )));
+            }
+            end = hunk_end;
+        }
+        Ok(())
+    }
+
+    /// `length` of the version this delta was computed for
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    pub fn hunks(&self) -> &[Hunk] {
+        &self.hunks
+    }
+
+    /// `size` returns the amount of bytes stored in the hunks of this delta
+    pub fn size(&self) -> usize {
+        self.hunks.iter().map(|hunk| hunk.data.len()).sum()
+    }
+}
+impl PlainBytes for Delta {}
@@ -47,6 +47,10 @@ impl ID {
     pub fn to_bytes(&self) -> Vec<u8> {
         self.data.to_bytes()
     }
+
+    pub fn from_hex(data: &str) -> Result<ID> {
+        Ok(ID { data: Data::from_hex(data)? })
+    }
 }
 impl Display for ID {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
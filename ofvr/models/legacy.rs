@@ -0,0 +1,53 @@
+//! Layouts written by earlier versions of ofvr, kept around so that
+//! existing state files can be migrated, see [`crate::OFVRState::migrate`]
+use std::collections::BTreeMap;
+
+use bt_diff::Diff;
+use iocore::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::models::author::Author;
+use crate::models::commit::Commit;
+use crate::traits::PlainBytes;
+use crate::Result;
+
+/// `LegacyCommitData` stored the cumulative [`Diff`] of every version
+/// up to and including its own
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct LegacyCommitData {
+    pub date: t16::Data,
+    pub diff: Diff,
+    pub message: String,
+    pub path: Path,
+    pub author: u16,
+}
+impl LegacyCommitData {
+    pub fn version(&self) -> Vec<u8> {
+        self.diff.current_version()
+    }
+}
+impl PlainBytes for LegacyCommitData {
+    fn from_plain_bytes(bytes: &[u8]) -> Result<LegacyCommitData> {
+        crate::from_strict_bytes::<LegacyCommitData>(bytes)
+    }
+}
+
+/// `UnbranchedState` held a single linear history
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct UnbranchedState {
+    pub commits: Vec<Commit>,
+    pub path: Path,
+    pub authors: BTreeMap<u16, Author>,
+}
+impl PlainBytes for UnbranchedState {
+    fn from_plain_bytes(bytes: &[u8]) -> Result<UnbranchedState> {
+        crate::from_strict_bytes::<UnbranchedState>(bytes)
+    }
+}
+
+/// `UnkeyedConf` held the author of commits only
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct UnkeyedConf {
+    pub author: Author,
+}
+impl PlainBytes for UnkeyedConf {}
@@ -10,3 +10,10 @@ pub mod commit_data;
 pub use commit_data::CommitData;
 pub mod id;
 pub use id::ID;
+pub mod delta;
+pub use delta::{Delta, Hunk};
+pub mod object;
+pub use object::Object;
+pub mod tag;
+pub use tag::Tag;
+pub mod legacy;
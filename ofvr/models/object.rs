@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::id::ID;
+use crate::traits::PlainBytes;
+
+/// `Object` is a version of the file in the content-addressed table
+/// of a state, held by the first commit whose version it is, see
+/// [`crate::OFVRState::objects`]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Deserialize, Serialize)]
+pub struct Object {
+    commit: ID,
+    length: u64,
+}
+impl Object {
+    pub fn new(commit: &ID, length: u64) -> Object {
+        Object {
+            commit: commit.clone(),
+            length,
+        }
+    }
+
+    /// `commit` returns the id of the commit holding the version
+    pub fn commit(&self) -> ID {
+        self.commit.clone()
+    }
+
+    /// `length` of the version in bytes
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+}
+impl PlainBytes for Object {}
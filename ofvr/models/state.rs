@@ -1,25 +1,156 @@
-use std::collections::BTreeMap;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, Read, Write};
+use std::time::Duration;
 
-use bt_diff::{AxisBoundary, Diff};
 use iocore::Path;
 use serde::{Deserialize, Serialize};
 pub use sha3::{Digest, Keccak256, Keccak256Full};
 
+use crate::chunk::{ChunkReader, Storage};
+use crate::data::{Data, DataSeq};
 use crate::errors::{Error, Result};
-use crate::io::read_data;
+use crate::format::{Journal, Record};
+use crate::hash::{keccak256, keccak256_reader};
+use crate::io::{read_data, write_atomic, write_atomic_with};
+use crate::keyframe::KeyframePolicy;
+use crate::lock::{StateLock, DEFAULT_LOCK_TIMEOUT};
+use crate::merge::Merge;
 use crate::models::author::Author;
 use crate::models::commit::Commit;
-// use crate::models::commit_data::CommitData;
+use crate::models::commit_data::CommitData;
+use crate::models::delta::Delta;
+use crate::models::id::ID;
+use crate::models::legacy::{LegacyCommitData, UnbranchedState};
+use crate::models::object::Object;
+use crate::models::tag::Tag;
+use crate::oplog::{Operation, OperationKind};
+use crate::pack::{Pack, STREAM_THRESHOLD};
 use crate::traits::{FileSystemBytes, PlainBytes};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+/// Name of the branch of new state files
+pub const DEFAULT_BRANCH: &str = "main";
+
+/// `Pushed` is what is known of the version of a commit being pushed,
+/// see [`OFVRState::push`]
+enum Pushed<'a> {
+    /// the version cannot be rebuilt, see [`OFVRState::add_commit`]
+    Unknown,
+    Version(&'a [u8]),
+    /// the digest and the chunks of a version streamed into the pack
+    Packed(Vec<u8>, DataSeq),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OFVRState {
+    /// commits of every branch in the order they were committed
     commits: Vec<Commit>,
     path: Path,
     authors: BTreeMap<u16, Author>,
+    /// id of the head commit of each branch
+    branches: BTreeMap<String, ID>,
+    /// name of the current branch, which has no head until its first commit
+    branch: String,
+    tags: BTreeMap<String, Tag>,
+    /// keccak256 of the version of each commit, see [`OFVRState::identify`]
+    #[serde(with = "pairs")]
+    digests: BTreeMap<ID, Vec<u8>>,
+    /// compressed version of some commits, see [`crate::keyframe`]
+    #[serde(with = "pairs")]
+    keyframes: BTreeMap<ID, Vec<u8>>,
+    /// content-addressed table of versions by keccak256, see
+    /// [`OFVRState::objects`]
+    #[serde(with = "pairs")]
+    objects: BTreeMap<Vec<u8>, Object>,
+    /// storage of the version of new commits, see [`Storage`]
+    storage: Storage,
+    /// keccak256 of the chunks of the version of some commits in
+    /// order, see [`OFVRState::manifests`]
+    #[serde(with = "pairs")]
+    manifests: BTreeMap<ID, DataSeq>,
+    /// valid journal at `path` when last read or written, `None` when
+    /// `path` is not known to hold a journal in the current format
+    #[serde(skip)]
+    journal: Cell<Option<Journal>>,
+    /// time mutating operations wait for the lock of the state file
+    #[serde(skip, default = "default_lock_timeout")]
+    lock_timeout: Duration,
+    /// when new commits get a keyframe
+    #[serde(skip)]
+    keyframe_policy: KeyframePolicy,
+}
+fn default_lock_timeout() -> Duration {
+    DEFAULT_LOCK_TIMEOUT
+}
+/// `pairs` serializes maps as a sequence of pairs since maps with
+/// keys other than strings cannot be held in JSON
+pub(crate) mod pairs {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<K: Serialize, V: Serialize, S: Serializer>(
+        map: &BTreeMap<K, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<BTreeMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<(K, V)>::deserialize(deserializer)?.into_iter().collect())
+    }
+}
+impl PartialEq for OFVRState {
+    fn eq(&self, other: &Self) -> bool {
+        self.commits == other.commits
+            && self.path == other.path
+            && self.authors == other.authors
+            && self.branches == other.branches
+            && self.branch == other.branch
+            && self.tags == other.tags
+            && self.digests == other.digests
+            && self.keyframes == other.keyframes
+            && self.objects == other.objects
+            && self.storage == other.storage
+            && self.manifests == other.manifests
+    }
+}
+impl Eq for OFVRState {}
+impl Hash for OFVRState {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.commits.hash(state);
+        self.path.hash(state);
+        self.authors.hash(state);
+        self.branches.hash(state);
+        self.branch.hash(state);
+        self.tags.hash(state);
+        self.digests.hash(state);
+        self.keyframes.hash(state);
+        self.objects.hash(state);
+        self.storage.hash(state);
+        self.manifests.hash(state);
+    }
+}
+impl From<UnbranchedState> for OFVRState {
+    /// `from` converts states of earlier versions of ofvr, whose
+    /// branch is set by [`OFVRState::migrate`]
+    fn from(state: UnbranchedState) -> OFVRState {
+        OFVRState::new(&state.path, state.commits, state.authors)
+    }
 }
 
 impl OFVRState {
+    pub fn authors(&self) -> &BTreeMap<u16, Author> {
+        &self.authors
+    }
+
     pub fn get_author(&self, author: u16) -> Result<Author> {
         if self.authors.is_empty() {
             return Err(Error::StateError(format!("no authors in state")));
@@ -57,21 +188,210 @@ impl OFVRState {
         }
     }
 
+    /// `empty` returns a state without commits to be stored at `path`,
+    /// which must not hold a state file yet, see
+    /// [`OFVRState::from_path`].
+    ///
+    /// Mutating operations reload the state file that another process
+    /// creates at `path` in the meantime
     pub fn empty(path: &Path, author: &Author) -> Result<OFVRState> {
+        if path.is_file() {
+            return Err(Error::StateError(format!("{} already holds a state", path)));
+        }
         let mut authors = BTreeMap::<u16, Author>::new();
-        let commits = Vec::new();
         authors.insert(author.id(), author.clone());
-        let path = path.clone();
+        Ok(OFVRState::new(path, Vec::new(), authors))
+    }
 
-        Ok(OFVRState {
-            commits: commits.into(),
+    /// `new` returns a state holding the given commits on the
+    /// [`DEFAULT_BRANCH`] with every other field at its default
+    fn new(path: &Path, commits: Vec<Commit>, authors: BTreeMap<u16, Author>) -> OFVRState {
+        OFVRState {
+            commits,
+            path: path.clone(),
             authors,
-            path,
-        })
+            branches: BTreeMap::new(),
+            branch: DEFAULT_BRANCH.to_string(),
+            tags: BTreeMap::new(),
+            digests: BTreeMap::new(),
+            keyframes: BTreeMap::new(),
+            objects: BTreeMap::new(),
+            storage: Storage::default(),
+            manifests: BTreeMap::new(),
+            journal: Cell::new(None),
+            lock_timeout: DEFAULT_LOCK_TIMEOUT,
+            keyframe_policy: KeyframePolicy::default(),
+        }
+    }
+
+    pub fn lock_timeout(&self) -> Duration {
+        self.lock_timeout
+    }
+
+    /// `set_lock_timeout` sets how long mutating operations wait for
+    /// another process to release the lock of the state file, see
+    /// [`StateLock`]
+    pub fn set_lock_timeout(&mut self, timeout: Duration) {
+        self.lock_timeout = timeout;
+    }
+
+    pub fn keyframe_policy(&self) -> KeyframePolicy {
+        self.keyframe_policy
+    }
+
+    /// `set_keyframe_policy` sets when new commits get a keyframe, see
+    /// [`crate::keyframe`]
+    pub fn set_keyframe_policy(&mut self, policy: KeyframePolicy) {
+        self.keyframe_policy = policy;
+    }
+
+    /// `lock` takes the exclusive lock of the state file, mutating
+    /// operations take it on their own
+    pub fn lock(&self) -> Result<StateLock> {
+        StateLock::acquire(&self.path, self.lock_timeout)
+    }
+
+    /// `store` rewrites the whole state file, see [`OFVRState::compact`],
+    /// after reloading it when another process changed it since it was
+    /// last read or written
+    pub fn store(&mut self) -> Result<()> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        self.write(self.operation(OperationKind::Store, None)?)?;
+        Ok(())
+    }
+
+    /// `compact` rewrites the journal of the state file as a single
+    /// snapshot and returns its length before and after compaction,
+    /// the state file before it is backed up so that it can be undone,
+    /// see [`OFVRState::undo`]
+    pub fn compact(&mut self) -> Result<(u64, u64)> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        let before = if self.path.is_file() {
+            std::fs::metadata(self.path.to_path_buf())?.len()
+        } else {
+            0
+        };
+        self.write(self.operation(OperationKind::Compact, None)?)?;
+        Ok((before, self.journal.get().map_or(0, |journal| journal.length)))
+    }
+
+    /// `write` backs up the state file, if any, see
+    /// [`OFVRState::backup`], atomically replaces it with a snapshot of
+    /// the state and logs the given operation as replacing it along
+    /// with the backup, which it returns, so that operations before it
+    /// can be undone, see [`OFVRState::undo`], callers must hold the
+    /// lock
+    fn write(&self, mut operation: Operation) -> Result<Option<Path>> {
+        let backup = if self.path.is_file() {
+            Some(self.backup()?)
+        } else {
+            None
+        };
+        let (bytes, journal) = crate::format::encode(self)?;
+        write_atomic(&self.path, &bytes)?;
+        self.journal.set(Some(journal));
+        operation.replaced = true;
+        self.log_operation(operation, backup.as_ref())?;
+        Ok(backup)
+    }
+
+    /// `refresh` reloads the state file when another process changed
+    /// it since it was last read or written, that is when it no longer
+    /// holds the same journal, see [`Journal::starts`], callers must
+    /// hold the lock
+    fn refresh(&mut self) -> Result<()> {
+        if !self.path.is_file() {
+            return Ok(());
+        }
+        if let Some(journal) = self.journal.get() {
+            let length = std::fs::metadata(self.path.to_path_buf())?.len();
+            if length == journal.length && journal.starts(&self.path)? {
+                return Ok(());
+            }
+        }
+        self.replace(OFVRState::from_path(&self.path)?);
+        Ok(())
+    }
+
+    /// `replace` takes the contents of a state loaded from the state file
+    fn replace(&mut self, current: OFVRState) {
+        self.commits = current.commits;
+        self.authors = current.authors;
+        self.branches = current.branches;
+        self.branch = current.branch;
+        self.tags = current.tags;
+        self.digests = current.digests;
+        self.keyframes = current.keyframes;
+        self.objects = current.objects;
+        self.storage = current.storage;
+        self.manifests = current.manifests;
+        self.journal.set(current.journal.get());
+    }
+
+    /// `append` appends the given records to the journal of the state
+    /// file, or writes the whole state when the file does not hold a
+    /// journal in the current format yet, then logs the given
+    /// operation, callers must hold the lock
+    fn append(&self, records: Vec<Record>, operation: Operation) -> Result<()> {
+        let journal = match self.journal.get() {
+            Some(journal) if self.path.is_file() => journal,
+            _ => return self.write(operation).map(|_| ()),
+        };
+        let journal =
+            crate::format::append(&self.path, journal, &records, self.commits.len() as u64)?;
+        self.journal.set(Some(journal));
+        self.log_operation(operation, None)
     }
 
-    pub fn store(&self) -> Result<()> {
-        self.save_to_file(&self.path)?;
+    /// `apply` replays a record read from the journal of the state file
+    pub(crate) fn apply(&mut self, record: Record) -> Result<()> {
+        match record {
+            Record::Author(author) => {
+                self.authors.insert(author.id(), author);
+            },
+            Record::Commit(commit) => self.commits.push(commit),
+            Record::Branch(name, Some(head)) => {
+                self.branches.insert(name, head);
+            },
+            Record::Branch(name, None) => {
+                self.branches.remove(&name);
+            },
+            Record::Switch(name) => self.branch = name,
+            Record::Tag(name, Some(tag)) => {
+                self.tags.insert(name, tag);
+            },
+            Record::Tag(name, None) => {
+                self.tags.remove(&name);
+            },
+            Record::Digest(digest) => {
+                let commit = self.commits.last().cloned().ok_or_else(|| {
+                    Error::FormatError(String::from("journal digest precedes every commit"))
+                })?;
+                self.hold(&commit, &digest)?;
+                self.digests.insert(commit.id, digest);
+            },
+            Record::Keyframe(keyframe) => {
+                let commit = self.commits.last().ok_or_else(|| {
+                    Error::FormatError(String::from("journal keyframe precedes every commit"))
+                })?;
+                self.keyframes.insert(commit.id.clone(), keyframe);
+            },
+            Record::Manifest(manifest) => {
+                let commit = self.commits.last().ok_or_else(|| {
+                    Error::FormatError(String::from("journal manifest precedes every commit"))
+                })?;
+                self.manifests.insert(commit.id.clone(), manifest);
+            },
+            Record::Storage(storage) => self.storage = storage,
+            record => {
+                return Err(Error::FormatError(format!(
+                    "unexpected journal record of kind {}",
+                    record.kind()
+                )))
+            },
+        }
         Ok(())
     }
 
@@ -79,29 +399,227 @@ impl OFVRState {
         self.path.clone()
     }
 
+    /// `journal` returns the valid journal of the state file when last
+    /// read or written, `None` when it is not in the current format
+    pub(crate) fn journal(&self) -> Option<Journal> {
+        self.journal.get()
+    }
+
+    /// `from_path` loads the state stored at `path`, migrating
+    /// layouts written by earlier versions of ofvr in memory, see
+    /// [`crate::format`]
     pub fn from_path(path: &Path) -> Result<OFVRState> {
-        Ok(OFVRState::load_from_file(path)?)
+        OFVRState::from_bytes(path, &path.read_bytes()?)
+    }
+
+    /// `from_bytes` decodes the contents of the state file at `path`
+    pub(crate) fn from_bytes(path: &Path, bytes: &[u8]) -> Result<OFVRState> {
+        let (mut state, journal) = crate::format::read(bytes)?;
+        state.path = path.clone();
+        state.journal.set(journal);
+        Ok(state)
+    }
+
+    /// `upgrade` rewrites the state file at `path` in the current
+    /// [`crate::format::FORMAT_VERSION`] and returns the format
+    /// version it was previously written in
+    pub fn upgrade(path: &Path, lock_timeout: Duration) -> Result<u16> {
+        let _lock = StateLock::acquire(path, lock_timeout)?;
+        let version = crate::format::version(&path.read_bytes()?)?;
+        if version != crate::format::FORMAT_VERSION {
+            let state = OFVRState::from_path(path)?;
+            state.write(state.operation(OperationKind::Upgrade, None)?)?;
+        }
+        Ok(version)
+    }
+
+    /// `migrate` converts commits written by earlier versions of ofvr,
+    /// which carry the cumulative [`bt_diff::Diff`] of every version up
+    /// to their own, see [`LegacyCommitData`], into commits carrying
+    /// only the [`Delta`] from their parent and linked to it, then
+    /// returns the amount of converted commits.
+    ///
+    /// Converted states get their history as the [`DEFAULT_BRANCH`],
+    /// the digest of every version, see [`OFVRState::digest`], and the
+    /// content-addressed table of versions, see [`OFVRState::objects`].
+    ///
+    /// The ids of converted commits and of every commit after them
+    /// change and the conversion is only persisted by the next call to
+    /// [`OFVRState::store`]
+    pub fn migrate(&mut self) -> Result<usize> {
+        let mut migrated = 0;
+        let mut anterior = Vec::<u8>::new();
+        let mut commits = Vec::<Commit>::with_capacity(self.commits.len());
+        let mut renamed = HashMap::<ID, ID>::new();
+        for commit in self.commits.iter() {
+            let bytes = commit.data_bytes();
+            let (data, parents) = match CommitData::from_plain_bytes(&bytes) {
+                Ok(data) => {
+                    let parents = data
+                        .parents()
+                        .iter()
+                        .map(|parent| renamed.get(parent).unwrap_or(parent).clone())
+                        .collect::<Vec<ID>>();
+                    if parents == data.parents() {
+                        anterior = data.delta().apply(&anterior);
+                        commits.push(commit.clone());
+                        continue;
+                    }
+                    (data, parents)
+                },
+                Err(_) => {
+                    // earlier versions of ofvr only held linear histories
+                    let parents =
+                        commits.last().map(|parent| parent.id.clone()).into_iter().collect();
+                    let legacy = LegacyCommitData::from_plain_bytes(&bytes)?;
+                    let data = CommitData::new(
+                        &legacy.date,
+                        Delta::new(&anterior, &legacy.version()),
+                        legacy.author,
+                        &legacy.message,
+                        &legacy.path,
+                    )?;
+                    (data, parents)
+                },
+            };
+            anterior = data.delta().apply(&anterior);
+            let converted = Commit::with_parents(data, parents, self)?;
+            renamed.insert(commit.id.clone(), converted.id.clone());
+            commits.push(converted);
+            migrated += 1;
+        }
+        self.commits = commits;
+        for head in self.branches.values_mut() {
+            if let Some(id) = renamed.get(head) {
+                *head = id.clone();
+            }
+        }
+        if self.branches.is_empty() {
+            if let Some(latest) = self.commits.last() {
+                self.branches.insert(self.branch.clone(), latest.id.clone());
+            }
+        }
+        for (id, converted) in renamed.iter() {
+            if let Some(digest) = self.digests.remove(id) {
+                self.digests.insert(converted.clone(), digest);
+            }
+            if let Some(keyframe) = self.keyframes.remove(id) {
+                self.keyframes.insert(converted.clone(), keyframe);
+            }
+        }
+        let mut digests = Vec::<(ID, Vec<u8>)>::new();
+        self.for_each_version(|commit, version| {
+            if !self.digests.contains_key(&commit.id) {
+                digests.push((commit.id.clone(), keccak256(version)));
+            }
+            Ok(true)
+        })?;
+        self.digests.extend(digests);
+        self.objects.clear();
+        for commit in self.commits.clone().iter() {
+            if let Some(digest) = self.digests.get(&commit.id).cloned() {
+                self.hold(commit, &digest)?;
+            }
+        }
+        Ok(migrated)
     }
 
     pub fn commits(&self) -> &[Commit] {
         &self.commits
     }
 
+    /// `add_commit` appends a commit created with [`Commit::new`] to
+    /// the current branch, refusing commits not linked to its head,
+    /// e.g. when another process committed in the meantime
     pub fn add_commit(&mut self, commit: Commit) -> Result<Commit> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        let operation = self.operation(OperationKind::Commit, commit.author(self).ok().as_ref())?;
+        let parents = commit.data(self)?.parents();
+        let head = self.latest_commit().map(|head| head.id).into_iter().collect::<Vec<ID>>();
+        if parents != head {
+            return Err(Error::CommitError(format!(
+                "commit {} is not a child of the head {} of branch {}",
+                commit.id,
+                head.first().map(|id| id.to_hex()).unwrap_or_else(|| String::from("(none)")),
+                self.branch
+            )));
+        }
+        // commits whose version cannot be rebuilt are added without a
+        // digest nor keyframe and reported by `verify`
+        let delta = commit.data(self)?.delta();
+        let version = delta
+            .check()
+            .and_then(|_| self.latest_version())
+            .ok()
+            .map(|anterior| delta.apply(&anterior));
+        let branch = self.branch.clone();
+        let version = version.as_deref().map_or(Pushed::Unknown, Pushed::Version);
+        self.push(&branch, commit, version, Vec::new(), operation)
+    }
+
+    /// `push` appends `commit` as the new head of `branch` along with
+    /// the given records, its digest, its keyframe, see
+    /// [`KeyframePolicy`], or its chunks, see [`Storage`], and its
+    /// version in the content-addressed table, callers must hold the
+    /// lock
+    fn push(
+        &mut self,
+        branch: &str,
+        commit: Commit,
+        version: Pushed,
+        mut records: Vec<Record>,
+        operation: Operation,
+    ) -> Result<Commit> {
         self.commits.push(commit.clone());
-        self.store()?;
+        self.branches.insert(branch.to_string(), commit.id.clone());
+        records.push(Record::Commit(commit.clone()));
+        match version {
+            Pushed::Unknown => {},
+            Pushed::Version(version) => {
+                let held = self.push_digest(&commit, keccak256(version), &mut records)?;
+                let due = match self.storage {
+                    Storage::Deltas => {
+                        let (length, size) = self.chain(&commit)?;
+                        self.keyframe_policy.is_due(length, size)
+                    },
+                    Storage::Chunks => true,
+                };
+                if !held && due {
+                    records.extend(self.keep_whole(&commit.id, version)?);
+                }
+            },
+            Pushed::Packed(digest, manifest) =>
+                if !self.push_digest(&commit, digest, &mut records)? {
+                    self.manifests.insert(commit.id.clone(), manifest.clone());
+                    records.push(Record::Manifest(manifest));
+                },
+        }
+        records.push(Record::Branch(branch.to_string(), Some(commit.id.clone())));
+        self.append(records, operation)?;
         Ok(commit)
     }
 
+    /// `push_digest` records the digest of the version of a pushed
+    /// commit and holds the version in the content-addressed table
+    /// unless an earlier commit holds it, in which case it returns true
+    fn push_digest(
+        &mut self,
+        commit: &Commit,
+        digest: Vec<u8>,
+        records: &mut Vec<Record>,
+    ) -> Result<bool> {
+        let held = self.objects.contains_key(&digest);
+        self.hold(commit, &digest)?;
+        self.digests.insert(commit.id.clone(), digest.clone());
+        records.push(Record::Digest(digest));
+        Ok(held)
+    }
+
+    /// `latest_commit` returns the head of the current branch
     pub fn latest_commit(&self) -> Option<Commit> {
-        match self.commits.last() {
-            Some(commit) => {
-                Some(commit.clone())
-            },
-            None => {
-                None
-            },
-        }
+        let head = self.branches.get(&self.branch)?;
+        self.commits.iter().rev().find(|commit| commit.id == *head).cloned()
     }
 
     pub fn first_commit(&self) -> Option<Commit> {
@@ -111,35 +629,1371 @@ impl OFVRState {
         }
     }
 
+    pub fn get_commit(&self, id: &ID) -> Result<Commit> {
+        match self.commits.iter().find(|commit| commit.id == *id) {
+            Some(commit) => Ok(commit.clone()),
+            None => Err(Error::StateError(format!("commit {} NOT present in state", id))),
+        }
+    }
+
+    /// `resolve` finds the commit addressed by the given revision, see [`crate::Revision`]
+    pub fn resolve(&self, revision: &str) -> Result<Commit> {
+        crate::Revision::parse(revision)?.resolve(self)
+    }
+
+    /// `log` returns the commits selected by the given query, see [`crate::LogQuery`]
+    pub fn log(&self, query: &crate::LogQuery) -> Result<Vec<Commit>> {
+        query.run(self)
+    }
+
+    /// `blame` attributes the bytes of the version of the given commit
+    /// within `range` to the commits that last changed them, see
+    /// [`crate::blame`]
+    pub fn blame(
+        &self,
+        commit: &Commit,
+        range: Option<std::ops::Range<usize>>,
+    ) -> Result<Vec<crate::BlameRun>> {
+        crate::blame::blame(self, commit, range)
+    }
+
+    /// `search` returns the commits that changed the amount of
+    /// occurrences of the given pattern, see [`crate::search`]
+    pub fn search(&self, pattern: &crate::Pattern) -> Result<Vec<crate::SearchHit>> {
+        crate::search::search(self, pattern)
+    }
+
+    /// `verify` checks the integrity of every commit, see [`crate::verify`]
+    pub fn verify(&self) -> Vec<crate::verify::Problem> {
+        crate::verify::verify(self)
+    }
+
+    /// `version` rebuilds the exact bytes of the file as of the given
+    /// commit by applying the delta of every commit from the nearest
+    /// commit stored whole, see [`OFVRState::whole`], or else from the
+    /// first commit, up to it, following
+    /// the first parent of each commit or the earlier commit holding
+    /// the same version, see [`OFVRState::objects`]
+    pub fn version(&self, commit: &Commit) -> Result<Vec<u8>> {
+        let positions = self.positions();
+        let mut deltas = Vec::<Delta>::new();
+        let mut version = Vec::<u8>::new();
+        let mut current = Some(commit.id.clone());
+        while let Some(id) = current {
+            if let Some(whole) = self.whole(&id)? {
+                version = whole;
+                break;
+            }
+            let index = *positions.get(&id).ok_or_else(|| {
+                Error::StateError(format!("commit {} NOT present in state", id))
+            })?;
+            if let Some(origin) = self.origin(index, &positions) {
+                current = Some(self.commits[origin].id.clone());
+                continue;
+            }
+            let data = self.commits[index].data(self)?;
+            let delta = data.delta();
+            delta.check()?;
+            self.check_digest(&id, &delta)?;
+            deltas.push(delta);
+            current = data.parents().into_iter().next();
+        }
+        for delta in deltas.iter().rev() {
+            version = delta.apply(&version);
+        }
+        Ok(version)
+    }
+
+    /// `version_at` rebuilds the exact bytes of the file as of the commit at `index`
+    pub fn version_at(&self, index: usize) -> Result<Vec<u8>> {
+        match self.commits.get(index) {
+            Some(commit) => self.version(commit),
+            None => Err(Error::StateError(format!(
+                "commit index {} out of range: {} has {} commits",
+                index,
+                self.path,
+                self.commits.len()
+            ))),
+        }
+    }
+
+    /// `latest_version` rebuilds the bytes of the head of the current
+    /// branch or returns an empty vector when it has no commits
+    pub fn latest_version(&self) -> Result<Vec<u8>> {
+        match self.latest_commit() {
+            Some(commit) => self.version(&commit),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// `is_recorded` returns true when `data` matches the bytes of any commit in the state
+    pub fn is_recorded(&self, data: &[u8]) -> Result<bool> {
+        self.is_recorded_digest(&keccak256(data))
+    }
+
+    /// `is_recorded_digest` returns true when the version of any
+    /// commit has the given keccak256
+    pub fn is_recorded_digest(&self, digest: &[u8]) -> Result<bool> {
+        for commit in self.commits.iter() {
+            if self.digest(commit)? == digest {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// `digests` returns the keccak256 of the version of each commit by id
+    pub fn digests(&self) -> &BTreeMap<ID, Vec<u8>> {
+        &self.digests
+    }
+
+    /// `digest` returns the keccak256 of the version of the given
+    /// commit, rebuilding the version only when the state holds no
+    /// digest for it
+    pub fn digest(&self, commit: &Commit) -> Result<Vec<u8>> {
+        match self.digests.get(&commit.id) {
+            Some(digest) => Ok(digest.clone()),
+            None => Ok(keccak256(&self.version(commit)?)),
+        }
+    }
+
+    /// `identify` finds the commits whose version is identical to
+    /// `data` by their digest or else the nearest version, see
+    /// [`crate::Identity`]
+    pub fn identify(&self, data: &[u8]) -> Result<crate::Identity> {
+        crate::identify::identify(self, data)
+    }
+
+    /// `identify_file` is [`OFVRState::identify`] for the file at
+    /// `path`, which is only read whole when no version matches it
+    pub fn identify_file(&self, path: &Path) -> Result<crate::Identity> {
+        crate::identify::identify_file(self, path)
+    }
+
+    /// `for_each_version` calls `visit` with each commit and its
+    /// version in the order they were committed until it returns
+    /// false, rebuilding each version from the version of the previous
+    /// commit when it is its first parent
+    pub(crate) fn for_each_version(
+        &self,
+        mut visit: impl FnMut(&Commit, &[u8]) -> Result<bool>,
+    ) -> Result<()> {
+        let positions = self.positions();
+        let mut previous: Option<(ID, Vec<u8>)> = None;
+        for (index, commit) in self.commits.iter().enumerate() {
+            let commit_data = commit.data(self)?;
+            let delta = commit_data.delta();
+            delta.check()?;
+            self.check_digest(&commit.id, &delta)?;
+            let version = match (commit_data.parents().first(), &previous) {
+                _ if self.origin(index, &positions).is_some() => self.version(commit)?,
+                _ if self.referenced(commit, &delta)? => self.version(commit)?,
+                (None, _) => delta.apply(&[]),
+                (Some(parent), Some((id, anterior))) if parent == id => delta.apply(anterior),
+                _ => self.version(commit)?,
+            };
+            if !visit(commit, &version)? {
+                break;
+            }
+            previous = Some((commit.id.clone(), version));
+        }
+        Ok(())
+    }
+
+    /// `delta` returns the delta turning the version of the first
+    /// parent of the given commit into its version, computing it when
+    /// the commit only references the version of an earlier commit,
+    /// see [`OFVRState::objects`]
+    pub fn delta(&self, commit: &Commit) -> Result<Delta> {
+        let data = commit.data(self)?;
+        let held = match self.digests.get(&commit.id).and_then(|digest| self.objects.get(digest)) {
+            Some(object) => object.commit() == commit.id,
+            None => true,
+        };
+        if held && !self.referenced(commit, &data.delta())? {
+            return Ok(data.delta());
+        }
+        let anterior = match data.parents().first() {
+            Some(parent) => self.version(&self.get_commit(parent)?)?,
+            None => Vec::new(),
+        };
+        Ok(Delta::new(&anterior, &self.version(commit)?))
+    }
+
+    fn positions(&self) -> HashMap<ID, usize> {
+        self.commits.iter().enumerate().map(|(index, commit)| (commit.id.clone(), index)).collect()
+    }
+
+    /// `checkout` writes the version of the given commit into `target`
+    /// atomically, refusing to overwrite a file whose contents were
+    /// never committed unless `force` is true
+    pub fn checkout(&self, commit: &Commit, target: &Path, force: bool) -> Result<Vec<u8>> {
+        let data = self.version(commit)?;
+        if !force && target.is_file() {
+            let working = read_data(target)?;
+            if working != data && !self.is_recorded(&working)? {
+                return Err(Error::CheckoutError(format!(
+                    "{} has changes not committed to {}",
+                    target, self.path
+                )));
+            }
+        }
+        write_atomic(target, &data)?;
+        Ok(data)
+    }
+
+    /// `checkout_to` is [`OFVRState::checkout`] streaming the version
+    /// into `target`, see [`OFVRState::write_version`], and returns its
+    /// length
+    pub fn checkout_to(&self, commit: &Commit, target: &Path, force: bool) -> Result<u64> {
+        if !force && target.is_file() {
+            let working = keccak256_reader(File::open(target.to_path_buf())?)?;
+            if working != self.digest(commit)? && !self.is_recorded_digest(&working)? {
+                return Err(Error::CheckoutError(format!(
+                    "{} has changes not committed to {}",
+                    target, self.path
+                )));
+            }
+        }
+        write_atomic_with(target, |writer| self.write_version(commit, writer))
+    }
+
+    /// `write_version` writes the version of the given commit into
+    /// `writer` and returns its length. Versions stored as chunks are
+    /// written one chunk at a time, other versions are rebuilt in
+    /// memory first, see [`OFVRState::version`], which only holds
+    /// versions shorter than [`STREAM_THRESHOLD`] unless they were
+    /// committed by earlier versions of ofvr, see [`OFVRState::commit_on`]
+    pub fn write_version(&self, commit: &Commit, writer: &mut dyn Write) -> Result<u64> {
+        let positions = self.positions();
+        let index = *positions.get(&commit.id).ok_or_else(|| {
+            Error::StateError(format!("commit {} NOT present in state", commit.id))
+        })?;
+        let index = self.origin(index, &positions).unwrap_or(index);
+        if !self.keyframes.contains_key(&self.commits[index].id) {
+            if let Some(manifest) = self.manifests.get(&self.commits[index].id) {
+                return self.write_chunks(manifest, writer);
+            }
+        }
+        let version = self.version(commit)?;
+        writer.write_all(&version)?;
+        Ok(version.len() as u64)
+    }
+
+    /// `commit_blob` records `data` as a new commit on top of the
+    /// head of the current branch, see [`OFVRState::commit_blob_on`]
     pub fn commit_blob(&mut self, data: &[u8], author: &Author, message: &str) -> Result<Commit> {
-        let author_id = if let Ok(author_id) = self.get_author_id(author) {
-            author_id
+        let branch = self.branch.clone();
+        self.commit_blob_on(&branch, data, author, message)
+    }
+
+    /// `commit_blob_on` records `data` as a new commit on top of the
+    /// head of `branch` in the state file, including commits appended
+    /// by other processes since the state was loaded
+    pub fn commit_blob_on(
+        &mut self,
+        branch: &str,
+        data: &[u8],
+        author: &Author,
+        message: &str,
+    ) -> Result<Commit> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        let operation = self.operation(OperationKind::Commit, Some(author))?;
+        let head = self.branch_head(branch)?;
+        self.record(branch, head.into_iter().collect(), data, author, message, operation)
+    }
+
+    /// `commit_stream` records the bytes of `reader` as a new commit on
+    /// top of the head of the current branch, see
+    /// [`OFVRState::commit_stream_on`]
+    pub fn commit_stream(
+        &mut self,
+        reader: impl Read,
+        author: &Author,
+        message: &str,
+    ) -> Result<Commit> {
+        let branch = self.branch.clone();
+        self.commit_stream_on(&branch, reader, author, message)
+    }
+
+    /// `commit_stream_on` records the bytes of `reader` as a new commit
+    /// on top of the head of `branch` without holding them in memory:
+    /// they are split into chunks as they are read, see
+    /// [`crate::chunk::ChunkReader`], and the chunks missing from the
+    /// pack are appended to it, see [`crate::pack`], whatever the
+    /// [`Storage`] of the state
+    pub fn commit_stream_on(
+        &mut self,
+        branch: &str,
+        reader: impl Read,
+        author: &Author,
+        message: &str,
+    ) -> Result<Commit> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        let operation = self.operation(OperationKind::Commit, Some(author))?;
+        let head = self.branch_head(branch)?;
+        self.stream(branch, head.into_iter().collect(), reader, author, message, operation)
+    }
+
+    /// `stream` records the bytes of `reader` as a new head of `branch`
+    /// stored as chunks in the pack, see
+    /// [`OFVRState::commit_stream_on`], callers must hold the lock
+    fn stream(
+        &mut self,
+        branch: &str,
+        parents: Vec<Commit>,
+        reader: impl Read,
+        author: &Author,
+        message: &str,
+        operation: Operation,
+    ) -> Result<Commit> {
+        let mut records = Vec::<Record>::new();
+        let author_id = self.push_author(author, &mut records)?;
+        let mut pack = self.pack()?;
+        let mut digest = Keccak256::new();
+        let mut manifest = DataSeq::new();
+        let mut length = 0usize;
+        for chunk in ChunkReader::new(reader) {
+            let chunk = chunk?;
+            digest.update(&chunk);
+            length += chunk.len();
+            pack.append(&chunk)?;
+            manifest.push(Data::new(keccak256(&chunk)));
+        }
+        // the chunks are persisted before the state refers to them
+        pack.sync()?;
+        let commit_data = CommitData::new(
+            &t16::Data::now(),
+            Delta::with_length(length),
+            author_id,
+            message,
+            &self.path,
+        )?;
+        let parents = parents.into_iter().map(|parent| parent.id).collect();
+        let commit = Commit::with_parents(commit_data, parents, self)?;
+        let version = Pushed::Packed(digest.finalize().to_vec(), manifest);
+        self.push(branch, commit, version, records, operation)
+    }
+
+    /// `push_author` returns the id of the given author, adding it to
+    /// the state along with a record when missing
+    fn push_author(&mut self, author: &Author, records: &mut Vec<Record>) -> Result<u16> {
+        if let Ok(author_id) = self.get_author_id(author) {
+            return Ok(author_id);
+        }
+        records.push(Record::Author(author.clone()));
+        self.add_author(author)
+    }
+
+    /// `record` records `data` as a new head of `branch` whose delta
+    /// is computed from the first of the given parents unless an
+    /// earlier commit holds the same version or the version is
+    /// streamed into the pack instead, see [`OFVRState::streams`],
+    /// callers must hold the lock
+    fn record(
+        &mut self,
+        branch: &str,
+        parents: Vec<Commit>,
+        data: &[u8],
+        author: &Author,
+        message: &str,
+        operation: Operation,
+    ) -> Result<Commit> {
+        // versions held by earlier commits are only referenced, see
+        // `OFVRState::objects`
+        let held = self.objects.contains_key(&keccak256(data));
+        if !held && self.streams(data.len(), parents.first())? {
+            return self.stream(branch, parents, data, author, message, operation);
+        }
+        let mut records = Vec::<Record>::new();
+        let author_id = self.push_author(author, &mut records)?;
+        let delta = if held {
+            Delta::new(data, data)
         } else {
-            let author_id = self.add_author(author)?;
-            self.store()?;
-            author_id
-        };
-        let mut diff = match self.latest_commit() {
-            Some(commit) => commit.data(&self)?.diff(),
-            None => Diff::new(AxisBoundary::default()),
+            match parents.first() {
+                Some(parent) => Delta::new(&self.version(parent)?, data),
+                None => Delta::new(&[], data),
+            }
         };
-        diff.update(data)?;
-        Ok(self.add_commit(Commit::now(
-            diff,
+        let commit_data = CommitData::new(
+            &t16::Data::now(),
+            delta,
             author_id,
             message,
             &self.path,
-            self,
-        )?)?)
+        )?;
+        let parents = parents.into_iter().map(|parent| parent.id).collect();
+        let commit = Commit::with_parents(commit_data, parents, self)?;
+        self.push(branch, commit, Pushed::Version(data), records, operation)
+    }
+
+    /// `streams` returns true when a version of the given length on top
+    /// of the given parent is streamed into the pack rather than
+    /// stored as a delta: when the state stores versions as chunks, or
+    /// when either version is at least [`STREAM_THRESHOLD`] long since
+    /// computing the delta holds both in memory
+    fn streams(&self, length: usize, parent: Option<&Commit>) -> Result<bool> {
+        if self.storage == Storage::Chunks || length >= STREAM_THRESHOLD {
+            return Ok(true);
+        }
+        match parent {
+            Some(parent) => Ok(parent.data(self)?.delta().length() >= STREAM_THRESHOLD),
+            None => Ok(false),
+        }
     }
 }
 
+/// Keyframes
 impl OFVRState {
+    /// `keyframes` returns the compressed version of the commits that
+    /// have a keyframe by id, see [`crate::keyframe`]
+    pub fn keyframes(&self) -> &BTreeMap<ID, Vec<u8>> {
+        &self.keyframes
+    }
+
+    /// `whole` returns the version of the given commit when it is
+    /// stored whole, as a keyframe or as chunks
+    pub fn whole(&self, id: &ID) -> Result<Option<Vec<u8>>> {
+        if let Some(keyframe) = self.keyframes.get(id) {
+            return Ok(Some(crate::keyframe::decompress(keyframe)?));
+        }
+        match self.manifests.get(id) {
+            Some(manifest) => Ok(Some(self.assemble(manifest)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// `keep_whole` stores the given version of a commit whole, as a
+    /// keyframe or as chunks appended to the pack according to the
+    /// [`Storage`] of the state, and returns the records storing it
+    fn keep_whole(&mut self, id: &ID, version: &[u8]) -> Result<Vec<Record>> {
+        let mut records = Vec::<Record>::new();
+        match self.storage {
+            Storage::Deltas => {
+                let keyframe = crate::keyframe::compress(version)?;
+                self.keyframes.insert(id.clone(), keyframe.clone());
+                records.push(Record::Keyframe(keyframe));
+            },
+            Storage::Chunks => {
+                let mut pack = self.pack()?;
+                let mut manifest = DataSeq::new();
+                for chunk in crate::chunk::split(version) {
+                    pack.append(chunk)?;
+                    manifest.push(Data::new(keccak256(chunk)));
+                }
+                // the chunks are persisted before the state refers to them
+                pack.sync()?;
+                self.manifests.insert(id.clone(), manifest.clone());
+                records.push(Record::Manifest(manifest));
+            },
+        }
+        Ok(records)
+    }
+
+    /// `chain` returns the amount and the total size of the deltas
+    /// applied on top of the nearest commit stored whole, see
+    /// [`OFVRState::whole`], or of the first commit, to rebuild the
+    /// version of the given commit, see [`OFVRState::version`]
+    pub fn chain(&self, commit: &Commit) -> Result<(usize, u64)> {
+        let positions = self.positions();
+        let (mut length, mut size) = (0, 0);
+        let mut current = commit.id.clone();
+        while !self.keyframes.contains_key(&current) && !self.manifests.contains_key(&current) {
+            let index = *positions.get(&current).ok_or_else(|| {
+                Error::StateError(format!("commit {} NOT present in state", current))
+            })?;
+            if let Some(origin) = self.origin(index, &positions) {
+                current = self.commits[origin].id.clone();
+                continue;
+            }
+            let data = self.commits[index].data(self)?;
+            current = match data.parents().into_iter().next() {
+                Some(parent) => parent,
+                None => break,
+            };
+            length += 1;
+            size += data.delta().size() as u64;
+        }
+        Ok((length, size))
+    }
+
+    /// `repack` stores a keyframe for every commit whose chain of
+    /// deltas is due one according to the [`KeyframePolicy`], taking
+    /// the keyframes stored meanwhile into account, rewrites the state
+    /// file when any was stored and returns their amount
+    pub fn repack(&mut self) -> Result<usize> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        let operation = self.operation(OperationKind::Repack, None)?;
+        let positions = self.positions();
+        let mut chains = HashMap::<ID, (usize, u64)>::new();
+        let mut keyframes = Vec::<(ID, Vec<u8>)>::new();
+        self.for_each_version(|commit, version| {
+            let data = commit.data(self)?;
+            let chain_of = |id: &ID| {
+                chains.get(id).copied().ok_or_else(|| {
+                    Error::StateError(format!("commit {} does not precede {}", id, commit.id))
+                })
+            };
+            if let Some(origin) = self.origin(positions[&commit.id], &positions) {
+                // commits referencing the version of an earlier commit
+                // share its keyframe
+                let chain = chain_of(&self.commits[origin].id)?;
+                chains.insert(commit.id.clone(), chain);
+                return Ok(true);
+            }
+            let chain = match data.parents().first() {
+                _ if self.keyframes.contains_key(&commit.id)
+                    || self.manifests.contains_key(&commit.id) =>
+                    (0, 0),
+                Some(parent) => {
+                    let (length, size) = chain_of(parent)?;
+                    (length + 1, size + data.delta().size() as u64)
+                },
+                None => (0, 0),
+            };
+            let chain = if self.keyframe_policy.is_due(chain.0, chain.1) {
+                keyframes.push((commit.id.clone(), crate::keyframe::compress(version)?));
+                (0, 0)
+            } else {
+                chain
+            };
+            chains.insert(commit.id.clone(), chain);
+            Ok(true)
+        })?;
+        if keyframes.is_empty() {
+            return Ok(0);
+        }
+        let stored = keyframes.len();
+        self.keyframes.extend(keyframes);
+        self.write(operation)?;
+        Ok(stored)
+    }
+}
+
+/// Content-addressed versions
+///
+/// The version of each commit is held in a content-addressed table
+/// by its digest by the first commit whose version it is. Later
+/// commits of the same version, e.g. after a revert, hold an empty
+/// delta and only reference it by their digest, without which
+/// rebuilding their version fails, see [`OFVRState::version`].
+impl OFVRState {
+    /// `objects` returns the content-addressed table of versions by
+    /// keccak256, see [`Object`]
+    pub fn objects(&self) -> &BTreeMap<Vec<u8>, Object> {
+        &self.objects
+    }
+
+    /// `dedup_stats` returns how many commits only reference the
+    /// version of an earlier commit, see [`crate::DedupStats`]
+    pub fn dedup_stats(&self) -> Result<crate::DedupStats> {
+        crate::dedup::stats(self)
+    }
+
+    /// `hold` adds the version of the given commit to the
+    /// content-addressed table unless an earlier commit holds it
+    fn hold(&mut self, commit: &Commit, digest: &[u8]) -> Result<()> {
+        if !self.objects.contains_key(digest) {
+            let length = commit.data(self)?.delta().length() as u64;
+            self.objects.insert(digest.to_vec(), Object::new(&commit.id, length));
+        }
+        Ok(())
+    }
+
+    /// `referenced` returns true when the given commit only referenced
+    /// the version of a commit dropped since then, see
+    /// [`OFVRState::rehold`], or was stored as chunks, in which case
+    /// its delta holds no hunks and its version is stored whole, see
+    /// [`OFVRState::whole`]
+    fn referenced(&self, commit: &Commit, delta: &Delta) -> Result<bool> {
+        if !delta.hunks().is_empty() {
+            return Ok(false);
+        }
+        match (self.whole(&commit.id)?, self.digests.get(&commit.id)) {
+            (Some(whole), Some(digest)) => Ok(keccak256(&whole) == *digest),
+            _ => Ok(false),
+        }
+    }
+
+    /// `check_digest` returns an error when the commit with the given
+    /// id holds a delta without hunks, as commits referencing the
+    /// version of an earlier commit do, but no digest to find that
+    /// version by, in which case applying the delta to the version of
+    /// its parent would silently rebuild another version
+    pub(crate) fn check_digest(&self, id: &ID, delta: &Delta) -> Result<()> {
+        if !delta.hunks().is_empty()
+            || self.digests.contains_key(id)
+            || self.keyframes.contains_key(id)
+            || self.manifests.contains_key(id)
+        {
+            return Ok(());
+        }
+        Err(Error::StateError(format!(
+            "commit {} holds no changes nor the digest of the version it references",
+            id
+        )))
+    }
+
+    /// `origin` returns the position of the earlier commit holding the
+    /// version of the commit at `index` in the content-addressed table
+    pub(crate) fn origin(&self, index: usize, positions: &HashMap<ID, usize>) -> Option<usize> {
+        let digest = self.digests.get(&self.commits[index].id)?;
+        let origin = *positions.get(&self.objects.get(digest)?.commit())?;
+        if origin < index {
+            Some(origin)
+        } else {
+            None
+        }
+    }
+}
+
+/// Chunks
+///
+/// State files storing versions as chunks, see [`Storage::Chunks`],
+/// split the version of each new commit into chunks, see
+/// [`crate::chunk`], stored once by keccak256 in the pack next to the
+/// state file, see [`crate::pack`], and hold the list of the chunks of
+/// the version of each commit instead of a delta
+impl OFVRState {
+    /// `storage` returns how the version of new commits is stored
+    pub fn storage(&self) -> Storage {
+        self.storage
+    }
+
+    /// `set_storage` changes how the version of commits added from now
+    /// on is stored, the version of earlier commits is left as is
+    pub fn set_storage(&mut self, storage: Storage) -> Result<()> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        if storage == self.storage {
+            return Ok(());
+        }
+        let operation = self.operation(OperationKind::Storage, None)?;
+        self.storage = storage;
+        self.append(vec![Record::Storage(storage)], operation)
+    }
+
+    /// `manifests` returns the keccak256 of the chunks of the version
+    /// of the commits stored as chunks in order, by id
+    pub fn manifests(&self) -> &BTreeMap<ID, DataSeq> {
+        &self.manifests
+    }
+
+    /// `assemble` returns the version made of the chunks of the given manifest
+    pub fn assemble(&self, manifest: &DataSeq) -> Result<Vec<u8>> {
+        let mut version = Vec::<u8>::new();
+        self.write_chunks(manifest, &mut version)?;
+        Ok(version)
+    }
+
+    /// `write_chunks` writes the chunks of the given manifest, held by
+    /// the pack, into `writer` one at a time and returns their total
+    /// length
+    fn write_chunks(&self, manifest: &DataSeq, writer: &mut dyn Write) -> Result<u64> {
+        let mut pack: Option<Pack> = None;
+        let mut length = 0u64;
+        for index in 0..manifest.len() {
+            let hash = &manifest[index];
+            if pack.is_none() {
+                pack = Some(self.pack()?);
+            }
+            match pack.as_mut().map(|pack| pack.get(&hash.inner)).transpose()?.flatten() {
+                Some(chunk) => {
+                    writer.write_all(&chunk)?;
+                    length += chunk.len() as u64;
+                },
+                None => {
+                    return Err(Error::StateError(format!(
+                        "chunk {} NOT present in state",
+                        hash.to_hex()
+                    )))
+                },
+            }
+        }
+        Ok(length)
+    }
+
+    /// `pack` loads the pack holding the chunks of versions stored as
+    /// chunks, see [`crate::pack`]
+    pub fn pack(&self) -> Result<Pack> {
+        Pack::load(&self.path)
+    }
+}
+
+/// Branches
+impl OFVRState {
+    /// `current_branch` returns the name of the branch commits are added to
+    pub fn current_branch(&self) -> String {
+        self.branch.clone()
+    }
+
+    /// `branches` returns the id of the head commit of each branch
+    pub fn branches(&self) -> &BTreeMap<String, ID> {
+        &self.branches
+    }
+
+    /// `branch_head` returns the head commit of the given branch or
+    /// `None` when it is the current branch and has no commits yet
+    pub fn branch_head(&self, name: &str) -> Result<Option<Commit>> {
+        match self.branches.get(name) {
+            Some(head) => Ok(Some(self.get_commit(head)?)),
+            None if name == self.branch => Ok(None),
+            None => Err(Error::BranchError(format!("branch {} does not exist", name))),
+        }
+    }
+
+    /// `history` returns the commits reachable from the head of the
+    /// given branch in the order they were committed
+    pub fn history(&self, branch: &str) -> Result<Vec<Commit>> {
+        let head = match self.branch_head(branch)? {
+            Some(head) => head,
+            None => return Ok(Vec::new()),
+        };
+        let mut indices = self.reachable(&head)?.into_iter().collect::<Vec<usize>>();
+        indices.sort();
+        Ok(indices.into_iter().map(|index| self.commits[index].clone()).collect())
+    }
+
+    /// `reachable` returns the positions of the given commit and of
+    /// every commit reachable through its parents
+    fn reachable(&self, commit: &Commit) -> Result<HashSet<usize>> {
+        let positions = self.positions();
+        let mut reachable = HashSet::<usize>::new();
+        let mut pending = vec![commit.id.clone()];
+        while let Some(id) = pending.pop() {
+            let index = *positions.get(&id).ok_or_else(|| {
+                Error::StateError(format!("commit {} NOT present in state", id))
+            })?;
+            if reachable.insert(index) {
+                pending.extend(self.commits[index].data(self)?.parents());
+            }
+        }
+        Ok(reachable)
+    }
+
+    /// `create_branch` creates a branch whose head is the given commit
+    pub fn create_branch(&mut self, name: &str, head: &Commit) -> Result<()> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        if !crate::revision::is_valid_reference_name(name) {
+            return Err(Error::BranchError(format!("invalid branch name {:#?}", name)));
+        }
+        if self.branches.contains_key(name) {
+            return Err(Error::BranchError(format!("branch {} already exists", name)));
+        }
+        if self.tags.contains_key(name) {
+            return Err(Error::BranchError(format!("a tag named {} already exists", name)));
+        }
+        self.get_commit(&head.id)?;
+        let operation = self.operation(OperationKind::BranchCreate, None)?;
+        self.branches.insert(name.to_string(), head.id.clone());
+        self.append(vec![Record::Branch(name.to_string(), Some(head.id.clone()))], operation)
+    }
+
+    /// `delete_branch` deletes a branch other than the current branch
+    /// and returns the id of its head, its commits are kept
+    pub fn delete_branch(&mut self, name: &str) -> Result<ID> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        if name == self.branch {
+            return Err(Error::BranchError(format!("cannot delete the current branch {}", name)));
+        }
+        let operation = self.operation(OperationKind::BranchDelete, None)?;
+        let head = self
+            .branches
+            .remove(name)
+            .ok_or_else(|| Error::BranchError(format!("branch {} does not exist", name)))?;
+        self.append(vec![Record::Branch(name.to_string(), None)], operation)?;
+        Ok(head)
+    }
+
+    /// `switch_branch` makes the given branch the current branch
+    pub fn switch_branch(&mut self, name: &str) -> Result<()> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        if !self.branches.contains_key(name) {
+            return Err(Error::BranchError(format!("branch {} does not exist", name)));
+        }
+        let operation = self.operation(OperationKind::BranchSwitch, None)?;
+        self.branch = name.to_string();
+        self.append(vec![Record::Switch(name.to_string())], operation)
+    }
+}
+
+/// Tags
+impl OFVRState {
+    /// `tags` returns every tag by name
+    pub fn tags(&self) -> &BTreeMap<String, Tag> {
+        &self.tags
+    }
+
+    pub fn get_tag(&self, name: &str) -> Result<Tag> {
+        match self.tags.get(name) {
+            Some(tag) => Ok(tag.clone()),
+            None => Err(Error::TagError(format!("tag {} does not exist", name))),
+        }
+    }
+
+    /// `tags_of` returns the tags of the given commit
+    pub fn tags_of(&self, commit: &Commit) -> Vec<Tag> {
+        self.tags.values().filter(|tag| tag.commit() == commit.id).cloned().collect()
+    }
+
+    /// `add_tag` names the given commit, tags are never moved once
+    /// added but can be deleted, see [`OFVRState::delete_tag`]
+    pub fn add_tag(
+        &mut self,
+        name: &str,
+        commit: &Commit,
+        message: Option<&str>,
+        tagger: Option<&Author>,
+    ) -> Result<Tag> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        if !crate::revision::is_valid_reference_name(name) {
+            return Err(Error::TagError(format!("invalid tag name {:#?}", name)));
+        }
+        if let Some(tag) = self.tags.get(name) {
+            return Err(Error::TagError(format!(
+                "tag {} already exists at {}",
+                name,
+                tag.commit()
+            )));
+        }
+        if self.branches.contains_key(name) {
+            return Err(Error::TagError(format!("a branch named {} already exists", name)));
+        }
+        self.get_commit(&commit.id)?;
+        let operation = self.operation(OperationKind::TagAdd, tagger)?;
+        let mut records = Vec::<Record>::new();
+        let tagger = match tagger {
+            Some(tagger) => Some(match self.get_author_id(tagger) {
+                Ok(tagger_id) => tagger_id,
+                Err(_) => {
+                    records.push(Record::Author(tagger.clone()));
+                    self.add_author(tagger)?
+                },
+            }),
+            None => None,
+        };
+        let tag = Tag::new(name, &commit.id, &t16::Data::now(), message, tagger);
+        self.tags.insert(name.to_string(), tag.clone());
+        records.push(Record::Tag(name.to_string(), Some(tag.clone())));
+        self.append(records, operation)?;
+        Ok(tag)
+    }
+
+    /// `delete_tag` deletes a tag and returns it, the tagged commit is kept
+    pub fn delete_tag(&mut self, name: &str) -> Result<Tag> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        let operation = self.operation(OperationKind::TagDelete, None)?;
+        let tag = self
+            .tags
+            .remove(name)
+            .ok_or_else(|| Error::TagError(format!("tag {} does not exist", name)))?;
+        self.append(vec![Record::Tag(name.to_string(), None)], operation)?;
+        Ok(tag)
+    }
+}
+
+/// History rewriting
+///
+/// Rewriting replaces commits of the current branch, drops the
+/// commits no branch or tag refers to any longer, rebuilds the
+/// content-addressed table of versions and rewrites the whole state
+/// file once a backup of it is written next to it
+impl OFVRState {
+    /// `amend` replaces the message and/or the author of the head of
+    /// the current branch, which changes its id, and returns the
+    /// amended commit along with the path of the backup
+    pub fn amend(
+        &mut self,
+        message: Option<&str>,
+        author: Option<&Author>,
+    ) -> Result<(Commit, Path)> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        let operation = self.operation(OperationKind::Amend, author)?;
+        let head = self.rewritable_head()?;
+        let mut data = head.data(self)?;
+        if let Some(message) = message {
+            data.set_message(message);
+        }
+        if let Some(author) = author {
+            let author_id = match self.get_author_id(author) {
+                Ok(author_id) => author_id,
+                Err(_) => self.add_author(author)?,
+            };
+            data.set_author(author_id);
+        }
+        let parents = data.parents();
+        let amended = Commit::with_parents(data, parents, self)?;
+        if amended.id == head.id {
+            return Err(Error::CommitError(format!("nothing to amend in commit {}", head.id)));
+        }
+        self.copy_version(&head.id, &amended.id)?;
+        self.commits.push(amended.clone());
+        self.branches.insert(self.branch.clone(), amended.id.clone());
+        Ok((amended, self.rewrite(operation)?))
+    }
+
+    /// `reset` makes the given commit of the history of the current
+    /// branch its head, dropping the commits after it, and returns
+    /// the path of the backup
+    pub fn reset(&mut self, commit: &Commit) -> Result<Path> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        let operation = self.operation(OperationKind::Reset, None)?;
+        let head = self.rewritable_head()?;
+        if commit.id == head.id {
+            return Err(Error::CommitError(format!(
+                "nothing to reset: {} is the head of branch {}",
+                commit.id, self.branch
+            )));
+        }
+        let reachable = self.reachable(&head)?;
+        match self.positions().get(&commit.id) {
+            Some(position) if reachable.contains(position) => {},
+            _ => {
+                return Err(Error::CommitError(format!(
+                    "commit {} is not in the history of branch {}",
+                    commit.id, self.branch
+                )))
+            },
+        }
+        self.branches.insert(self.branch.clone(), commit.id.clone());
+        self.rewrite(operation)
+    }
+
+    /// `squash` collapses the commits after `from` up to and
+    /// including `to` into a single commit holding the version of
+    /// `to`, where both are first parents of the head of the current
+    /// branch, and returns the squashed commit along with the path of
+    /// the backup. Commits after `to` are linked to the squashed
+    /// commit.
+    pub fn squash(
+        &mut self,
+        from: &Commit,
+        to: &Commit,
+        author: &Author,
+        message: &str,
+    ) -> Result<(Commit, Path)> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        let operation = self.operation(OperationKind::Squash, Some(author))?;
+        let mut chain = Vec::<Commit>::new();
+        let mut current = Some(self.rewritable_head()?);
+        while let Some(commit) = current.take() {
+            if commit.id == from.id {
+                break;
+            }
+            current = match commit.data(self)?.parents().first() {
+                Some(parent) => Some(self.get_commit(parent)?),
+                None => {
+                    return Err(Error::CommitError(format!(
+                        "commit {} is not a first parent of the head of branch {}",
+                        from.id, self.branch
+                    )))
+                },
+            };
+            chain.push(commit);
+        }
+        let end = match chain.iter().position(|commit| commit.id == to.id) {
+            Some(end) => end,
+            None => {
+                return Err(Error::CommitError(format!(
+                    "commit {} is not a first parent of the head of branch {} after {}",
+                    to.id, self.branch, from.id
+                )))
+            },
+        };
+        if chain.len() - end < 2 {
+            return Err(Error::CommitError(format!(
+                "nothing to squash between {} and {}",
+                from.id, to.id
+            )));
+        }
+        let author_id = match self.get_author_id(author) {
+            Ok(author_id) => author_id,
+            Err(_) => self.add_author(author)?,
+        };
+        let data = to.data(self)?;
+        let squashed = CommitData::new(
+            &data.date(),
+            Delta::new(&self.version(from)?, &self.version(to)?),
+            author_id,
+            message,
+            &data.path(),
+        )?;
+        let squashed = Commit::with_parents(squashed, vec![from.id.clone()], self)?;
+        self.copy_version(&to.id, &squashed.id)?;
+        let mut parent = squashed.id.clone();
+        self.commits.push(squashed.clone());
+        for commit in chain[..end].iter().rev() {
+            let data = commit.data(self)?;
+            let mut parents = data.parents();
+            parents[0] = parent;
+            let relinked = Commit::with_parents(data, parents, self)?;
+            self.copy_version(&commit.id, &relinked.id)?;
+            parent = relinked.id.clone();
+            self.commits.push(relinked);
+        }
+        self.branches.insert(self.branch.clone(), parent);
+        Ok((squashed, self.rewrite(operation)?))
+    }
+
+    /// `copy_version` records the digest, the keyframe and the chunks
+    /// of a commit for a rewritten commit holding the same version
+    fn copy_version(&mut self, from: &ID, to: &ID) -> Result<()> {
+        let digest = self.digest(&self.get_commit(from)?)?;
+        self.digests.insert(to.clone(), digest);
+        if let Some(keyframe) = self.keyframes.get(from).cloned() {
+            self.keyframes.insert(to.clone(), keyframe);
+        }
+        if let Some(manifest) = self.manifests.get(from).cloned() {
+            self.manifests.insert(to.clone(), manifest);
+        }
+        Ok(())
+    }
+
+    fn rewritable_head(&self) -> Result<Commit> {
+        self.latest_commit().ok_or_else(|| {
+            Error::CommitError(format!("branch {} of {} has no commits", self.branch, self.path))
+        })
+    }
+
+    /// `rewrite` drops the commits no branch or tag refers to, see
+    /// [`OFVRState::rehold`], backs up the state file and replaces it
+    /// with the state, callers must hold the lock
+    fn rewrite(&mut self, operation: Operation) -> Result<Path> {
+        if !self.path.is_file() {
+            return Err(Error::StateError(format!("{} does not exist", self.path)));
+        }
+        let mut kept = HashSet::<usize>::new();
+        let heads = self
+            .branches
+            .values()
+            .cloned()
+            .chain(self.tags.values().map(Tag::commit))
+            .collect::<Vec<ID>>();
+        for head in heads.iter() {
+            kept.extend(self.reachable(&self.get_commit(head)?)?);
+        }
+        self.rehold(&kept)?;
+        self.commits = std::mem::take(&mut self.commits)
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| kept.contains(index))
+            .map(|(_, commit)| commit)
+            .collect();
+        let ids = self.commits.iter().map(|commit| commit.id.clone()).collect::<HashSet<ID>>();
+        self.digests.retain(|id, _| ids.contains(id));
+        self.keyframes.retain(|id, _| ids.contains(id));
+        self.manifests.retain(|id, _| ids.contains(id));
+        self.write(operation)?
+            .ok_or_else(|| Error::StateError(format!("{} does not exist", self.path)))
+    }
+
+    /// `rehold` rebuilds the content-addressed table of the versions
+    /// of the commits at the given positions and stores the version of
+    /// those now holding a version they only referenced until then
+    /// whole, see [`OFVRState::keep_whole`]
+    fn rehold(&mut self, kept: &HashSet<usize>) -> Result<()> {
+        let positions = self.positions();
+        let mut objects = BTreeMap::<Vec<u8>, Object>::new();
+        let mut wholes = Vec::<(ID, Vec<u8>)>::new();
+        for (index, commit) in self.commits.iter().enumerate() {
+            let digest = match self.digests.get(&commit.id) {
+                Some(digest) if kept.contains(&index) && !objects.contains_key(digest) => digest,
+                _ => continue,
+            };
+            let data = commit.data(self)?;
+            if self.origin(index, &positions).is_some() && !self.keyframes.contains_key(&commit.id)
+            {
+                let version = self.version(commit)?;
+                let delta = data.delta();
+                let anterior = match data.parents().first() {
+                    Some(parent) => self.version(&self.get_commit(parent)?)?,
+                    None => Vec::new(),
+                };
+                if delta.check().is_err() || delta.apply(&anterior) != version {
+                    wholes.push((commit.id.clone(), version));
+                }
+            }
+            objects.insert(digest.clone(), Object::new(&commit.id, data.delta().length() as u64));
+        }
+        self.objects = objects;
+        for (id, version) in wholes.iter() {
+            self.keep_whole(id, version)?;
+        }
+        Ok(())
+    }
+
+    /// `backup` copies the state file next to it with the current
+    /// time in its name and returns the path of the copy
+    fn backup(&self) -> Result<Path> {
+        let time = chrono::Utc::now().format("%Y%m%dT%H%M%S%.9f");
+        let backup = self.path.with_filename(format!("{}.{}.bak", self.path.name(), time));
+        write_atomic(&backup, &self.path.read_bytes()?)?;
+        Ok(backup)
+    }
+}
+
+/// Merges
+impl OFVRState {
+    /// `merge_base` returns the latest commit reachable from both of
+    /// the given commits, i.e. their common ancestor
+    pub fn merge_base(&self, ours: &Commit, theirs: &Commit) -> Result<Option<Commit>> {
+        let ancestors = self.reachable(ours)?;
+        Ok(self
+            .reachable(theirs)?
+            .intersection(&ancestors)
+            .max()
+            .map(|index| self.commits[*index].clone()))
+    }
+
+    /// `merge` merges the version of the given commit into the head
+    /// of the current branch, see [`Merge`]
+    pub fn merge(&self, theirs: &Commit) -> Result<Merge> {
+        let ours = match self.latest_commit() {
+            Some(ours) => ours,
+            None => {
+                return Err(Error::MergeError(format!(
+                    "branch {} of {} has no commits",
+                    self.branch, self.path
+                )))
+            },
+        };
+        let ancestor = match self.merge_base(&ours, theirs)? {
+            Some(ancestor) => self.version(&ancestor)?,
+            None => Vec::new(),
+        };
+        Merge::new(&ancestor, &self.version(&ours)?, &self.version(theirs)?)
+    }
+
+    /// `commit_merge` records `data`, usually the result of
+    /// [`OFVRState::merge`], as a merge commit whose parents are the
+    /// head of the current branch and the given commit
+    pub fn commit_merge(
+        &mut self,
+        theirs: &Commit,
+        data: &[u8],
+        author: &Author,
+        message: &str,
+    ) -> Result<Commit> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        let operation = self.operation(OperationKind::Merge, Some(author))?;
+        let theirs = self.get_commit(&theirs.id)?;
+        let ours = match self.latest_commit() {
+            Some(ours) => ours,
+            None => {
+                return Err(Error::MergeError(format!(
+                    "branch {} of {} has no commits",
+                    self.branch, self.path
+                )))
+            },
+        };
+        let position = self.positions()[&theirs.id];
+        if self.reachable(&ours)?.contains(&position) {
+            return Err(Error::MergeError(format!(
+                "commit {} is already merged into branch {}",
+                theirs.id, self.branch
+            )));
+        }
+        let branch = self.branch.clone();
+        self.record(&branch, vec![ours, theirs], data, author, message, operation)
+    }
+}
+
+/// Operation log
+impl OFVRState {
+    /// `operations` returns the operations logged for the state file
+    /// from the oldest to the latest, see [`crate::oplog`]
+    pub fn operations(&self) -> Result<Vec<Operation>> {
+        crate::oplog::read(&self.path)
+    }
+
+    /// `undo` restores the state file as it was before the operation
+    /// at `index` of [`OFVRState::operations`], which undoes it along
+    /// with every later operation, and returns the path of the backup
+    /// of the state file before the undo. Undoing is logged as well,
+    /// so it can be undone in turn.
+    pub fn undo(&mut self, index: usize) -> Result<Path> {
+        let _lock = self.lock()?;
+        self.refresh()?;
+        let operations = self.operations()?;
+        let undone = operations.get(index).ok_or_else(|| {
+            Error::StateError(format!(
+                "operation index {} out of range: {} has {} operations",
+                index,
+                self.path,
+                operations.len()
+            ))
+        })?;
+        if undone.length == 0 {
+            return Err(Error::StateError(format!(
+                "{} did not exist before operation {} ({})",
+                self.path, index, undone.kind
+            )));
+        }
+        // operations that did not replace the state file only appended
+        // to the journal, so the state before them is a prefix of the
+        // backup of the next operation replacing it or of the current
+        // state file
+        let replacing = operations[index..]
+            .iter()
+            .enumerate()
+            .find(|(_, operation)| operation.replaced || operation.backup.is_some());
+        let source = match replacing {
+            Some((_, Operation { backup: Some(backup), .. })) => self.path.with_filename(backup),
+            Some((offset, operation)) =>
+                return Err(Error::StateError(format!(
+                    "{} was replaced without a backup by operation {} ({}), operation {} and \
+                     earlier ones cannot be undone",
+                    self.path,
+                    index + offset,
+                    operation.kind,
+                    index
+                ))),
+            None => self.path.clone(),
+        };
+        let bytes = source.read_bytes()?;
+        let length = usize::try_from(undone.length)?;
+        if bytes.len() < length {
+            return Err(Error::StateError(format!(
+                "{} is shorter than {} before operation {}",
+                source, self.path, index
+            )));
+        }
+        let bytes = bytes[..length].to_vec();
+        let (restored, journal) = crate::format::read(&bytes)?;
+        restored.journal.set(journal);
+        let mut operation = self.operation(OperationKind::Undo, None)?;
+        operation.replaced = true;
+        let backup = self.backup()?;
+        write_atomic(&self.path, &bytes)?;
+        self.replace(restored);
+        self.log_operation(operation, Some(&backup))?;
+        Ok(backup)
+    }
+
+    /// `operation` describes the state before an operation of the
+    /// given kind, callers must hold the lock
+    fn operation(&self, kind: OperationKind, author: Option<&Author>) -> Result<Operation> {
+        let length = match self.journal.get() {
+            Some(journal) => journal.length,
+            None if self.path.is_file() => std::fs::metadata(self.path.to_path_buf())?.len(),
+            None => 0,
+        };
+        Ok(Operation::new(
+            kind,
+            author,
+            &self.branch,
+            self.branches.get(&self.branch).map(ID::to_hex),
+            self.commits.len(),
+            length,
+        ))
+    }
+
+    /// `log_operation` appends a completed operation to the operation log,
+    /// callers must hold the lock
+    fn log_operation(&self, mut operation: Operation, backup: Option<&Path>) -> Result<()> {
+        operation.backup = backup.map(|backup| backup.name());
+        crate::oplog::append(&self.path, &operation)
+    }
+}
+
+impl OFVRState {
+    /// `commit` records the contents of the file at `data_path` as a
+    /// new commit on top of the head of the current branch, see
+    /// [`OFVRState::commit_on`]
     pub fn commit(&mut self, data_path: &Path, author: &Author, message: &str) -> Result<Commit> {
-        let data = read_data(&data_path)?;
-        Ok(self.commit_blob(&data, author, message)?)
+        let branch = self.branch.clone();
+        self.commit_on(&branch, data_path, author, message)
+    }
+
+    /// `commit_on` records the contents of the file at `data_path` as
+    /// a new commit on top of the head of `branch`, streamed from the
+    /// file, see [`OFVRState::commit_stream_on`], when the state
+    /// stores versions as chunks or the file is at least
+    /// [`STREAM_THRESHOLD`] long
+    pub fn commit_on(
+        &mut self,
+        branch: &str,
+        data_path: &Path,
+        author: &Author,
+        message: &str,
+    ) -> Result<Commit> {
+        if !data_path.is_file() {
+            return Err(Error::IOError(format!("{} does not exist", data_path)));
+        }
+        let length = std::fs::metadata(data_path.to_path_buf())?.len();
+        if self.storage == Storage::Chunks || length >= STREAM_THRESHOLD as u64 {
+            let reader = BufReader::new(File::open(data_path.to_path_buf())?);
+            return self.commit_stream_on(branch, reader, author, message);
+        }
+        self.commit_blob_on(branch, &read_data(data_path)?, author, message)
+    }
+
+    /// `revert` undoes the given commit without rewriting history by
+    /// committing the version of its first parent on top of the head
+    /// of the current branch, see [`OFVRState::revert_to`]
+    pub fn revert(&mut self, commit: &Commit, author: &Author) -> Result<Commit> {
+        let data = commit.data(self)?;
+        let parent = match data.parents().first() {
+            Some(parent) => self.get_commit(parent)?,
+            None => {
+                return Err(Error::CommitError(format!(
+                    "commit {} has no parent to revert to",
+                    commit.id
+                )))
+            },
+        };
+        let version = self.version(&parent)?;
+        let message = format!("Revert commit {}: {}", commit.id, data.message());
+        self.commit_reverted(&version, author, &message)
+    }
+
+    /// `revert_to` commits the version of the given commit on top of
+    /// the head of the current branch
+    pub fn revert_to(&mut self, commit: &Commit, author: &Author) -> Result<Commit> {
+        let version = self.version(commit)?;
+        let message = format!("Revert to commit {}: {}", commit.id, commit.data(self)?.message());
+        self.commit_reverted(&version, author, &message)
+    }
+
+    fn commit_reverted(
+        &mut self,
+        version: &[u8],
+        author: &Author,
+        message: &str,
+    ) -> Result<Commit> {
+        if self.latest_commit().is_some() && self.latest_version()? == version {
+            return Err(Error::CommitError(format!(
+                "nothing to revert: the head of branch {} already matches",
+                self.branch
+            )));
+        }
+        self.commit_blob(version, author, message)
     }
 }
 
 impl PlainBytes for OFVRState{}
-impl FileSystemBytes for OFVRState {}
+impl FileSystemBytes for OFVRState {
+    fn save_to_file(&self, path: impl Into<Path>) -> Result<()> {
+        let path = path.into();
+        let _lock = StateLock::acquire(&path, self.lock_timeout)?;
+        write_atomic(&path, &crate::format::encode(self)?.0)?;
+        Ok(())
+    }
+
+    fn load_from_file(path: impl Into<Path>) -> Result<OFVRState> {
+        let path = path.into();
+        crate::format::decode(&path.read_bytes()?)
+    }
+}
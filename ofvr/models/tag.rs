@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::author::Author;
+use crate::models::id::ID;
+use crate::models::state::OFVRState;
+use crate::traits::PlainBytes;
+use crate::Result;
+
+/// `Tag` is an immutable name given to a commit, e.g. the version of
+/// a release, optionally annotated with a message and its tagger
+#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Deserialize, Serialize)]
+pub struct Tag {
+    name: String,
+    commit: ID,
+    date: t16::Data,
+    message: Option<String>,
+    tagger: Option<u16>,
+}
+impl Tag {
+    pub fn new(
+        name: &str,
+        commit: &ID,
+        date: &t16::Data,
+        message: Option<&str>,
+        tagger: Option<u16>,
+    ) -> Tag {
+        Tag {
+            name: name.to_string(),
+            commit: commit.clone(),
+            date: *date,
+            message: message.map(String::from),
+            tagger,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// `commit` returns the id of the tagged commit
+    pub fn commit(&self) -> ID {
+        self.commit.clone()
+    }
+
+    pub fn date(&self) -> t16::Data {
+        self.date
+    }
+
+    pub fn message(&self) -> Option<String> {
+        self.message.clone()
+    }
+
+    pub fn tagger_id(&self) -> Option<u16> {
+        self.tagger
+    }
+
+    pub fn tagger(&self, ofvr: &OFVRState) -> Result<Option<Author>> {
+        match self.tagger {
+            Some(tagger) => Ok(Some(ofvr.get_author(tagger)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn to_json(&self, ofvr: &OFVRState) -> Result<serde_json::Value> {
+        Ok(serde_json::json!({
+            "name": self.name,
+            "commit": self.commit.to_hex(),
+            "date": self.date.to_chrono().to_rfc3339(),
+            "message": self.message,
+            "tagger": self.tagger(ofvr)?,
+        }))
+    }
+}
+impl PlainBytes for Tag {}
@@ -0,0 +1,200 @@
+//! Operation log of state files, see [`Operation`]
+//!
+//! Every mutating operation on an [`crate::OFVRState`] appends an
+//! [`Operation`] describing the state before it to a file next to the
+//! state file, see [`oplog_path`], one JSON object per line.
+//!
+//! Operations appending to the journal of the state file only record
+//! its length before them since the earlier state is a prefix of the
+//! journal. Operations replacing the whole state file, e.g. rewriting
+//! history or compaction, record the path of a backup of it next to
+//! it, see [`crate::OFVRState::undo`].
+use std::fmt::Display;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use chrono::{DateTime, Utc};
+use iocore::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{Error, Result};
+use crate::models::author::Author;
+
+/// `OperationKind` of the operations recorded in the operation log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OperationKind {
+    Commit,
+    Merge,
+    Amend,
+    Reset,
+    Squash,
+    BranchCreate,
+    BranchDelete,
+    BranchSwitch,
+    TagAdd,
+    TagDelete,
+    Store,
+    Compact,
+    Repack,
+    Storage,
+    Upgrade,
+    Undo,
+}
+impl Display for OperationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                OperationKind::Commit => "commit",
+                OperationKind::Merge => "merge",
+                OperationKind::Amend => "amend",
+                OperationKind::Reset => "reset",
+                OperationKind::Squash => "squash",
+                OperationKind::BranchCreate => "branch-create",
+                OperationKind::BranchDelete => "branch-delete",
+                OperationKind::BranchSwitch => "branch-switch",
+                OperationKind::TagAdd => "tag-add",
+                OperationKind::TagDelete => "tag-delete",
+                OperationKind::Store => "store",
+                OperationKind::Compact => "compact",
+                OperationKind::Repack => "repack",
+                OperationKind::Storage => "storage",
+                OperationKind::Upgrade => "upgrade",
+                OperationKind::Undo => "undo",
+            }
+        )
+    }
+}
+
+/// `Operation` records a mutating operation along with the state
+/// before it
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Operation {
+    pub kind: OperationKind,
+    pub date: DateTime<Utc>,
+    /// author of the operation when it has one, e.g. of a commit
+    pub author: Option<Author>,
+    /// current branch before the operation
+    pub branch: String,
+    /// hex id of the head of the current branch before the operation
+    pub head: Option<String>,
+    /// amount of commits before the operation
+    pub commits: usize,
+    /// length of the state file before the operation, zero when it
+    /// did not exist
+    pub length: u64,
+    /// file name of the copy of the state file before the operation,
+    /// next to it, when the operation rewrote history
+    pub backup: Option<String>,
+    /// whether the operation replaced the whole state file rather than
+    /// appending to its journal
+    #[serde(default)]
+    pub replaced: bool,
+}
+impl Operation {
+    pub fn new(
+        kind: OperationKind,
+        author: Option<&Author>,
+        branch: &str,
+        head: Option<String>,
+        commits: usize,
+        length: u64,
+    ) -> Operation {
+        Operation {
+            kind,
+            date: Utc::now(),
+            author: author.cloned(),
+            branch: branch.to_string(),
+            head,
+            commits,
+            length,
+            backup: None,
+            replaced: false,
+        }
+    }
+}
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} on {} at {} with {} commits",
+            self.date.to_rfc3339(),
+            self.kind,
+            self.branch,
+            self.head.as_deref().unwrap_or("(none)"),
+            self.commits
+        )?;
+        if let Some(author) = &self.author {
+            write!(f, " by {}", author)?;
+        }
+        Ok(())
+    }
+}
+
+/// `oplog_path` returns the path of the operation log of the state
+/// file at `path`
+pub fn oplog_path(path: &Path) -> Path {
+    path.with_filename(format!("{}.oplog", path.name()))
+}
+
+/// `read` returns the operations logged for the state file at `path`
+/// from the oldest to the latest, ignoring the remains of an
+/// interrupted append at the end of the operation log. Lines that
+/// cannot be parsed before the last one are an error since skipping
+/// them would shift the index of every later operation
+pub fn read(path: &Path) -> Result<Vec<Operation>> {
+    let oplog = oplog_path(path);
+    if !oplog.is_file() {
+        return Ok(Vec::new());
+    }
+    let bytes = oplog.read_bytes()?;
+    let mut operations = Vec::<Operation>::new();
+    let mut lines = bytes.split(|byte| *byte == b'\n').peekable();
+    while let Some(line) = lines.next() {
+        match serde_json::from_slice::<Operation>(line) {
+            Ok(operation) => operations.push(operation),
+            // the last line is only complete once its newline is written
+            Err(_) if lines.peek().is_none() => break,
+            Err(error) =>
+                return Err(Error::StateError(format!(
+                    "line {} of {} is not an operation: {}",
+                    operations.len() + 1,
+                    oplog,
+                    error
+                ))),
+        }
+    }
+    Ok(operations)
+}
+
+/// `append` logs an operation of the state file at `path`, dropping
+/// the remains of an interrupted append first, callers must hold the
+/// lock of the state file
+pub fn append(path: &Path, operation: &Operation) -> Result<()> {
+    let mut line = serde_json::to_string(operation)?;
+    line.push('\n');
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(oplog_path(path).to_path_buf())?;
+    let mut length = file.metadata()?.len();
+    let mut last = *b"\n";
+    if length > 0 {
+        file.seek(SeekFrom::Start(length - 1))?;
+        file.read_exact(&mut last)?;
+    }
+    if last[0] != b'\n' {
+        let mut bytes = Vec::<u8>::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_end(&mut bytes)?;
+        length = bytes.iter().rposition(|byte| *byte == b'\n').map_or(0, |end| end as u64 + 1);
+        file.set_len(length)?;
+    }
+    file.seek(SeekFrom::Start(length))?;
+    file.write_all(line.as_bytes())?;
+    file.sync_data()?;
+    Ok(())
+}
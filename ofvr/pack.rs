@@ -0,0 +1,184 @@
+//! Pack of the chunks of the versions of a state file stored as
+//! chunks, see [`crate::chunk`], kept next to the state file so that
+//! neither the state nor its snapshots hold their bytes.
+//!
+//! The pack is an append-only sequence of entries laid out as:
+//!
+//! | bytes          | field                              |
+//! |----------------|------------------------------------|
+//! | 0..32          | keccak256 of the chunk             |
+//! | 32..36         | chunk length, little endian `u32`  |
+//! | 36..36+N       | chunk                              |
+//!
+//! An incomplete entry at the end is the remains of an interrupted
+//! append, it is ignored when loading and overwritten by the next
+//! append. Entries are never removed, so that state files restored
+//! from backups still find their chunks, see [`crate::OFVRState::undo`].
+//!
+//! Versions of at least [`STREAM_THRESHOLD`] bytes are streamed into
+//! the pack whatever the [`crate::Storage`] of the state, see
+//! [`crate::OFVRState::commit_on`], and out of it by checkouts, see
+//! [`crate::OFVRState::write_version`], holding a chunk at a time.
+//! What they hold besides grows with the amount of chunks rather than
+//! with their bytes: the offset of every chunk of the pack, see
+//! [`Pack::load`], and the list of the chunks of each version held by
+//! the state, a few hundred bytes per chunk of about ten KiB, see
+//! [`crate::chunk`]. Other commands, e.g. diff, merge or blame,
+//! rebuild versions in memory.
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use iocore::Path;
+
+use crate::errors::{Error, Result};
+use crate::hash::keccak256;
+
+/// Length of the hash and length preceding each chunk of a pack
+pub const ENTRY_OVERHEAD: usize = 36;
+/// Versions at least this long are stored as chunks in the pack
+/// rather than as deltas, see [`crate::OFVRState::commit_on`]
+pub const STREAM_THRESHOLD: usize = 64 << 20;
+
+/// `Pack` of the chunks of the state file at a given path
+#[derive(Debug)]
+pub struct Pack {
+    path: Path,
+    /// offset and length of each chunk by keccak256
+    entries: BTreeMap<[u8; 32], (u64, u32)>,
+    /// length of the valid entries of the pack file
+    length: u64,
+    file: Option<File>,
+    /// whether `file` was opened for appending
+    writable: bool,
+}
+impl Pack {
+    /// `load` reads the offset of every chunk of the pack of the state
+    /// file at `path`, without reading the chunks themselves
+    pub fn load(path: &Path) -> Result<Pack> {
+        let mut pack = Pack {
+            path: pack_path(path),
+            entries: BTreeMap::new(),
+            length: 0,
+            file: None,
+            writable: false,
+        };
+        if !pack.path.is_file() {
+            return Ok(pack);
+        }
+        let mut file = File::open(pack.path.to_path_buf())?;
+        let size = file.metadata()?.len();
+        let mut header = [0u8; ENTRY_OVERHEAD];
+        while pack.length + ENTRY_OVERHEAD as u64 <= size {
+            file.seek(SeekFrom::Start(pack.length))?;
+            file.read_exact(&mut header)?;
+            let length = u32::from_le_bytes(header[32..].try_into().expect("4 bytes"));
+            let offset = pack.length + ENTRY_OVERHEAD as u64;
+            if offset + length as u64 > size {
+                break;
+            }
+            pack.entries
+                .insert(header[..32].try_into().expect("32 bytes"), (offset, length));
+            pack.length = offset + length as u64;
+        }
+        pack.file = Some(file);
+        Ok(pack)
+    }
+
+    pub fn contains(&self, hash: &[u8]) -> bool {
+        <&[u8; 32]>::try_from(hash).is_ok_and(|hash| self.entries.contains_key(hash))
+    }
+
+    /// `len` returns the amount of chunks in the pack
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// `iter` returns the keccak256 and the length of every chunk in the pack
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], u32)> {
+        self.entries.iter().map(|(hash, (_, length))| (hash.as_slice(), *length))
+    }
+
+    /// `bytes` returns the total length of the chunks in the pack
+    pub fn bytes(&self) -> u64 {
+        self.entries.values().map(|(_, length)| *length as u64).sum()
+    }
+
+    /// `get` reads the chunk with the given keccak256, if any, and
+    /// checks that it still hashes to it
+    pub fn get(&mut self, hash: &[u8]) -> Result<Option<Vec<u8>>> {
+        let entry = <&[u8; 32]>::try_from(hash).ok().and_then(|hash| self.entries.get(hash));
+        let (offset, length) = match entry {
+            Some(entry) => *entry,
+            None => return Ok(None),
+        };
+        let file = match self.file.as_mut() {
+            Some(file) => file,
+            None => return Ok(None),
+        };
+        let mut chunk = vec![0u8; length as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut chunk)?;
+        if keccak256(&chunk) != hash {
+            return Err(Error::StateError(format!(
+                "corrupted chunk {} in {}",
+                hex::encode(hash),
+                self.path
+            )));
+        }
+        Ok(Some(chunk))
+    }
+
+    /// `append` adds the given chunk to the pack unless it holds it
+    /// already and returns true when added, callers must hold the
+    /// lock of the state file and call [`Pack::sync`] before
+    /// referring to the chunk in the state file
+    pub fn append(&mut self, chunk: &[u8]) -> Result<bool> {
+        let hash: [u8; 32] = keccak256(chunk).try_into().expect("32 bytes");
+        if self.entries.contains_key(&hash) {
+            return Ok(false);
+        }
+        let length = u32::try_from(chunk.len()).map_err(|_| {
+            Error::StateError(format!("chunk of {} bytes is too long", chunk.len()))
+        })?;
+        if !self.writable {
+            self.writable = true;
+            self.file = Some(
+                OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(self.path.to_path_buf())?,
+            );
+        }
+        let file = self.file.as_mut().expect("pack file");
+        file.seek(SeekFrom::Start(self.length))?;
+        file.write_all(&hash)?;
+        file.write_all(&length.to_le_bytes())?;
+        file.write_all(chunk)?;
+        let offset = self.length + ENTRY_OVERHEAD as u64;
+        self.entries.insert(hash, (offset, length));
+        self.length = offset + length as u64;
+        Ok(true)
+    }
+
+    /// `sync` drops the remains of an interrupted append past the
+    /// valid entries and persists the pack file
+    pub fn sync(&mut self) -> Result<()> {
+        if let Some(file) = self.file.as_mut().filter(|_| self.writable) {
+            file.set_len(self.length)?;
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+/// `pack_path` returns the path of the pack of the state file at `path`
+pub fn pack_path(path: &Path) -> Path {
+    path.with_filename(format!("{}.pack", path.name()))
+}
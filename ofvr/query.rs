@@ -0,0 +1,99 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::errors::Result;
+use crate::index::{Commits, IndexEntry};
+use crate::models::commit::Commit;
+use crate::models::id::ID;
+use crate::models::state::OFVRState;
+
+/// `LogQuery` selects commits of an [`OFVRState`] for display
+///
+/// Commits of the history of `branch` (defaults to the current
+/// branch), or of every branch when `all` is true, are yielded in the
+/// order they were committed unless `reverse` is true. `max_count`
+/// keeps only the most recent matching commits and is applied before
+/// `reverse`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct LogQuery {
+    pub max_count: Option<usize>,
+    pub reverse: bool,
+    pub author: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub grep: Option<String>,
+    pub branch: Option<String>,
+    pub all: bool,
+}
+
+impl LogQuery {
+    pub fn new() -> LogQuery {
+        LogQuery::default()
+    }
+
+    /// `matches` returns true when the given commit satisfies every filter of the query
+    pub fn matches<C: Commits>(&self, entry: &IndexEntry, commits: &C) -> Result<bool> {
+        if let Some(author) = &self.author {
+            let author = author.to_lowercase();
+            let commit_author = commits.get_author(entry.author)?;
+            if !commit_author.name().to_lowercase().contains(&author)
+                && !commit_author.email().to_lowercase().contains(&author)
+            {
+                return Ok(false);
+            }
+        }
+        let date = entry.date.to_chrono();
+        if let Some(since) = &self.since {
+            if date < *since {
+                return Ok(false);
+            }
+        }
+        if let Some(until) = &self.until {
+            if date > *until {
+                return Ok(false);
+            }
+        }
+        if let Some(grep) = &self.grep {
+            if !entry.message.contains(grep.as_str()) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// `run` runs the query on the commits of the given state, see
+    /// [`LogQuery::select`]
+    pub fn run(&self, ofvr: &OFVRState) -> Result<Vec<Commit>> {
+        let commits = ofvr
+            .commits()
+            .iter()
+            .map(|commit| (&commit.id, commit))
+            .collect::<HashMap<&ID, &Commit>>();
+        Ok(self.select(ofvr)?.into_iter().map(|entry| commits[&entry.id].clone()).collect())
+    }
+
+    /// `select` runs the query on the given commits, e.g. those of the
+    /// index of a state file, see [`crate::CommitIndex::load`]
+    pub fn select<'a, C: Commits>(&self, commits: &'a C) -> Result<Vec<Cow<'a, IndexEntry>>> {
+        let history = if self.all {
+            commits.every_entry()?
+        } else {
+            commits.history_of(&self.branch.clone().unwrap_or_else(|| commits.current_branch()))?
+        };
+        let mut entries = Vec::<Cow<'a, IndexEntry>>::new();
+        for entry in history.into_iter() {
+            if self.matches(&entry, commits)? {
+                entries.push(entry);
+            }
+        }
+        if let Some(max_count) = self.max_count {
+            entries = entries.split_off(entries.len().saturating_sub(max_count));
+        }
+        if self.reverse {
+            entries.reverse();
+        }
+        Ok(entries)
+    }
+}
@@ -0,0 +1,247 @@
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+use crate::errors::{Error, Result};
+use crate::index::{Commits, IndexEntry};
+use crate::models::commit::Commit;
+use crate::models::id::ID;
+use crate::models::state::OFVRState;
+use crate::traits::PlainBytes;
+
+/// Minimum amount of hex digits accepted as an abbreviated commit id
+pub const MIN_HEX_PREFIX_LEN: usize = 4;
+
+/// `Revision` addresses a single commit within an [`OFVRState`]
+///
+/// Supported syntax:
+///
+/// - `HEAD` the head of the current branch, `HEAD~N` the Nth first
+///   parent of it
+/// - `rN` the Nth commit, 1-based, in the history of the current
+///   branch in the order they were committed
+/// - `@{DATE}` the latest commit of the history of the current branch
+///   made at or before `DATE`, where `DATE` is `YYYY-MM-DD`,
+///   `YYYY-MM-DD HH:MM:SS` or RFC3339
+/// - a unique prefix of at least [`MIN_HEX_PREFIX_LEN`] hex digits of
+///   a commit id of any branch
+/// - the name of a branch or of a tag, see [`is_valid_reference_name`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Revision {
+    Head(usize),
+    Ordinal(usize),
+    Date(DateTime<Utc>),
+    Hex(String),
+    Name(String),
+}
+
+impl Revision {
+    pub fn parse(revision: &str) -> Result<Revision> {
+        let revision = revision.trim();
+        if revision == "HEAD" {
+            return Ok(Revision::Head(0));
+        }
+        if let Some(offset) = revision.strip_prefix("HEAD~") {
+            return Ok(Revision::Head(parse_number(revision, offset)?));
+        }
+        if let Some(date) = revision.strip_prefix("@{").and_then(|date| date.strip_suffix("}")) {
+            return Ok(Revision::Date(parse_date(date)?));
+        }
+        if let Some(ordinal) = revision
+            .strip_prefix("r")
+            .filter(|ordinal| !ordinal.is_empty() && ordinal.chars().all(|c| c.is_ascii_digit()))
+        {
+            return match parse_number(revision, ordinal)? {
+                0 => Err(Error::RevisionError(format!(
+                    "invalid revision {:#?}: ordinals start at r1",
+                    revision
+                ))),
+                ordinal => Ok(Revision::Ordinal(ordinal)),
+            };
+        }
+        if revision.len() >= MIN_HEX_PREFIX_LEN && revision.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(Revision::Hex(revision.to_lowercase()));
+        }
+        if is_valid_name(revision) {
+            return Ok(Revision::Name(revision.to_string()));
+        }
+        Err(Error::RevisionError(format!("invalid revision {:#?}", revision)))
+    }
+
+    /// `resolve` finds the commit addressed by the revision among the
+    /// commits of the given state, see [`Revision::resolve_entry`]
+    pub fn resolve(&self, ofvr: &OFVRState) -> Result<Commit> {
+        ofvr.get_commit(&self.resolve_entry(ofvr)?.id)
+    }
+
+    /// `resolve_entry` finds the commit addressed by the revision
+    /// among the given commits, e.g. those of the index of a state
+    /// file, see [`crate::CommitIndex::load`]
+    pub fn resolve_entry<'a, C: Commits>(&self, commits: &'a C) -> Result<Cow<'a, IndexEntry>> {
+        match self {
+            Revision::Head(offset) => {
+                let head = commits.branches().get(&commits.current_branch()).cloned();
+                let mut entry = match head {
+                    Some(head) => commits.entry(&head)?,
+                    None => return Err(self.no_commits(commits)),
+                };
+                for _ in 0..*offset {
+                    entry = match entry.parents.first().cloned() {
+                        Some(parent) => commits.entry(&parent)?,
+                        None => {
+                            return Err(Error::RevisionError(format!(
+                                "{} is out of range: {} has {} commits",
+                                self,
+                                commits.path(),
+                                self.history(commits)?.len()
+                            )))
+                        },
+                    };
+                }
+                Ok(entry)
+            },
+            Revision::Ordinal(ordinal) => {
+                let mut entries = self.history(commits)?;
+                if *ordinal > entries.len() {
+                    return Err(Error::RevisionError(format!(
+                        "{} is out of range: {} has {} commits",
+                        self,
+                        commits.path(),
+                        entries.len()
+                    )));
+                }
+                Ok(entries.swap_remove(ordinal - 1))
+            },
+            Revision::Date(date) => {
+                let mut found: Option<Cow<'a, IndexEntry>> = None;
+                for entry in self.history(commits)?.into_iter() {
+                    if entry.date.to_chrono() <= *date {
+                        found = Some(entry);
+                    }
+                }
+                found.ok_or_else(|| {
+                    Error::RevisionError(format!("no commits at or before {}", date.to_rfc3339()))
+                })
+            },
+            Revision::Hex(prefix) => {
+                let matches = commits
+                    .ids()
+                    .into_iter()
+                    .filter(|id| id.to_hex().starts_with(prefix.as_str()))
+                    .collect::<Vec<&ID>>();
+                match matches.len() {
+                    0 => Err(Error::RevisionError(format!("unknown revision {}", prefix))),
+                    1 => commits.entry(matches[0]),
+                    _ => Err(Error::RevisionError(format!(
+                        "ambiguous revision {} matches: {}",
+                        prefix,
+                        matches.iter().map(|id| id.to_hex()).collect::<Vec<String>>().join(", ")
+                    ))),
+                }
+            },
+            Revision::Name(name) => match (commits.branches().get(name), commits.tags().get(name)) {
+                (Some(head), _) => commits.entry(head),
+                (None, Some(tag)) => commits.entry(&tag.commit()),
+                (None, None) => Err(Error::RevisionError(format!("unknown revision {}", name))),
+            },
+        }
+    }
+
+    /// `history` returns the history of the current branch, failing
+    /// when it has no commits
+    fn history<'a, C: Commits>(&self, commits: &'a C) -> Result<Vec<Cow<'a, IndexEntry>>> {
+        let entries = commits.history_of(&commits.current_branch())?;
+        if entries.is_empty() {
+            return Err(self.no_commits(commits));
+        }
+        Ok(entries)
+    }
+
+    /// `no_commits` is the error of revisions of a branch without commits
+    fn no_commits<C: Commits>(&self, commits: &C) -> Error {
+        Error::RevisionError(format!(
+            "cannot resolve {} because branch {} of {} has no commits",
+            self,
+            commits.current_branch(),
+            commits.path()
+        ))
+    }
+}
+
+impl Display for Revision {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Revision::Head(0) => write!(f, "HEAD"),
+            Revision::Head(offset) => write!(f, "HEAD~{}", offset),
+            Revision::Ordinal(ordinal) => write!(f, "r{}", ordinal),
+            Revision::Date(date) => write!(f, "@{{{}}}", date.to_rfc3339()),
+            Revision::Hex(prefix) => write!(f, "{}", prefix),
+            Revision::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl FromStr for Revision {
+    type Err = Error;
+
+    fn from_str(revision: &str) -> Result<Revision> {
+        Revision::parse(revision)
+    }
+}
+
+/// `parse_range` parses `A..B`, the commits after `A` up to and
+/// including `B`, where `B` defaults to `HEAD` when omitted
+pub fn parse_range(range: &str) -> Result<(Revision, Revision)> {
+    match range.split_once("..") {
+        Some((from, "")) => Ok((Revision::parse(from)?, Revision::Head(0))),
+        Some((from, to)) => Ok((Revision::parse(from)?, Revision::parse(to)?)),
+        None => Err(Error::RevisionError(format!("invalid range {:#?}, expected A..B", range))),
+    }
+}
+
+/// `is_valid_reference_name` returns true for names of branches and
+/// tags made of ASCII letters, digits, `-`, `_`, `.` and `/` that
+/// cannot be mistaken for any other kind of [`Revision`]
+pub fn is_valid_reference_name(name: &str) -> bool {
+    matches!(Revision::parse(name), Ok(Revision::Name(parsed)) if parsed == name)
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || "-_./".contains(c))
+        && !name.starts_with(['-', '.', '/'])
+        && !name.ends_with(['.', '/'])
+        && !name.contains("..")
+}
+
+fn parse_number(revision: &str, number: &str) -> Result<usize> {
+    number
+        .parse::<usize>()
+        .map_err(|e| Error::RevisionError(format!("invalid revision {:#?}: {}", revision, e)))
+}
+
+/// `parse_date` parses `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS` or RFC3339 dates as UTC
+pub fn parse_date(date: &str) -> Result<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Ok(date.to_utc());
+    }
+    if let Ok(date) = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S") {
+        return Ok(date.and_utc());
+    }
+    match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        Ok(date) => Ok(date.and_hms_opt(0, 0, 0).expect("midnight").and_utc()),
+        Err(e) => Err(Error::RevisionError(format!("invalid date {:#?}: {}", date, e))),
+    }
+}
+
+/// `parse_until` parses dates as [`parse_date`] except that a date
+/// without a time is the end rather than the start of that day, so
+/// that `--until` includes the day it names
+pub fn parse_until(date: &str) -> Result<DateTime<Utc>> {
+    match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        Ok(day) => Ok(day.and_hms_nano_opt(23, 59, 59, 999_999_999).expect("end of day").and_utc()),
+        Err(_) => parse_date(date),
+    }
+}
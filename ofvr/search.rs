@@ -0,0 +1,173 @@
+//! Search of the commits that changed the amount of occurrences of a
+//! pattern, see [`OFVRState::search`]
+use std::fmt::Display;
+
+use regex::bytes::Regex;
+
+use crate::errors::{Error, Result};
+use crate::models::commit::Commit;
+use crate::models::id::ID;
+use crate::models::state::OFVRState;
+use crate::traits::PlainBytes;
+
+/// `Pattern` searched in the versions of a file
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Bytes(Vec<u8>),
+    Regex(Regex),
+}
+impl Pattern {
+    /// `hex` parses a pattern given as hex digits, optionally prefixed
+    /// with `0x` and separated by whitespace, e.g. `7f 45 4c 46`
+    pub fn hex(pattern: &str) -> Result<Pattern> {
+        let digits = pattern.split_whitespace().collect::<String>();
+        let digits = digits.strip_prefix("0x").unwrap_or(&digits);
+        Pattern::bytes(&hex::decode(digits)?)
+    }
+
+    /// `text` matches the UTF-8 bytes of `pattern`
+    pub fn text(pattern: &str) -> Result<Pattern> {
+        Pattern::bytes(pattern.as_bytes())
+    }
+
+    /// `regex` matches a regular expression, see [`regex::bytes::Regex`]
+    pub fn regex(pattern: &str) -> Result<Pattern> {
+        Ok(Pattern::Regex(Regex::new(pattern)?))
+    }
+
+    fn bytes(pattern: &[u8]) -> Result<Pattern> {
+        if pattern.is_empty() {
+            return Err(Error::SearchError(String::from("empty pattern")));
+        }
+        Ok(Pattern::Bytes(pattern.to_vec()))
+    }
+
+    /// `offsets` returns the offsets of the non-overlapping occurrences
+    /// of the pattern in `haystack`
+    pub fn offsets(&self, haystack: &[u8]) -> Vec<usize> {
+        match self {
+            Pattern::Bytes(needle) => {
+                let mut offsets = Vec::<usize>::new();
+                let mut offset = 0;
+                while offset + needle.len() <= haystack.len() {
+                    if haystack[offset..].starts_with(needle) {
+                        offsets.push(offset);
+                        offset += needle.len();
+                    } else {
+                        offset += 1;
+                    }
+                }
+                offsets
+            },
+            Pattern::Regex(regex) => regex
+                .find_iter(haystack)
+                .filter(|found| !found.is_empty())
+                .map(|found| found.start())
+                .collect(),
+        }
+    }
+}
+impl Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Pattern::Bytes(bytes) => write!(f, "{}", hex::encode(bytes)),
+            Pattern::Regex(regex) => write!(f, "/{}/", regex.as_str()),
+        }
+    }
+}
+
+/// `SearchHit` is a commit whose version holds a different amount of
+/// occurrences of the searched pattern than the version of its first
+/// parent
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SearchHit {
+    pub commit: Commit,
+    /// occurrences in the version of the first parent
+    pub before: usize,
+    /// occurrences in the version of the commit
+    pub after: usize,
+    /// offsets of occurrences the first parent did not hold at the same offset
+    pub added: Vec<usize>,
+    /// offsets of occurrences of the first parent gone from the commit
+    pub removed: Vec<usize>,
+}
+impl SearchHit {
+    pub fn to_json(&self, ofvr: &OFVRState) -> Result<serde_json::Value> {
+        let data = self.commit.data(ofvr)?;
+        Ok(serde_json::json!({
+            "commit": self.commit.id.to_hex(),
+            "author": self.commit.author(ofvr)?,
+            "date": data.date_rfc3339(),
+            "message": data.message(),
+            "before": self.before,
+            "after": self.after,
+            "added": self.added,
+            "removed": self.removed,
+        }))
+    }
+}
+impl Display for SearchHit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let offsets = |offsets: &[usize]| {
+            offsets
+                .iter()
+                .map(|offset| format!("{:#x}", offset))
+                .collect::<Vec<String>>()
+                .join(",")
+        };
+        write!(f, "{} {} -> {}", self.commit.id.to_hex(), self.before, self.after)?;
+        if !self.added.is_empty() {
+            write!(f, " +{}", offsets(&self.added))?;
+        }
+        if !self.removed.is_empty() {
+            write!(f, " -{}", offsets(&self.removed))?;
+        }
+        Ok(())
+    }
+}
+
+/// `search` returns the commits, in the order they were committed,
+/// whose version holds a different amount of occurrences of `pattern`
+/// than the version of their first parent, or than an empty file for
+/// commits without parents
+pub fn search(ofvr: &OFVRState, pattern: &Pattern) -> Result<Vec<SearchHit>> {
+    let mut hits = Vec::<SearchHit>::new();
+    // version and occurrences of the previous commit, usually the
+    // first parent of the next one
+    let mut previous: Option<(ID, Vec<u8>, Vec<usize>)> = None;
+    for commit in ofvr.commits().iter() {
+        let data = commit.data(ofvr)?;
+        let delta = ofvr.delta(commit)?;
+        delta.check()?;
+        let (anterior, before) = match (data.parents().first(), previous.take()) {
+            (None, _) => (Vec::new(), Vec::new()),
+            (Some(parent), Some((id, version, offsets))) if *parent == id => (version, offsets),
+            (Some(parent), _) => {
+                let version = ofvr.version(&ofvr.get_commit(parent)?)?;
+                let offsets = pattern.offsets(&version);
+                (version, offsets)
+            },
+        };
+        let version = delta.apply(&anterior);
+        let after = pattern.offsets(&version);
+        if after.len() != before.len() {
+            hits.push(SearchHit {
+                commit: commit.clone(),
+                before: before.len(),
+                after: after.len(),
+                added: after
+                    .iter()
+                    .filter(|offset| before.binary_search(offset).is_err())
+                    .cloned()
+                    .collect(),
+                removed: before
+                    .iter()
+                    .filter(|offset| after.binary_search(offset).is_err())
+                    .cloned()
+                    .collect(),
+            });
+        }
+        previous = Some((commit.id.clone(), version, after));
+    }
+    Ok(hits)
+}
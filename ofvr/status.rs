@@ -0,0 +1,189 @@
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read, Write};
+
+use iocore::Path;
+use serde::Serialize;
+
+use crate::errors::{Error, Result};
+use crate::hash::keccak256_reader;
+use crate::index::CommitIndex;
+use crate::models::state::OFVRState;
+use crate::traits::PlainBytes;
+
+/// `Status` of a working file relative to its state file
+///
+/// Each status maps to a distinct process exit code, see [`Status::exit_code`]:
+///
+/// | status          | exit code |
+/// |-----------------|-----------|
+/// | `clean`         | 0         |
+/// | (error)         | 1         |
+/// | `modified`      | 2         |
+/// | `untracked`     | 3         |
+/// | `missing-state` | 4         |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    /// the working file matches the compared commit
+    Clean,
+    /// the working file differs from the compared commit
+    Modified,
+    /// the state file exists but holds no commits
+    Untracked,
+    /// the state file does not exist
+    MissingState,
+}
+impl Status {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Status::Clean => 0,
+            Status::Modified => 2,
+            Status::Untracked => 3,
+            Status::MissingState => 4,
+        }
+    }
+}
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Status::Clean => "clean",
+                Status::Modified => "modified",
+                Status::Untracked => "untracked",
+                Status::MissingState => "missing-state",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct StatusReport {
+    pub status: Status,
+    pub path: String,
+    pub ofvr_state_path: String,
+    pub commit: Option<String>,
+    pub changed_bytes: usize,
+    pub working_length: usize,
+    pub committed_length: usize,
+}
+impl StatusReport {
+    /// `new` compares the working file at `path` with the commit
+    /// addressed by `revision` (defaults to `HEAD`) in the state file
+    /// at `ofvr_state_path`, which is only loaded when the digest of
+    /// the working file differs from the digest of the commit, see
+    /// [`CommitIndex`]. The working file is read as a stream and
+    /// compared with the committed version as it is written, see
+    /// [`OFVRState::write_version`]
+    pub fn new(
+        path: &Path,
+        ofvr_state_path: &Path,
+        revision: Option<&str>,
+    ) -> Result<StatusReport> {
+        if !path.is_file() {
+            return Err(Error::IOError(format!("{} does not exist", path)));
+        }
+        let working_length = std::fs::metadata(path.to_path_buf())?.len() as usize;
+        let mut report = StatusReport {
+            status: Status::MissingState,
+            path: path.to_string(),
+            ofvr_state_path: ofvr_state_path.to_string(),
+            commit: None,
+            changed_bytes: working_length,
+            working_length,
+            committed_length: 0,
+        };
+        if !ofvr_state_path.is_file() {
+            return Ok(report);
+        }
+        let index = CommitIndex::load(ofvr_state_path)?;
+        if index.entries().is_empty() {
+            report.status = Status::Untracked;
+            return Ok(report);
+        }
+        let entry = index.resolve(revision.unwrap_or("HEAD"))?;
+        report.commit = Some(entry.id.to_hex());
+        let digest = keccak256_reader(File::open(path.to_path_buf())?)?;
+        if entry.digest.as_deref() == Some(digest.as_slice()) {
+            report.committed_length = working_length;
+            report.changed_bytes = 0;
+            report.status = Status::Clean;
+            return Ok(report);
+        }
+        let ofvr = OFVRState::from_path(ofvr_state_path)?;
+        let mut comparison = Comparison {
+            working: BufReader::new(File::open(path.to_path_buf())?),
+            buffer: Vec::new(),
+            changed: 0,
+        };
+        let committed = ofvr.write_version(&ofvr.get_commit(&entry.id)?, &mut comparison)?;
+        report.committed_length = committed as usize;
+        report.changed_bytes =
+            comparison.changed + working_length.saturating_sub(committed as usize);
+        report.status = if report.changed_bytes == 0 { Status::Clean } else { Status::Modified };
+        Ok(report)
+    }
+
+    pub fn summary(&self) -> String {
+        match self.status {
+            Status::Clean => format!(
+                "{}: clean, matches {} in {}",
+                self.path,
+                self.commit.clone().unwrap_or_default(),
+                self.ofvr_state_path
+            ),
+            Status::Modified => format!(
+                "{}: modified, {} bytes changed ({} -> {} bytes) since {} in {}",
+                self.path,
+                self.changed_bytes,
+                self.committed_length,
+                self.working_length,
+                self.commit.clone().unwrap_or_default(),
+                self.ofvr_state_path
+            ),
+            Status::Untracked =>
+                format!("{}: untracked, {} has no commits", self.path, self.ofvr_state_path),
+            Status::MissingState =>
+                format!("{}: missing state, {} does not exist", self.path, self.ofvr_state_path),
+        }
+    }
+}
+
+/// `Comparison` counts the bytes written into it that differ from the
+/// bytes of `working` at the same positions, including those written
+/// past its end
+struct Comparison<R: Read> {
+    working: R,
+    buffer: Vec<u8>,
+    changed: usize,
+}
+impl<R: Read> Write for Comparison<R> {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.buffer.resize(bytes.len(), 0);
+        let mut read = 0;
+        while read < bytes.len() {
+            match self.working.read(&mut self.buffer[read..]) {
+                Ok(0) => break,
+                Ok(count) => read += count,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {},
+                Err(error) => return Err(error),
+            }
+        }
+        self.changed += changed_bytes(&bytes[..read], &self.buffer[..read]) + bytes.len() - read;
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// `changed_bytes` counts the positions at which `anterior` and
+/// `current` differ, including bytes appended to or truncated from either
+pub fn changed_bytes(anterior: &[u8], current: &[u8]) -> usize {
+    let common = anterior.len().min(current.len());
+    let differing = anterior[..common].iter().zip(current[..common].iter()).filter(|(a, c)| a != c);
+    differing.count() + anterior.len().max(current.len()) - common
+}
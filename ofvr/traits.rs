@@ -43,7 +43,7 @@ pub trait PlainBytes: for<'a> Deserialize<'a> + Serialize + Sized {
 pub trait FileSystemBytes: PlainBytes {
     fn save_to_file(&self, path: impl Into<Path>) -> Result<()> {
         let path = path.into();
-        path.write(&self.to_bytes())?;
+        crate::io::write_atomic(&path, &self.to_bytes())?;
         Ok(())
     }
     fn load_from_file(path: impl Into<Path>) -> Result<Self> {
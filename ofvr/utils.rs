@@ -1,5 +1,6 @@
 use std::io::{Read, Write};
 
+use bincode::Options;
 use flate2::read::DeflateDecoder;
 use flate2::write::DeflateEncoder;
 use flate2::Compression;
@@ -45,6 +46,15 @@ pub fn from_deflate_bytes<T: for<'a> Deserialize<'a>>(bytes: &[u8]) -> Result<T>
     Ok(bincode::deserialize::<T>(&bytes).unwrap())
 }
 
+/// `from_strict_bytes` deserializes bincode bytes failing rather than
+/// ignoring trailing bytes, so that layouts can be told apart
+pub fn from_strict_bytes<T: for<'a> Deserialize<'a>>(bytes: &[u8]) -> Result<T> {
+    Ok(bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+        .deserialize::<T>(bytes)?)
+}
+
 pub fn chunk_padded(items: &[u8], chunk_size: usize, padding: u8) -> Vec<Vec<u8>> {
     let rem = rem(items, chunk_size);
     let mut items = items.iter().map(|byte| *byte).collect::<Vec<u8>>();
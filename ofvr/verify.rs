@@ -0,0 +1,316 @@
+//! Integrity verification of state files, see [`OFVRState::verify`]
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use iocore::Path;
+use serde::Serialize;
+
+use crate::data::Data;
+use crate::errors::{Error, Result};
+use crate::hash::keccak256;
+use crate::models::id::ID;
+use crate::models::state::OFVRState;
+use crate::traits::PlainBytes;
+
+/// `ProblemKind` classifies the inconsistencies found by [`verify`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProblemKind {
+    /// the state file cannot be loaded, for instance because its
+    /// journal was damaged before its last transaction, see
+    /// [`crate::format::check_tail`]
+    UnreadableState,
+    /// the commit data cannot be decoded
+    UnreadableData,
+    /// the commit id differs from the id recomputed from its data
+    IdMismatch,
+    /// the author of the commit is not present in the state
+    UnknownAuthor,
+    /// the author recorded in the commit differs from the author in its data
+    AuthorMismatch,
+    /// the delta of the commit cannot be applied to rebuild its version
+    BrokenDelta,
+    /// the commit is not linked to parents preceding it
+    BrokenChain,
+    /// the digest recorded for the commit is missing or differs from
+    /// the digest of its version
+    DigestMismatch,
+    /// the keyframe recorded for the commit cannot be decompressed or
+    /// differs from its version
+    KeyframeMismatch,
+    /// the version of the commit is missing from the content-addressed
+    /// table or differs in length, see [`OFVRState::objects`]
+    ObjectMismatch,
+    /// a chunk of the version of the commit is not present in the
+    /// state, see [`OFVRState::manifests`]
+    MissingChunk,
+}
+impl Display for ProblemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ProblemKind::UnreadableState => "unreadable-state",
+                ProblemKind::UnreadableData => "unreadable-data",
+                ProblemKind::IdMismatch => "id-mismatch",
+                ProblemKind::UnknownAuthor => "unknown-author",
+                ProblemKind::AuthorMismatch => "author-mismatch",
+                ProblemKind::BrokenDelta => "broken-delta",
+                ProblemKind::BrokenChain => "broken-chain",
+                ProblemKind::DigestMismatch => "digest-mismatch",
+                ProblemKind::KeyframeMismatch => "keyframe-mismatch",
+                ProblemKind::ObjectMismatch => "object-mismatch",
+                ProblemKind::MissingChunk => "missing-chunk",
+            }
+        )
+    }
+}
+
+/// `Problem` found in the commit at `index` of [`OFVRState::commits`],
+/// or in the state file as a whole when `commit` is empty
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct Problem {
+    pub index: usize,
+    pub commit: String,
+    pub kind: ProblemKind,
+    pub message: String,
+}
+impl Problem {
+    pub fn new(index: usize, commit: &str, kind: ProblemKind, message: impl Display) -> Problem {
+        Problem {
+            index,
+            commit: commit.to_string(),
+            kind,
+            message: message.to_string(),
+        }
+    }
+}
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.commit.is_empty() {
+            return write!(f, "{}: {}", self.kind, self.message);
+        }
+        write!(f, "commit {} ({}): {}: {}", self.index, self.commit, self.kind, self.message)
+    }
+}
+
+/// `verify` checks every commit of the state and returns all the
+/// problems found rather than stopping at the first one.
+///
+/// Each version reconstructs when every commit is linked to parents
+/// preceding it and the delta of every commit applies, see
+/// [`OFVRState::version`], in which case the digest, the keyframe and
+/// the object recorded for each commit are checked against its
+/// version as well
+pub fn verify(state: &OFVRState) -> Vec<Problem> {
+    let mut problems = Vec::<Problem>::new();
+    let mut preceding = HashSet::<ID>::new();
+    // an unreadable pack leaves the chunks it would hold missing
+    let pack = state.pack().ok();
+    for (index, commit) in state.commits().iter().enumerate() {
+        let hex = commit.id.to_hex();
+        let mut problem = |kind: ProblemKind, message: String| {
+            problems.push(Problem::new(index, &hex, kind, message))
+        };
+        if state.get_author(commit.author_id()).is_err() {
+            problem(
+                ProblemKind::UnknownAuthor,
+                format!("author {} NOT present in state", commit.author_id()),
+            );
+        }
+        let data = match commit.data(state) {
+            Ok(data) => data,
+            Err(error) => {
+                problem(ProblemKind::UnreadableData, error.to_string());
+                continue;
+            },
+        };
+        match data.id() {
+            Ok(id) if id == commit.id => {},
+            Ok(id) => problem(ProblemKind::IdMismatch, format!("data hashes to {}", id.to_hex())),
+            Err(error) => problem(ProblemKind::IdMismatch, error.to_string()),
+        }
+        if data.author_id() != commit.author_id() {
+            problem(
+                ProblemKind::AuthorMismatch,
+                format!(
+                    "commit records author {} but its data records author {}",
+                    commit.author_id(),
+                    data.author_id()
+                ),
+            );
+        }
+        if let Err(error) = data.delta().check() {
+            problem(ProblemKind::BrokenDelta, error.to_string());
+        }
+        if let Err(error) = state.check_digest(&commit.id, &data.delta()) {
+            problem(ProblemKind::DigestMismatch, error.to_string());
+        }
+        if let Some(manifest) = state.manifests().get(&commit.id) {
+            let held = |hash: &Data| pack.as_ref().is_some_and(|pack| pack.contains(&hash.inner));
+            for hash in manifest.iter().filter(|hash| !held(hash)) {
+                problem(
+                    ProblemKind::MissingChunk,
+                    format!("chunk {} NOT present in state", hash.to_hex()),
+                );
+            }
+        }
+        let parents = data.parents();
+        if index == 0 && !parents.is_empty() {
+            problem(ProblemKind::BrokenChain, format!("first commit links to {}", parents[0]));
+        } else if index > 0 && parents.is_empty() {
+            problem(ProblemKind::BrokenChain, "commit links to no parent".to_string());
+        } else if let Some(parent) = parents.iter().find(|parent| !preceding.contains(*parent)) {
+            problem(
+                ProblemKind::BrokenChain,
+                format!("parent {} does not precede the commit", parent),
+            );
+        }
+        preceding.insert(commit.id.clone());
+    }
+    if problems.is_empty() {
+        let mut index = 0;
+        let result = state.for_each_version(|commit, version| {
+            let data = commit.data(state)?;
+            let delta = data.delta();
+            let object = state.digests().get(&commit.id).and_then(|digest| state.objects().get(digest));
+            // commits referencing the version of an earlier commit hold
+            // a delta without hunks, any other delta rebuilds it as well
+            let own = match object {
+                Some(object) if object.commit() != commit.id && !delta.hunks().is_empty() => {
+                    let anterior = match data.parents().first() {
+                        Some(parent) => state.version(&state.get_commit(parent)?)?,
+                        None => Vec::new(),
+                    };
+                    Some(delta.apply(&anterior))
+                },
+                _ => None,
+            };
+            let version = own.as_deref().unwrap_or(version);
+            let digest = state.digests().get(&commit.id);
+            match object {
+                _ if digest != Some(&keccak256(version)) => {},
+                Some(object) if object.length() == version.len() as u64 => {},
+                Some(object) => problems.push(Problem::new(
+                    index,
+                    &commit.id.to_hex(),
+                    ProblemKind::ObjectMismatch,
+                    format!(
+                        "version of {} bytes held as an object of {} bytes",
+                        version.len(),
+                        object.length()
+                    ),
+                )),
+                None => problems.push(Problem::new(
+                    index,
+                    &commit.id.to_hex(),
+                    ProblemKind::ObjectMismatch,
+                    "version missing from the content-addressed table",
+                )),
+            }
+            match digest {
+                Some(digest) if *digest == keccak256(version) => {},
+                Some(_) => problems.push(Problem::new(
+                    index,
+                    &commit.id.to_hex(),
+                    ProblemKind::DigestMismatch,
+                    "recorded digest differs from the digest of the version",
+                )),
+                None => problems.push(Problem::new(
+                    index,
+                    &commit.id.to_hex(),
+                    ProblemKind::DigestMismatch,
+                    "no digest recorded",
+                )),
+            }
+            if let Some(keyframe) = state.keyframes().get(&commit.id) {
+                match crate::keyframe::decompress(keyframe) {
+                    Ok(keyframe) if keyframe == version => {},
+                    Ok(_) => problems.push(Problem::new(
+                        index,
+                        &commit.id.to_hex(),
+                        ProblemKind::KeyframeMismatch,
+                        "recorded keyframe differs from the version",
+                    )),
+                    Err(error) => problems.push(Problem::new(
+                        index,
+                        &commit.id.to_hex(),
+                        ProblemKind::KeyframeMismatch,
+                        error,
+                    )),
+                }
+            }
+            index += 1;
+            Ok(true)
+        });
+        if let Err(error) = result {
+            let hex = state.commits().get(index).map(|commit| commit.id.to_hex());
+            problems.push(Problem::new(
+                index,
+                &hex.unwrap_or_default(),
+                ProblemKind::BrokenDelta,
+                error,
+            ));
+        }
+    }
+    problems
+}
+
+/// `FsckReport` of a state file, see [`FsckReport::exit_code`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct FsckReport {
+    pub ofvr_state_path: String,
+    pub commits: usize,
+    pub problems: Vec<Problem>,
+}
+impl FsckReport {
+    /// `new` loads the state file at `ofvr_state_path` and verifies it,
+    /// state files that cannot be read or locked result in an error
+    /// while those that cannot be decoded are reported as a problem
+    pub fn new(ofvr_state_path: &Path) -> Result<FsckReport> {
+        let state = match OFVRState::from_path(ofvr_state_path) {
+            Ok(state) => state,
+            Err(error @ (Error::IOError(_) | Error::LockError(_))) => return Err(error),
+            Err(error) => {
+                return Ok(FsckReport {
+                    ofvr_state_path: ofvr_state_path.to_string(),
+                    commits: 0,
+                    problems: vec![Problem::new(0, "", ProblemKind::UnreadableState, error)],
+                })
+            },
+        };
+        Ok(FsckReport {
+            ofvr_state_path: ofvr_state_path.to_string(),
+            commits: state.commits().len(),
+            problems: state.verify(),
+        })
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// `exit_code` is 0 when no problems were found and 2 otherwise,
+    /// leaving 1 to errors such as a state file that cannot be loaded
+    pub fn exit_code(&self) -> u8 {
+        if self.is_ok() {
+            0
+        } else {
+            2
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        if self.is_ok() {
+            format!("{}: ok, {} commits verified", self.ofvr_state_path, self.commits)
+        } else {
+            format!(
+                "{}: {} problems found in {} commits",
+                self.ofvr_state_path,
+                self.problems.len(),
+                self.commits
+            )
+        }
+    }
+}
@@ -0,0 +1,30 @@
+//! Helpers shared by the tests
+use iocore::Path;
+use ofvr::errors::Result;
+use ofvr::index::index_path;
+use ofvr::models::author::Author;
+use ofvr::oplog::oplog_path;
+use ofvr::pack::pack_path;
+use ofvr::state::OFVRState;
+
+/// `empty` creates a state at `path` without the files left next to
+/// it by earlier runs: its operation log, pack, index and backups
+pub fn empty(path: &Path, author: &Author) -> Result<OFVRState> {
+    let backups = match path.parent() {
+        Some(directory) if directory.is_dir() => directory.list()?,
+        _ => Vec::new(),
+    };
+    let prefix = format!("{}.", path.name());
+    let backups = backups
+        .into_iter()
+        .filter(|file| file.name().starts_with(&prefix) && file.name().ends_with(".bak"));
+    for sidecar in [oplog_path(path), pack_path(path), index_path(path)]
+        .into_iter()
+        .chain(backups)
+    {
+        if sidecar.is_file() {
+            sidecar.delete()?;
+        }
+    }
+    OFVRState::empty(path, author)
+}
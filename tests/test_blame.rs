@@ -0,0 +1,87 @@
+use iocore_test::path_to_test_file;
+use ofvr::blame::parse_byte_range;
+use ofvr::errors::Result;
+use ofvr::models::author::Author;
+use ofvr::state::OFVRState;
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+#[test]
+fn test_blame_attributes_runs_to_the_last_change() -> Result<()> {
+    let author = author();
+    let patcher = Author::new("Patcher", "patcher@example.com");
+    let path = path_to_test_file!("blame.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    let mut image = vec![0xFFu8; 0x100];
+    let first = state.commit_blob(&image, &author, "Erased image")?;
+    image[0x40..0x50].copy_from_slice(&[0xAA; 0x10]);
+    let second = state.commit_blob(&image, &patcher, "Patch 0x40")?;
+    // rewriting the same bytes does not change their attribution
+    image[0x80..0x90].copy_from_slice(&[0xBB; 0x10]);
+    image[0x40] = 0xAA;
+    let third = state.commit_blob(&image, &author, "Patch 0x80")?;
+    image.extend_from_slice(&[0xCC; 0x20]);
+    let fourth = state.commit_blob(&image, &author, "Append")?;
+
+    let runs = state.blame(&fourth, None)?;
+    let summary = runs
+        .iter()
+        .map(|run| (run.start, run.end, run.commit.id.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (0x00, 0x40, first.id.clone()),
+            (0x40, 0x50, second.id.clone()),
+            (0x50, 0x80, first.id.clone()),
+            (0x80, 0x90, third.id.clone()),
+            (0x90, 0x100, first.id.clone()),
+            (0x100, 0x120, fourth.id.clone()),
+        ]
+    );
+    assert_eq!(runs[1].author, patcher);
+    assert_eq!(runs[0].author, author);
+
+    let runs = state.blame(&fourth, Some(0x48..0x84))?;
+    let summary = runs.iter().map(|run| (run.start, run.end)).collect::<Vec<_>>();
+    assert_eq!(summary, vec![(0x48, 0x50), (0x50, 0x80), (0x80, 0x84)]);
+
+    // ranges are clipped to the version and may not start past its end
+    let runs = state.blame(&second, Some(0xF0..0x200))?;
+    assert_eq!(runs.len(), 1);
+    assert_eq!((runs[0].start, runs[0].end), (0xF0, 0x100));
+    let error = state.blame(&second, Some(0x100..0x200)).err().expect("error");
+    assert_eq!(error.variant(), "BlameError");
+    Ok(())
+}
+
+#[test]
+fn test_blame_after_truncation() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("truncation.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    state.commit_blob(b"0123456789", &author, "Commit 1")?;
+    let second = state.commit_blob(b"01234", &author, "Commit 2")?;
+    let third = state.commit_blob(b"0123456789", &author, "Commit 3")?;
+
+    let runs = state.blame(&third, None)?;
+    assert_eq!(runs.len(), 2);
+    assert_eq!((runs[0].start, runs[0].end), (0, 5));
+    assert_ne!(runs[0].commit, second);
+    assert_eq!((runs[1].start, runs[1].end, runs[1].commit.clone()), (5, 10, third));
+    Ok(())
+}
+
+#[test]
+fn test_parse_byte_range() -> Result<()> {
+    assert_eq!(parse_byte_range("0x4000:0x4100")?, 0x4000..0x4100);
+    assert_eq!(parse_byte_range("16:32")?, 16..32);
+    assert_eq!(parse_byte_range("0x10:")?, 16..usize::MAX);
+    for range in ["", "16", "32:16", "16:16", "x:1", "0xg:0x10"] {
+        let error = parse_byte_range(range).err().expect("error");
+        assert_eq!(error.variant(), "BlameError", "{}", range);
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use iocore::Path;
+use iocore_test::path_to_test_file;
+use ofvr::errors::Result;
+use ofvr::models::author::Author;
+use ofvr::state::{OFVRState, DEFAULT_BRANCH};
+use ofvr::LogQuery;
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+fn messages(state: &OFVRState, query: &LogQuery) -> Result<Vec<String>> {
+    state
+        .log(query)?
+        .iter()
+        .map(|commit| Ok(commit.data(state)?.message()))
+        .collect()
+}
+
+/// `diverged` returns a state whose `main` branch and `customer`
+/// branch share their first commit
+fn diverged(path: &Path) -> Result<OFVRState> {
+    let author = author();
+    let mut state = OFVRState::empty(path, &author)?;
+    let base = state.commit_blob(b"firmware 1.0", &author, "base")?;
+    state.commit_blob(b"firmware 1.1", &author, "main fix")?;
+    state.create_branch("customer", &base)?;
+    state.commit_blob_on("customer", b"firmware 1.0-customer", &author, "customer tweak")?;
+    state.commit_blob(b"firmware 1.2", &author, "main feature")?;
+    Ok(state)
+}
+
+#[test]
+fn test_branches_diverge_from_common_ancestor() -> Result<()> {
+    let path = path_to_test_file!("diverged.ofvr");
+    let mut state = diverged(&path)?;
+    assert_eq!(state.current_branch(), DEFAULT_BRANCH);
+    assert_eq!(
+        state.branches().keys().cloned().collect::<Vec<String>>(),
+        vec!["customer", "main"]
+    );
+    assert_eq!(state.latest_version()?, b"firmware 1.2".to_vec());
+
+    let customer = state.branch_head("customer")?.expect("head");
+    assert_eq!(state.version(&customer)?, b"firmware 1.0-customer".to_vec());
+    assert_eq!(customer.data(&state)?.parents(), vec![state.commits()[0].id.clone()]);
+
+    let query = LogQuery::new();
+    assert_eq!(messages(&state, &query)?, vec!["base", "main fix", "main feature"]);
+    let query = LogQuery {
+        branch: Some(String::from("customer")),
+        ..LogQuery::new()
+    };
+    assert_eq!(messages(&state, &query)?, vec!["base", "customer tweak"]);
+    let query = LogQuery {
+        all: true,
+        ..LogQuery::new()
+    };
+    assert_eq!(messages(&state, &query)?.len(), 4);
+
+    assert_eq!(state.resolve("customer")?, customer);
+    assert_eq!(state.resolve("HEAD~1")?.data(&state)?.message(), "main fix");
+    assert_eq!(state.resolve("r3")?.data(&state)?.message(), "main feature");
+
+    state.switch_branch("customer")?;
+    assert_eq!(state.latest_version()?, b"firmware 1.0-customer".to_vec());
+    assert_eq!(state.resolve("HEAD")?, customer);
+    assert_eq!(state.resolve("r2")?, customer);
+
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded, state);
+    assert_eq!(reloaded.current_branch(), "customer");
+    assert_eq!(reloaded.verify(), Vec::new());
+    Ok(())
+}
+
+#[test]
+fn test_branch_create_delete_and_switch_errors() -> Result<()> {
+    let path = path_to_test_file!("errors.ofvr");
+    let mut state = diverged(&path)?;
+    let head = state.latest_commit().expect("head");
+
+    for name in ["customer", "HEAD", "r2", "beef", "-x", "a..b", "a b"] {
+        let error = state.create_branch(name, &head).err().expect("error");
+        assert_eq!(error.variant(), "BranchError", "{}", name);
+    }
+    let error = state.delete_branch(DEFAULT_BRANCH).err().expect("error");
+    assert_eq!(error.variant(), "BranchError");
+    let error = state.switch_branch("unknown").err().expect("error");
+    assert_eq!(error.variant(), "BranchError");
+
+    let customer = state.branch_head("customer")?.expect("head");
+    assert_eq!(state.delete_branch("customer")?, customer.id);
+    assert_eq!(state.branch_head("customer").err().expect("error").variant(), "BranchError");
+    assert_eq!(state.resolve("customer").err().expect("error").variant(), "RevisionError");
+
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded.branches().len(), 1);
+    assert_eq!(reloaded.commits().len(), 4);
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use iocore_test::path_to_test_file;
+use ofvr::chunk::{split, MAX_CHUNK_SIZE, MIN_CHUNK_SIZE};
+use ofvr::errors::Result;
+use ofvr::models::author::Author;
+use ofvr::pack::pack_path;
+use ofvr::state::OFVRState;
+use ofvr::{keccak256, OperationKind, ProblemKind, Storage};
+
+mod common;
+use common::empty;
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+/// `image` returns `length` bytes that do not repeat, as firmware images would
+fn image(length: usize) -> Vec<u8> {
+    let mut image = Vec::<u8>::with_capacity(length + 32);
+    let mut block = 0u64;
+    while image.len() < length {
+        image.extend(keccak256(&block.to_le_bytes()));
+        block += 1;
+    }
+    image.truncate(length);
+    image
+}
+
+fn inserted(image: &[u8], offset: usize, bytes: &[u8]) -> Vec<u8> {
+    let mut inserted = image[..offset].to_vec();
+    inserted.extend(bytes);
+    inserted.extend(&image[offset..]);
+    inserted
+}
+
+#[test]
+fn test_split_survives_insertions() {
+    let original = image(512 << 10);
+    let chunks = split(&original);
+    assert_eq!(chunks.concat(), original);
+    assert!(chunks[..chunks.len() - 1]
+        .iter()
+        .all(|chunk| chunk.len() >= MIN_CHUNK_SIZE && chunk.len() <= MAX_CHUNK_SIZE));
+
+    let shifted = inserted(&original, 100, b"bootloader patch");
+    let hashes = chunks.iter().map(|chunk| keccak256(chunk)).collect::<Vec<_>>();
+    let changed = split(&shifted)
+        .iter()
+        .filter(|chunk| !hashes.contains(&keccak256(chunk)))
+        .count();
+    assert_eq!(changed, 1);
+    assert_eq!(split(&[]), Vec::<&[u8]>::new());
+}
+
+#[test]
+fn test_chunks_are_stored_once() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("chunks.ofvr");
+    let mut state = empty(&path, &author)?;
+    state.set_storage(Storage::Chunks)?;
+    let original = image(256 << 10);
+    let shifted = inserted(&original, 100, b"bootloader patch");
+    let first = state.commit_blob(&original, &author, "Original")?;
+    let stored = state.pack()?.len();
+    let second = state.commit_blob(&shifted, &author, "Shifted")?;
+    assert_eq!(state.pack()?.len(), stored + 1);
+    assert_eq!(state.manifests().len(), 2);
+    assert!(second.data(&state)?.delta().hunks().is_empty());
+    assert_eq!(state.delta(&second)?.apply(&original), shifted);
+    assert_eq!(state.dedup_stats()?.chunks, stored + 1);
+
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded, state);
+    assert_eq!(reloaded.storage(), Storage::Chunks);
+    assert_eq!(reloaded.version(&first)?, original);
+    assert_eq!(reloaded.version(&second)?, shifted);
+    assert_eq!(reloaded.verify(), Vec::new());
+    Ok(())
+}
+
+#[test]
+fn test_storage_applies_to_later_commits() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("switch.ofvr");
+    let mut state = empty(&path, &author)?;
+    let first = state.commit_blob(&image(64 << 10), &author, "As a delta")?;
+    state.set_storage(Storage::Chunks)?;
+    assert_eq!(
+        state.operations()?.last().map(|operation| operation.kind),
+        Some(OperationKind::Storage)
+    );
+    let second = state.commit_blob(&image(96 << 10), &author, "As chunks")?;
+    let third = state.commit_blob(&image(32 << 10), &author, "As chunks again")?;
+    assert!(!first.data(&state)?.delta().hunks().is_empty());
+    assert!(!state.manifests().contains_key(&first.id));
+
+    // the chunks only the dropped commits referenced stay in the pack
+    // for the backup
+    state.reset(&second)?;
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded.storage(), Storage::Chunks);
+    assert_eq!(reloaded.version(&first)?, image(64 << 10));
+    assert_eq!(reloaded.version(&second)?, image(96 << 10));
+    assert!(reloaded.get_commit(&third.id).is_err());
+    let mut hashes = split(&image(96 << 10))
+        .into_iter()
+        .chain(split(&image(32 << 10)))
+        .map(keccak256)
+        .collect::<Vec<_>>();
+    hashes.sort();
+    hashes.dedup();
+    assert_eq!(reloaded.pack()?.iter().map(|(hash, _)| hash.to_vec()).collect::<Vec<_>>(), hashes);
+    assert_eq!(reloaded.verify(), Vec::new());
+
+    let reset = state.operations()?.len() - 1;
+    state.undo(reset)?;
+    let restored = OFVRState::from_path(&path)?;
+    assert_eq!(restored.version(&third)?, image(32 << 10));
+    assert_eq!(restored.verify(), Vec::new());
+    Ok(())
+}
+
+#[test]
+fn test_verify_reports_missing_chunks() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("missing_chunk.ofvr");
+    let mut state = empty(&path, &author)?;
+    state.set_storage(Storage::Chunks)?;
+    state.commit_blob(&image(64 << 10), &author, "Commit 1")?;
+    state.commit_blob(&image(80 << 10), &author, "Commit 2")?;
+
+    // the last chunk of the pack no longer fits in it
+    let pack = std::fs::OpenOptions::new().write(true).open(pack_path(&path).to_path_buf())?;
+    pack.set_len(pack.metadata()?.len() - 1)?;
+    drop(pack);
+    let problems = OFVRState::from_path(&path)?.verify();
+    assert!(!problems.is_empty());
+    assert!(problems.iter().all(|problem| problem.kind == ProblemKind::MissingChunk));
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use iocore_test::path_to_test_file;
+use ofvr::errors::Result;
+use ofvr::models::author::Author;
+use ofvr::state::OFVRState;
+use ofvr::{keccak256, PlainBytes, ProblemKind};
+
+mod common;
+use common::empty;
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+#[test]
+fn test_identical_versions_reference_the_earlier_commit() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("dedup.ofvr");
+    let mut state = empty(&path, &author)?;
+    let stable = vec![0x55u8; 1024];
+    let mut patched = stable.clone();
+    patched[256..768].fill(0xAA);
+    let first = state.commit_blob(&stable, &author, "Stable")?;
+    let second = state.commit_blob(&patched, &author, "Patched")?;
+    let rollback = state.revert(&second, &author)?;
+    let again = state.commit_blob(&patched, &author, "Patched again")?;
+
+    assert_eq!(state.objects().len(), 2);
+    assert_eq!(state.objects()[&keccak256(&stable)].commit(), first.id);
+    assert_eq!(state.objects()[&keccak256(&patched)].commit(), second.id);
+    for commit in [&rollback, &again] {
+        assert!(commit.data(&state)?.delta().hunks().is_empty());
+    }
+    assert_eq!(state.version(&rollback)?, stable);
+    assert_eq!(state.version(&again)?, patched);
+    assert_eq!(state.delta(&rollback)?.size(), 512);
+
+    let stats = state.dedup_stats()?;
+    assert_eq!((stats.commits, stats.objects), (4, 2));
+    assert_eq!((stats.deduplicated, stats.deduplicated_bytes), (2, 2048));
+    assert_eq!(stats.stored_bytes, 1024 + 512);
+
+    // the rollback changed the bytes back
+    let runs = state.blame(&rollback, Some(0..1024))?;
+    let summary = runs.iter().map(|run| (run.start, run.end, run.commit.id.clone()));
+    assert_eq!(
+        summary.collect::<Vec<_>>(),
+        vec![
+            (0, 256, first.id.clone()),
+            (256, 768, rollback.id.clone()),
+            (768, 1024, first.id.clone()),
+        ]
+    );
+
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded, state);
+    assert_eq!(reloaded.verify(), Vec::new());
+    Ok(())
+}
+
+#[test]
+fn test_rewriting_keeps_referenced_versions() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("dedup_rewrite.ofvr");
+    let mut state = empty(&path, &author)?;
+    let first = state.commit_blob(b"bootloader 1", &author, "Commit 1")?;
+    state.commit_blob(b"bootloader 2", &author, "Commit 2")?;
+    state.create_branch("feature", &first)?;
+    let feature = state.commit_blob_on("feature", b"bootloader 2", &author, "Feature")?;
+    assert!(feature.data(&state)?.delta().hunks().is_empty());
+
+    // the feature commit holds the version once the commit it
+    // referenced is amended
+    let (amended, _) = state.amend(Some("Commit 2, amended"), None)?;
+    assert_eq!(state.objects()[&keccak256(b"bootloader 2")].commit(), feature.id);
+    assert!(state.keyframes().contains_key(&feature.id));
+    assert_eq!(state.version(&amended)?, b"bootloader 2".to_vec());
+
+    state.reset(&first)?;
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded.commits().len(), 2);
+    assert_eq!(reloaded.version(&feature)?, b"bootloader 2".to_vec());
+    assert_eq!(reloaded.verify(), Vec::new());
+    Ok(())
+}
+
+#[test]
+fn test_references_without_digest_are_refused() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("dedup_digest.ofvr");
+    let mut state = empty(&path, &author)?;
+    state.commit_blob(b"bootloader 1", &author, "Commit 1")?;
+    let second = state.commit_blob(b"bootloader 2", &author, "Commit 2")?;
+    let rollback = state.revert(&second, &author)?;
+    assert!(rollback.data(&state)?.delta().hunks().is_empty());
+
+    // without its digest the rollback would rebuild the version of its parent
+    let mut value = serde_json::to_value(&state)?;
+    let id = serde_json::to_value(&rollback.id)?;
+    let digests = value["digests"].as_array_mut().expect("digests");
+    let before = digests.len();
+    digests.retain(|pair| pair[0] != id);
+    assert_eq!(digests.len(), before - 1);
+    let tampered: OFVRState = serde_json::from_value(value)?;
+    assert_eq!(tampered.version(&second)?, b"bootloader 2".to_vec());
+    assert_eq!(tampered.version(&rollback).err().expect("error").variant(), "StateError");
+    let problems = tampered.verify();
+    assert_eq!(problems.len(), 1);
+    assert_eq!(
+        (problems[0].kind, problems[0].commit.clone()),
+        (ProblemKind::DigestMismatch, rollback.id.to_hex())
+    );
+    Ok(())
+}
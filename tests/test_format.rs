@@ -0,0 +1,224 @@
+use iocore::Path;
+use iocore_test::{path_to_test_file, seq_bytes};
+use ofvr::errors::{Error, Result};
+use ofvr::format::{self, Header, Index, Record, FLAG_DEFLATE, FORMAT_VERSION, HEADER_LEN, MAGIC};
+use ofvr::lock::DEFAULT_LOCK_TIMEOUT;
+use ofvr::models::author::Author;
+use ofvr::state::OFVRState;
+use ofvr::{FsckReport, ProblemKind};
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+#[test]
+fn test_header_roundtrip() -> Result<()> {
+    let header = Header::new(FORMAT_VERSION, FLAG_DEFLATE, b"payload");
+    let bytes = header.to_bytes();
+    assert_eq!(bytes.len(), HEADER_LEN);
+    assert_eq!(&bytes[..4], &MAGIC);
+    assert_eq!(Header::parse(&bytes)?, Some(header.clone()));
+    assert!(header.has_flag(FLAG_DEFLATE));
+    header.verify(b"payload")?;
+    assert_eq!(header.verify(b"paYload").err().expect("error").variant(), "FormatError");
+    assert_eq!(Header::parse(b"not a header")?, None);
+    assert_eq!(Header::parse(&bytes[..12]).err().expect("error").variant(), "FormatError");
+    Ok(())
+}
+
+#[test]
+fn test_state_file_has_header_and_checksum() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("format.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    state.commit_blob(&seq_bytes(1024), &author, "Commit 1")?;
+
+    let mut bytes = path.read_bytes()?;
+    assert_eq!(format::version(&bytes)?, FORMAT_VERSION);
+    assert_eq!(OFVRState::from_path(&path)?, state);
+
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xFF;
+    path.write(&bytes)?;
+    assert_eq!(OFVRState::from_path(&path).err().expect("error").variant(), "FormatError");
+    Ok(())
+}
+
+#[test]
+fn test_newer_format_version_is_rejected() -> Result<()> {
+    let header = Header::new(FORMAT_VERSION + 1, 0, &[]);
+    let error = format::decode(&header.to_bytes()).err().expect("error");
+    assert_eq!(error.variant(), "FormatError");
+    Ok(())
+}
+
+#[test]
+fn test_upgrade_legacy_state_file() -> Result<()> {
+    let legacy = Path::new(file!()).with_filename("legacy-diff.ofvr");
+    let path = path_to_test_file!("legacy.ofvr");
+    path.write(&legacy.read_bytes()?)?;
+    assert_eq!(format::version(&path.read_bytes()?)?, 0);
+
+    let before = OFVRState::from_path(&path)?;
+    assert_eq!(OFVRState::upgrade(&path, DEFAULT_LOCK_TIMEOUT)?, 0);
+    assert_eq!(format::version(&path.read_bytes()?)?, FORMAT_VERSION);
+    assert_eq!(OFVRState::from_path(&path)?, before);
+
+    assert_eq!(OFVRState::upgrade(&path, DEFAULT_LOCK_TIMEOUT)?, FORMAT_VERSION);
+    Ok(())
+}
+
+#[test]
+fn test_commit_appends_to_journal() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("journal.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    state.commit_blob(&seq_bytes(4096), &author, "Commit 1")?;
+
+    for index in 2..6 {
+        let before = path.read_bytes()?;
+        let mut data = seq_bytes(4096);
+        data[index * 10] = 0xFF;
+        state.commit_blob(&data, &author, &format!("Commit {}", index))?;
+
+        let after = path.read_bytes()?;
+        assert!(after.starts_with(&before));
+        assert!(after.len() - before.len() < 512);
+    }
+    assert_eq!(OFVRState::from_path(&path)?, state);
+    Ok(())
+}
+
+#[test]
+fn test_journal_survives_interrupted_append() -> Result<()> {
+    let author = author();
+    let qa = Author::new("Gabriel DeMoura", "gabrielteratos+qa@gmail.com");
+    let path = path_to_test_file!("crash.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    state.commit_blob(&seq_bytes(512), &author, "Commit 1")?;
+    state.commit_blob(&seq_bytes(600), &author, "Commit 2")?;
+    let committed = path.read_bytes()?;
+    let expected = OFVRState::from_path(&path)?;
+
+    state.commit_blob(&seq_bytes(700), &qa, "Commit 3")?;
+    let appended = path.read_bytes()?;
+
+    for length in committed.len()..appended.len() {
+        path.write(&appended[..length])?;
+        let mut recovered = OFVRState::from_path(&path)?;
+        assert_eq!(recovered, expected, "truncated at {} of {}", length, appended.len());
+
+        if length % 64 == 0 || length == appended.len() - 1 {
+            recovered.commit_blob(&seq_bytes(800), &qa, "Commit 3")?;
+            let reloaded = OFVRState::from_path(&path)?;
+            assert_eq!(reloaded.commits().len(), 3);
+            assert_eq!(reloaded.latest_version()?, seq_bytes(800));
+            assert_eq!(reloaded.get_author(qa.id())?, qa);
+        }
+    }
+    path.write(&appended)?;
+    assert_eq!(OFVRState::from_path(&path)?, state);
+    Ok(())
+}
+
+#[test]
+fn test_index_within_torn_record_is_not_damage() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("embedded.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    state.commit_blob(&seq_bytes(512), &author, "Commit 1")?;
+    let committed = path.read_bytes()?;
+    let expected = OFVRState::from_path(&path)?;
+
+    let index = Record::Index(Index {
+        commits: 2,
+        entries: Vec::new(),
+        chain: [0u8; 8],
+    })
+    .to_bytes();
+    let data = [&seq_bytes(256)[..], &index[..], &seq_bytes(256)[..]].concat();
+    state.commit_blob(&data, &author, "Commit 2")?;
+    let appended = path.read_bytes()?;
+    let embedded = appended[committed.len()..]
+        .windows(index.len())
+        .position(|window| window == &index[..])
+        .expect("embedded index")
+        + committed.len();
+
+    // the append is torn right after the index held by the commit
+    path.write(&appended[..embedded + index.len()])?;
+    assert_eq!(OFVRState::from_path(&path)?, expected);
+    Ok(())
+}
+
+#[test]
+fn test_damaged_journal_is_not_truncated() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("damaged.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    state.commit_blob(&seq_bytes(512), &author, "Commit 1")?;
+    let offset = path.read_bytes()?.len();
+    state.commit_blob(&seq_bytes(600), &author, "Commit 2")?;
+    state.commit_blob(&seq_bytes(700), &author, "Commit 3")?;
+
+    // one byte of the record of the second commit
+    let mut damaged = path.read_bytes()?;
+    damaged[offset + 20] ^= 0xFF;
+    path.write(&damaged)?;
+    assert!(matches!(OFVRState::from_path(&path), Err(Error::FormatError(_))));
+    let report = FsckReport::new(&path)?;
+    assert_eq!(report.problems[0].kind, ProblemKind::UnreadableState);
+    assert_eq!(report.exit_code(), 2);
+
+    // appending keeps the records following the damage
+    state.commit_blob(&seq_bytes(800), &author, "Commit 4")?;
+    let appended = path.read_bytes()?;
+    assert!(appended.starts_with(&damaged));
+    assert!(appended.len() > damaged.len());
+
+    damaged[offset + 20] ^= 0xFF;
+    path.write(&[&damaged[..], &appended[damaged.len()..]].concat())?;
+    let repaired = OFVRState::from_path(&path)?;
+    assert_eq!(repaired.commits().len(), 4);
+    assert_eq!(repaired.latest_version()?, seq_bytes(800));
+    Ok(())
+}
+
+#[test]
+fn test_journal_index_chains_its_transaction() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("chain.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    let commit = state.commit_blob(&seq_bytes(512), &author, "Commit 1")?;
+    let committed = path.read_bytes()?;
+    state.add_tag("v1", &commit, None, None)?;
+    let ours = path.read_bytes()?;
+
+    path.write(&committed)?;
+    let mut other = OFVRState::from_path(&path)?;
+    other.add_tag("v2", &commit, None, None)?;
+    let theirs = path.read_bytes()?;
+    assert_eq!(theirs.len(), ours.len());
+
+    // the transaction adding v2 does not follow the one adding v1
+    path.write(&[&ours[..], &theirs[committed.len()..]].concat())?;
+    assert!(matches!(OFVRState::from_path(&path), Err(Error::FormatError(_))));
+    Ok(())
+}
+
+#[test]
+fn test_compact_rewrites_journal_as_snapshot() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("compact.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    for index in 0..16 {
+        state.commit_blob(&seq_bytes(256 + index), &author, "Commit")?;
+    }
+    let length = path.read_bytes()?.len() as u64;
+    let (before, after) = state.compact()?;
+    assert_eq!(before, length);
+    assert!(after < before);
+    assert_eq!(after, path.read_bytes()?.len() as u64);
+    assert_eq!(OFVRState::from_path(&path)?, state);
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use iocore_test::path_to_test_file;
+use ofvr::errors::Result;
+use ofvr::models::author::Author;
+use ofvr::state::OFVRState;
+use ofvr::{keccak256, Identity, ProblemKind};
+
+mod common;
+use common::empty;
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+#[test]
+fn test_identify_exact_and_nearest_versions() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("identify.ofvr");
+    let mut state = empty(&path, &author)?;
+    assert_eq!(state.identify(b"anything")?, Identity::Unknown);
+
+    let first = state.commit_blob(b"firmware 1.0 build 17", &author, "Commit 1")?;
+    let second = state.commit_blob(b"firmware 1.1 build 23", &author, "Commit 2")?;
+    let third = state.commit_blob(b"firmware 1.0 build 17", &author, "Commit 3")?;
+    state.commit_blob(b"firmware 2.0 build 41, extended", &author, "Commit 4")?;
+
+    assert_eq!(state.digest(&second)?, keccak256(b"firmware 1.1 build 23"));
+    assert_eq!(
+        state.identify(b"firmware 1.1 build 23")?,
+        Identity::Exact(vec![second.clone()])
+    );
+    assert_eq!(
+        OFVRState::from_path(&path)?.identify(b"firmware 1.0 build 17")?,
+        Identity::Exact(vec![first, third])
+    );
+    assert_eq!(state.identify(b"firmware 1.1 build 29")?, Identity::Nearest(second.clone(), 1));
+    // bytes past the end of the file count as differing
+    assert_eq!(state.identify(b"firmware 1.1 build")?, Identity::Nearest(second.clone(), 3));
+
+    let file = path_to_test_file!("identify.bin");
+    file.write(b"firmware 1.1 build 23")?;
+    assert_eq!(state.identify_file(&file)?, Identity::Exact(vec![second.clone()]));
+    file.write(b"firmware 1.1 build 29")?;
+    assert_eq!(state.identify_file(&file)?, Identity::Nearest(second, 1));
+    file.delete()?;
+    assert_eq!(state.identify_file(&file).err().expect("error").variant(), "IOError");
+    Ok(())
+}
+
+#[test]
+fn test_digests_are_kept_by_history_rewriting() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("digests.ofvr");
+    let mut state = empty(&path, &author)?;
+    for index in 1..=4 {
+        state.commit_blob(format!("version {}", index).as_bytes(), &author, "Commit")?;
+    }
+    state.amend(Some("Commit 4, amended"), None)?;
+    let (from, to) = (state.resolve("r1")?, state.resolve("r3")?);
+    state.squash(&from, &to, &author, "Commits 2 and 3")?;
+    state.reset(&state.resolve("HEAD~1")?)?;
+
+    assert_eq!(state.digests().len(), state.commits().len());
+    assert_eq!(
+        state.identify(b"version 3")?,
+        Identity::Exact(vec![state.latest_commit().expect("head")])
+    );
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded.verify(), Vec::new());
+    assert_eq!(reloaded.digests(), state.digests());
+    Ok(())
+}
+
+#[test]
+fn test_verify_reports_digest_mismatch() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("mismatch.ofvr");
+    let mut state = empty(&path, &author)?;
+    state.commit_blob(b"first", &author, "Commit 1")?;
+    state.commit_blob(b"second", &author, "Commit 2")?;
+
+    let mut value = serde_json::to_value(&state)?;
+    for digest in value["digests"].as_array_mut().expect("digests").iter_mut() {
+        digest[1] = serde_json::to_value(keccak256(b"first"))?;
+    }
+    let tampered: OFVRState = serde_json::from_value(value)?;
+    let problems = tampered.verify();
+    assert_eq!(
+        problems
+            .iter()
+            .map(|problem| (problem.index, problem.kind))
+            .collect::<Vec<_>>(),
+        vec![(1, ProblemKind::DigestMismatch)]
+    );
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use iocore::Path;
+use iocore_test::path_to_test_file;
+use ofvr::errors::Result;
+use ofvr::format::HEADER_LEN;
+use ofvr::index::index_path;
+use ofvr::models::author::Author;
+use ofvr::models::commit::Commit;
+use ofvr::models::commit_data::CommitData;
+use ofvr::models::delta::Delta;
+use ofvr::state::OFVRState;
+use ofvr::status::{Status, StatusReport};
+use ofvr::{CommitIndex, FsckReport, LogQuery, PlainBytes, ProblemKind, StateLock};
+
+mod common;
+use common::empty;
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+/// `corrupt_snapshot` damages the checksum of the snapshot of the
+/// state file at `path`, which the index checks without reading the
+/// snapshot itself
+fn corrupt_snapshot(path: &Path) -> Result<()> {
+    let mut bytes = path.read_bytes()?;
+    let length = u64::from_le_bytes(bytes[HEADER_LEN + 1..HEADER_LEN + 9].try_into().unwrap());
+    bytes[HEADER_LEN + 9 + length as usize] ^= 0xFF;
+    path.write(&bytes)?;
+    assert!(OFVRState::from_path(path).is_err());
+    Ok(())
+}
+
+fn messages(index: &CommitIndex, query: &LogQuery) -> Result<Vec<String>> {
+    Ok(index.log(query)?.into_iter().map(|entry| entry.message.clone()).collect())
+}
+
+#[test]
+fn test_log_of_ten_thousand_commits_reads_the_index() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("index_10k.ofvr");
+    let state = empty(&path, &author)?;
+    let mut commits = Vec::<Commit>::new();
+    for number in 0..10_000u32 {
+        let anterior = number.saturating_sub(1).to_le_bytes();
+        let delta = Delta::new(if number == 0 { &[] } else { &anterior }, &number.to_le_bytes());
+        let message = format!("Commit {}", number + 1);
+        let data = CommitData::new(&t16::Data::now(), delta, author.id(), &message, &path)?;
+        let parents = commits.last().map(|parent| parent.id.clone()).into_iter().collect();
+        commits.push(Commit::with_parents(data, parents, &state)?);
+    }
+    let mut value = serde_json::to_value(&state)?;
+    value["branches"]["main"] = serde_json::to_value(&commits[commits.len() - 1].id)?;
+    value["commits"] = serde_json::to_value(&commits)?;
+    let mut state: OFVRState = serde_json::from_value(value)?;
+    state.store()?;
+
+    let index = CommitIndex::load(&path)?;
+    assert_eq!(index.entries().len(), 10_000);
+    assert!(index_path(&path).is_file());
+    let query = LogQuery {
+        max_count: Some(2),
+        reverse: true,
+        ..LogQuery::new()
+    };
+    assert_eq!(messages(&index, &query)?, vec!["Commit 10000", "Commit 9999"]);
+    assert_eq!(state.log(&query)?, vec![commits[9_999].clone(), commits[9_998].clone()]);
+
+    let index = CommitIndex::load(&path)?;
+    assert_eq!(index.log(&LogQuery::new())?.len(), 10_000);
+    assert_eq!(index.resolve("r5000")?.message, "Commit 5000");
+    assert_eq!(index.resolve("HEAD~2")?.id, commits[9_997].id);
+    assert_eq!(index.resolve(&commits[42].id.to_hex()[..12])?.message, "Commit 43");
+    Ok(())
+}
+
+#[test]
+fn test_index_follows_the_journal() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("index_journal.ofvr");
+    let working = path_to_test_file!("index_journal.data");
+    let mut state = empty(&path, &author)?;
+    let first = state.commit_blob(b"bootloader 1", &author, "Commit 1")?;
+    let second = state.commit_blob(b"bootloader 2", &author, "Commit 2")?;
+    state.add_tag("v2", &second, None, None)?;
+
+    let index = CommitIndex::load(&path)?;
+    let indexed = index.entries().iter().map(|entry| (entry.id.clone(), entry.digest.clone()));
+    let committed = state
+        .commits()
+        .iter()
+        .map(|commit| (commit.id.clone(), state.digest(commit).ok()));
+    assert_eq!(indexed.collect::<Vec<_>>(), committed.collect::<Vec<_>>());
+    // the first commit was written with the snapshot of the journal
+    assert_eq!(index.get(&first.id)?.offset, None);
+    assert!(index.get(&second.id)?.offset.is_some());
+    assert_eq!(index.commit(&first.id)?, first);
+    assert_eq!(index.commit(&second.id)?.data(&state)?, second.data(&state)?);
+    assert_eq!(index.resolve("v2")?.id, second.id);
+
+    working.write(b"bootloader 3")?;
+    let report = StatusReport::new(&working, &path, None)?;
+    assert_eq!((report.status, report.changed_bytes), (Status::Modified, 1));
+
+    // commits appended since are indexed from the journal alone
+    state.create_branch("feature", &second)?;
+    state.switch_branch("feature")?;
+    let third = state.commit_blob(b"bootloader 3", &author, "Commit 3")?;
+    let index = CommitIndex::load(&path)?;
+    assert_eq!(index.current_branch(), "feature");
+    assert_eq!(index.branches()["feature"], third.id);
+    assert_eq!(index.resolve("HEAD")?.parents, vec![second.id.clone()]);
+    assert_eq!(index.commit(&third.id)?, third);
+    assert_eq!(index.get(&third.id)?.digest, Some(ofvr::keccak256(b"bootloader 3")));
+    let query = LogQuery {
+        grep: Some("Commit".to_string()),
+        ..LogQuery::new()
+    };
+    assert_eq!(messages(&index, &query)?, vec!["Commit 1", "Commit 2", "Commit 3"]);
+    let report = StatusReport::new(&working, &path, None)?;
+    assert_eq!((report.status, report.commit), (Status::Clean, Some(third.id.to_hex())));
+
+    // rewriting the state file rebuilds the index
+    state.reset(&first)?;
+    let index = CommitIndex::load(&path)?;
+    assert_eq!(index.entries().len(), 2);
+    assert_eq!(messages(&index, &LogQuery::new())?, vec!["Commit 1"]);
+    assert_eq!(index.tags().len(), 1);
+    assert_eq!(index.commit(&first.id)?, first);
+    Ok(())
+}
+
+#[test]
+fn test_index_of_a_damaged_state_file() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("index_damaged.ofvr");
+    let mut state = empty(&path, &author)?;
+    state.commit_blob(b"bootloader 1", &author, "Commit 1")?;
+    CommitIndex::load(&path)?;
+    assert!(index_path(&path).is_file());
+
+    // the index is not written while another process holds the lock
+    state.commit_blob(b"bootloader 2", &author, "Commit 2")?;
+    let indexed = index_path(&path).read_bytes()?;
+    let lock = StateLock::acquire(&path, std::time::Duration::ZERO)?;
+    assert_eq!(CommitIndex::load(&path)?.entries().len(), 2);
+    assert_eq!(index_path(&path).read_bytes()?, indexed);
+    drop(lock);
+    CommitIndex::load(&path)?;
+    assert_ne!(index_path(&path).read_bytes()?, indexed);
+
+    // history is not served from the index of a state file that no
+    // longer decodes
+    corrupt_snapshot(&path)?;
+    assert!(CommitIndex::load(&path).is_err());
+    let report = FsckReport::new(&path)?;
+    assert_eq!(report.problems.len(), 1);
+    assert_eq!(report.problems[0].kind, ProblemKind::UnreadableState);
+    Ok(())
+}
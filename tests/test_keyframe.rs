@@ -0,0 +1,126 @@
+use iocore_test::path_to_test_file;
+use ofvr::errors::Result;
+use ofvr::keyframe::{compress, KeyframePolicy};
+use ofvr::models::author::Author;
+use ofvr::state::OFVRState;
+use ofvr::{OperationKind, ProblemKind};
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+fn keyframed(state: &OFVRState) -> Vec<usize> {
+    state
+        .commits()
+        .iter()
+        .enumerate()
+        .filter(|(_, commit)| state.keyframes().contains_key(&commit.id))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+fn version(index: usize) -> Vec<u8> {
+    format!("firmware build {:04}", index).into_bytes()
+}
+
+#[test]
+fn test_keyframes_every_interval() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("interval.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    state.set_keyframe_policy(KeyframePolicy::new(3, 0));
+    for index in 0..8 {
+        state.commit_blob(&version(index), &author, "Commit")?;
+    }
+    let commits = state.commits().to_vec();
+    assert_eq!(keyframed(&state), vec![3, 6]);
+    assert_eq!(state.chain(&commits[0])?.0, 0);
+    assert_eq!(state.chain(&commits[6])?.0, 0);
+    assert_eq!(state.chain(&commits[7])?.0, 1);
+
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded, state);
+    assert_eq!(reloaded.verify(), Vec::new());
+    for (index, commit) in commits.iter().enumerate() {
+        assert_eq!(reloaded.version(commit)?, version(index));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_keyframes_over_threshold() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("threshold.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    state.set_keyframe_policy(KeyframePolicy::new(0, 100));
+    let mut image = vec![0u8; 256];
+    state.commit_blob(&image, &author, "Blank")?;
+    for index in 1..=6u8 {
+        image[..40].fill(index);
+        state.commit_blob(&image, &author, "Patch")?;
+    }
+    // each delta holds 40 bytes, so every third one exceeds 100 bytes
+    assert_eq!(keyframed(&state), vec![3, 6]);
+    assert_eq!(state.latest_version()?, image);
+    Ok(())
+}
+
+#[test]
+fn test_repack_stores_keyframes_in_existing_files() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("repack.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    for index in 0..10 {
+        state.commit_blob(&version(index), &author, "Commit")?;
+    }
+    state.create_branch("feature", &state.resolve("HEAD~2")?)?;
+    state.commit_blob_on("feature", b"feature build", &author, "Feature")?;
+    assert_eq!(state.keyframes().len(), 0);
+    assert_eq!(state.repack()?, 0);
+
+    state.set_keyframe_policy(KeyframePolicy::new(4, 0));
+    assert_eq!(state.repack()?, 3);
+    assert_eq!(keyframed(&state), vec![4, 8, 10]);
+    assert_eq!(state.repack()?, 0);
+    assert_eq!(
+        state.operations()?.last().map(|operation| operation.kind),
+        Some(OperationKind::Repack)
+    );
+
+    let commits = state.commits().to_vec();
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded, state);
+    assert_eq!(reloaded.verify(), Vec::new());
+    assert_eq!(reloaded.version(&commits[9])?, version(9));
+    assert_eq!(reloaded.version(&commits[10])?, b"feature build".to_vec());
+    Ok(())
+}
+
+#[test]
+fn test_version_starts_from_the_nearest_keyframe() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("nearest.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    state.set_keyframe_policy(KeyframePolicy::new(2, 0));
+    for index in 0..4 {
+        state.commit_blob(&version(index), &author, "Commit")?;
+    }
+    let mut value = serde_json::to_value(&state)?;
+    for keyframe in value["keyframes"].as_array_mut().expect("keyframes").iter_mut() {
+        keyframe[1] = serde_json::to_value(compress(b"firmware build 9999")?)?;
+    }
+    let tampered: OFVRState = serde_json::from_value(value)?;
+    let commits = tampered.commits().to_vec();
+    assert_eq!(tampered.version(&commits[1])?, version(1));
+    assert_eq!(tampered.version(&commits[2])?, b"firmware build 9999".to_vec());
+    assert_eq!(tampered.version(&commits[3])?, b"firmware build 9993".to_vec());
+    assert_eq!(
+        tampered
+            .verify()
+            .iter()
+            .map(|problem| (problem.index, problem.kind))
+            .collect::<Vec<_>>(),
+        vec![(2, ProblemKind::KeyframeMismatch)]
+    );
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use iocore_test::{path_to_test_file, seq_bytes};
+use ofvr::errors::{Error, Result};
+use ofvr::lock::StateLock;
+use ofvr::models::author::Author;
+use ofvr::state::OFVRState;
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+#[test]
+fn test_lock_times_out_while_held() -> Result<()> {
+    let path = path_to_test_file!("locked.ofvr");
+    let mut state = OFVRState::empty(&path, &author())?;
+    state.set_lock_timeout(Duration::from_millis(50));
+    let lock = state.lock()?;
+    assert_eq!(lock.path(), StateLock::lock_path(&path));
+
+    let error = state.commit_blob(b"blocked", &author(), "Commit").err().expect("error");
+    assert_eq!(error.variant(), "LockError");
+    assert!(!path.exists());
+
+    drop(lock);
+    state.commit_blob(b"unblocked", &author(), "Commit")?;
+    assert_eq!(OFVRState::from_path(&path)?.latest_version()?, b"unblocked".to_vec());
+    Ok(())
+}
+
+#[test]
+fn test_concurrent_commits_are_not_lost() -> Result<()> {
+    let path = path_to_test_file!("concurrent.ofvr");
+    OFVRState::empty(&path, &author())?.store()?;
+    let handles = (0..4)
+        .map(|worker| {
+            let path = path.clone();
+            std::thread::spawn(move || -> Result<()> {
+                let mut state = OFVRState::from_path(&path)?;
+                for index in 0..8 {
+                    let data = seq_bytes(64 + worker * 8 + index);
+                    state.commit_blob(&data, &author(), &format!("{}:{}", worker, index))?;
+                }
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().map_err(|_| Error::StateError("worker panicked".to_string()))??;
+    }
+    let state = OFVRState::from_path(&path)?;
+    assert_eq!(state.commits().len(), 32);
+    for (index, commit) in state.commits().iter().enumerate() {
+        let worker_index = commit.data(&state)?.message();
+        let (worker, local) = worker_index.split_once(':').expect("message");
+        let expected = 64 + worker.parse::<usize>().unwrap() * 8 + local.parse::<usize>().unwrap();
+        assert_eq!(state.version_at(index)?, seq_bytes(expected));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_store_replaces_state_file_atomically() -> Result<()> {
+    let path = path_to_test_file!("atomic.ofvr");
+    let mut state = OFVRState::empty(&path, &author())?;
+    state.commit_blob(b"first", &author(), "Commit")?;
+    state.compact()?;
+    let parent = path.parent().expect("parent");
+    let leftovers = std::fs::read_dir(parent.to_path_buf())?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(".tmp"))
+        .collect::<Vec<String>>();
+    assert_eq!(leftovers, Vec::<String>::new());
+    assert_eq!(OFVRState::from_path(&path)?, state);
+    Ok(())
+}
+
+#[test]
+fn test_state_file_replaced_with_same_length_is_reloaded() -> Result<()> {
+    let path = path_to_test_file!("replaced.ofvr");
+    let mut state = OFVRState::empty(&path, &author())?;
+    let commit = state.commit_blob(b"first", &author(), "Commit")?;
+    let before = path.read_bytes()?;
+
+    let mut ours = OFVRState::from_path(&path)?;
+    ours.add_tag("v1", &commit, None, None)?;
+    let ours_length = path.read_bytes()?.len();
+
+    // another process appends a record of the same length to the
+    // journal as it was before
+    path.write(&before)?;
+    let mut theirs = OFVRState::from_path(&path)?;
+    theirs.add_tag("v2", &commit, None, None)?;
+    assert_eq!(path.read_bytes()?.len(), ours_length);
+
+    ours.add_tag("v3", &commit, None, None)?;
+    let tags = ours.tags().keys().cloned().collect::<Vec<String>>();
+    assert_eq!(tags, vec!["v2".to_string(), "v3".to_string()]);
+    assert_eq!(OFVRState::from_path(&path)?, ours);
+
+    // as does storing the whole state
+    path.write(&before)?;
+    let mut theirs = OFVRState::from_path(&path)?;
+    theirs.add_tag("v4", &commit, None, None)?;
+    ours.store()?;
+    let tags = OFVRState::from_path(&path)?.tags().keys().cloned().collect::<Vec<String>>();
+    assert_eq!(tags, vec!["v4".to_string()]);
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use iocore_test::{path_to_test_file, seq_bytes};
+use ofvr::errors::Result;
+use ofvr::models::author::Author;
+use ofvr::state::OFVRState;
+use ofvr::{Merge, Side};
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+#[test]
+fn test_merge_combines_changes_to_distinct_ranges() -> Result<()> {
+    let ancestor = seq_bytes(64);
+    let mut ours = ancestor.clone();
+    ours[4..8].copy_from_slice(b"ours");
+    let mut theirs = ancestor.clone();
+    theirs[40..46].copy_from_slice(b"theirs");
+    theirs.extend(b"appended");
+
+    let merge = Merge::new(&ancestor, &ours, &theirs)?;
+    assert!(merge.conflicts().is_empty());
+    assert!(merge.is_resolved());
+
+    let mut expected = ours.clone();
+    expected[40..46].copy_from_slice(b"theirs");
+    expected.extend(b"appended");
+    assert_eq!(merge.result()?, expected);
+
+    let merge = Merge::new(&ancestor, &ours, &ours)?;
+    assert_eq!(merge.result()?, ours);
+    Ok(())
+}
+
+#[test]
+fn test_merge_reports_overlapping_changes_as_conflicts() -> Result<()> {
+    let ancestor = b"0123456789abcdefghij".to_vec();
+    let ours = b"01OO456789abcdEFGhij".to_vec();
+    let theirs = b"01TT456789abcdeFXhijklmn".to_vec();
+
+    let mut merge = Merge::new(&ancestor, &ours, &theirs)?;
+    let ranges = merge
+        .conflicts()
+        .iter()
+        .map(|conflict| (conflict.offset, conflict.end))
+        .collect::<Vec<(usize, usize)>>();
+    assert_eq!(ranges, vec![(2, 4), (16, 17)]);
+    let conflict = &merge.conflicts()[0];
+    assert_eq!(
+        (
+            conflict.ancestor.as_slice(),
+            conflict.ours.as_slice(),
+            conflict.theirs.as_slice()
+        ),
+        (&b"23"[..], &b"OO"[..], &b"TT"[..])
+    );
+    assert_eq!(merge.result().err().expect("error").variant(), "MergeError");
+
+    merge.resolve(2, Side::Theirs)?;
+    assert!(!merge.is_resolved());
+    assert_eq!(merge.resolve(3, Side::Ours).err().expect("error").variant(), "MergeError");
+    merge.resolve_all(Side::Ours);
+    assert!(merge.is_resolved());
+    assert_eq!(merge.result()?, b"01TT456789abcdEFGhijklmn".to_vec());
+    Ok(())
+}
+
+#[test]
+fn test_merge_conflicts_past_the_end_of_a_version() -> Result<()> {
+    let ancestor = b"0123456789".to_vec();
+    let ours = b"01234".to_vec();
+    let theirs = b"01234567X9".to_vec();
+
+    // ours truncates and theirs edits past the end of ours
+    let mut merge = Merge::new(&ancestor, &ours, &theirs)?;
+    assert_eq!(merge.conflicts().len(), 1);
+    let conflict = merge.conflicts()[0].clone();
+    assert_eq!((conflict.offset, conflict.end), (5, 10));
+    assert_eq!(conflict.ancestor, b"56789".to_vec());
+    assert_eq!(conflict.ours, Vec::<u8>::new());
+    assert_eq!(conflict.theirs, b"567X9".to_vec());
+    merge.resolve_all(Side::Ours);
+    assert_eq!(merge.result()?, ours);
+    merge.resolve(5, Side::Theirs)?;
+    assert_eq!(merge.result()?, theirs);
+
+    let mut merge = Merge::new(&ancestor, &theirs, &ours)?;
+    assert_eq!(merge.conflicts()[0].offset, 5);
+    merge.resolve_all(Side::Theirs);
+    assert_eq!(merge.result()?, ours);
+
+    // truncating and appending past the end of the ancestor conflict
+    let merge = Merge::new(&ancestor, &ours, b"0123456789ab")?;
+    let conflict = &merge.conflicts()[0];
+    assert_eq!((conflict.offset, conflict.end), (5, 12));
+
+    // a truncation merges with changes before it
+    let merge = Merge::new(&ancestor, &ours, b"X123456789")?;
+    assert!(merge.conflicts().is_empty());
+    assert_eq!(merge.result()?, b"X1234".to_vec());
+    Ok(())
+}
+
+#[test]
+fn test_commit_merge_of_diverged_branches() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("diverged.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    let base = state.commit_blob(b"header:v1|body:plain|footer", &author, "base")?;
+    state.create_branch("feature", &base)?;
+    let ours = state.commit_blob(b"HEADER:v1|body:plain|footer", &author, "ours")?;
+    let theirs =
+        state.commit_blob_on("feature", b"header:v1|body:fancy|footer", &author, "theirs")?;
+
+    assert_eq!(state.merge_base(&ours, &theirs)?, Some(base.clone()));
+    let merge = state.merge(&theirs)?;
+    assert!(merge.is_resolved());
+    let merged = merge.result()?;
+    assert_eq!(merged, b"HEADER:v1|body:fancy|footer".to_vec());
+
+    let commit = state.commit_merge(&theirs, &merged, &author, "Merge feature")?;
+    assert_eq!(commit.data(&state)?.parents(), vec![ours.id.clone(), theirs.id.clone()]);
+    assert_eq!(state.latest_version()?, merged);
+    assert_eq!(state.version(&ours)?, b"HEADER:v1|body:plain|footer".to_vec());
+    assert_eq!(state.history("main")?.len(), 4);
+    assert_eq!(state.merge_base(&commit, &theirs)?, Some(theirs.clone()));
+
+    let error = state.commit_merge(&theirs, &merged, &author, "Again").err().expect("error");
+    assert_eq!(error.variant(), "MergeError");
+
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded, state);
+    assert_eq!(reloaded.verify(), Vec::new());
+    Ok(())
+}
+
+#[test]
+fn test_commit_merge_with_resolved_file() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("resolved.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    let base = state.commit_blob(b"value=1", &author, "base")?;
+    state.create_branch("other", &base)?;
+    state.commit_blob(b"value=2", &author, "ours")?;
+    let theirs = state.commit_blob_on("other", b"value=3", &author, "theirs")?;
+
+    let merge = state.merge(&theirs)?;
+    assert_eq!(merge.conflicts().len(), 1);
+    assert_eq!(merge.conflicts()[0].offset, 6);
+
+    let commit = state.commit_merge(&theirs, b"value=5", &author, "Merge other")?;
+    assert_eq!(state.version(&commit)?, b"value=5".to_vec());
+    assert_eq!(state.resolve("HEAD~1")?.data(&state)?.message(), "ours");
+    Ok(())
+}
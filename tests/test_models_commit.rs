@@ -1,10 +1,10 @@
-use bt_diff::{AxisBoundary, Diff};
 use iocore::Path;
 use iocore_test::{path_to_test_file, seq_bytes};
 use ofvr::errors::Result;
 use ofvr::models::author::Author;
 use ofvr::models::commit::Commit;
 use ofvr::models::commit_data::CommitData;
+use ofvr::models::delta::Delta;
 use ofvr::state::OFVRState;
 
 fn author() -> Author {
@@ -22,16 +22,16 @@ fn test_commit() -> Result<()> {
         yhre: u16::MAX,
         nano: u32::MAX,
     };
-    let diff = Diff::new(AxisBoundary::default());
+    let delta = Delta::default();
     let path = Path::new(file!());
 
     let author = author();
-    let state_path = Path::new(file!()).with_extension(".state");
+    let state_path = path_to_test_file!("commit.state");
     let mut state = OFVRState::empty(&state_path, &author)?;
 
     assert!(state.commits().is_empty());
 
-    let commit_data = CommitData::new(&data, diff, author.id(), "test_commit.-", &path)?;
+    let commit_data = CommitData::new(&data, delta, author.id(), "test_commit.-", &path)?;
     let commit = Commit::new(commit_data, &state)?;
 
     assert_eq!(state.commits().len(), 0);
@@ -41,9 +41,9 @@ fn test_commit() -> Result<()> {
     assert!(state.first_commit().is_some());
     assert_eq!(state.latest_commit(), state.first_commit());
 
-    let diff = Diff::new(AxisBoundary::default());
+    let delta = Delta::default();
     let path = Path::new(file!());
-    let commit_data = CommitData::new(&data, diff, author.id(), "test_commit.--", &path)?;
+    let commit_data = CommitData::new(&data, delta, author.id(), "test_commit.--", &path)?;
     let commit = Commit::new(commit_data, &state)?;
 
     state.add_commit(commit)?;
@@ -56,12 +56,12 @@ fn test_commit() -> Result<()> {
 #[test]
 fn test_commit_now() -> Result<()> {
     let author = author();
-    let state_path = Path::new(file!()).with_extension(".state");
+    let state_path = path_to_test_file!("commit.state");
     let mut state = OFVRState::empty(&state_path, &author)?;
 
     assert!(state.commits().is_empty());
     let commit = Commit::now(
-        Diff::new(AxisBoundary::default()),
+        Delta::default(),
         author.id(),
         "test",
         &Path::new(file!()),
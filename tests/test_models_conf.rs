@@ -1,7 +1,9 @@
 use iocore_test::path_to_test_file;
 use ofvr::errors::Error;
 use ofvr::models::author::Author;
+use ofvr::keyframe::KeyframePolicy;
 use ofvr::models::conf::Conf;
+use ofvr::models::legacy::UnkeyedConf;
 use ofvr::traits::{FileSystemBytes, PlainBytes};
 
 #[test]
@@ -36,3 +38,19 @@ fn test_conf_save_and_load() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_conf_keyframes() -> Result<(), Error> {
+    let author = Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com");
+    let mut conf = Conf::new(author.clone());
+    assert_eq!(conf.keyframes(), KeyframePolicy::default());
+    conf.set_keyframes(KeyframePolicy::new(16, 4096));
+    let path = path_to_test_file!("keyframes.conf");
+    conf.save_to_file(&path)?;
+    assert_eq!(Conf::load_from_file(&path)?.keyframes(), KeyframePolicy::new(16, 4096));
+
+    // configurations written before keyframes get the default policy
+    let legacy = UnkeyedConf { author }.to_plain_bytes();
+    assert_eq!(Conf::from_plain_bytes(&legacy)?.keyframes(), KeyframePolicy::default());
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use iocore_test::{path_to_test_file, seq_bytes};
+use ofvr::errors::Result;
+use ofvr::models::author::Author;
+use ofvr::models::delta::{Delta, Hunk, HUNK_GAP};
+use ofvr::state::OFVRState;
+use ofvr::traits::PlainBytes;
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+#[test]
+fn test_delta_new_and_apply() {
+    let anterior = seq_bytes(100);
+    let mut current = anterior.clone();
+    current[10] = 0xFF;
+    current[11] = 0xFE;
+    current[80] = 0xFD;
+
+    let delta = Delta::new(&anterior, &current);
+    assert_eq!(delta.length(), 100);
+    assert_eq!(delta.hunks(), &[Hunk::new(10, &[0xFF, 0xFE]), Hunk::new(80, &[0xFD])]);
+    assert_eq!(delta.apply(&anterior), current);
+}
+
+#[test]
+fn test_delta_folds_short_gaps() {
+    let anterior = vec![0u8; 64];
+    let mut current = anterior.clone();
+    current[0] = 1;
+    current[HUNK_GAP] = 1;
+
+    let delta = Delta::new(&anterior, &current);
+    assert_eq!(delta.hunks().len(), 1);
+    assert_eq!(delta.size(), HUNK_GAP + 1);
+    assert_eq!(delta.apply(&anterior), current);
+}
+
+#[test]
+fn test_delta_grow_and_truncate() {
+    let anterior = seq_bytes(10);
+    let grown = seq_bytes(20);
+    let delta = Delta::new(&anterior, &grown);
+    assert_eq!(delta.size(), 10);
+    assert_eq!(delta.apply(&anterior), grown);
+
+    let truncated = seq_bytes(4);
+    let delta = Delta::new(&anterior, &truncated);
+    assert_eq!(delta.size(), 0);
+    assert_eq!(delta.apply(&anterior), truncated);
+
+    assert_eq!(Delta::new(&[], &anterior).apply(&[]), anterior);
+}
+
+#[test]
+fn test_state_size_grows_linearly_with_change_size() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("growth.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    let mut data = seq_bytes(u16::MAX.into());
+    state.commit_blob(&data, &author, "base")?;
+
+    for change in [16usize, 256, 4096] {
+        let before = state.to_bytes().len();
+        for round in 0..8 {
+            let offset = (round * 7919) % (data.len() - change);
+            for byte in data[offset..offset + change].iter_mut() {
+                *byte = byte.wrapping_add(1);
+            }
+            state.commit_blob(&data, &author, "change")?;
+        }
+        let growth = (state.to_bytes().len() - before) / 8;
+        assert!(growth >= change, "{} bytes per commit for {} changed bytes", growth, change);
+        // date, message, path, author, parent id, digest and object of
+        // each commit
+        assert!(
+            growth <= change + 480,
+            "{} bytes per commit for {} changed bytes",
+            growth,
+            change
+        );
+    }
+    assert_eq!(state.latest_version()?, data);
+    Ok(())
+}
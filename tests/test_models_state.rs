@@ -9,7 +9,7 @@ use ofvr::traits::PlainBytes;
 fn test_state() -> Result<()> {
     let author = Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com");
     let path = path_to_test_file!("state.ofvr");
-    let state = OFVRState::empty(&path, &author)?;
+    let mut state = OFVRState::empty(&path, &author)?;
     state.store()?;
     let mut state = OFVRState::from_path(&path)?;
 
@@ -65,15 +65,13 @@ fn test_state_commit_blob() -> Result<()> {
 #[test]
 fn test_state_from_path() -> Result<()> {
     let author = load_author();
-    let path = Path::new(file!())
-        .canonicalize()?
-        .with_filename("test_state_from_path")
-        .with_extension("ofvrf")
-        .hidden();
-    let state = OFVRState::empty(&path, &author)?;
+    let path = path_to_test_file!("test_state_from_path.ofvrf");
+    let mut state = OFVRState::empty(&path, &author)?;
     state.store()?;
 
     assert_eq!(state, OFVRState::from_path(&path)?);
+    let error = OFVRState::empty(&path, &author).err().expect("error");
+    assert_eq!(error.variant(), "StateError");
     Ok(())
 }
 
@@ -108,3 +106,58 @@ fn test_state_commit() -> Result<()> {
     assert_eq!(state.commits().len(), 1);
     Ok(())
 }
+
+#[test]
+fn test_state_checkout() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("state.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    let first = state.commit_blob(&[0, 1, 2, 3, 4, 5, 6], &author, "Commit 1")?;
+    let latest = state.commit_blob(&[6, 5, 4, 3], &author, "Commit 2")?;
+
+    assert_eq!(state.version(&first)?, vec![0, 1, 2, 3, 4, 5, 6]);
+    assert_eq!(state.version(&latest)?, vec![6, 5, 4, 3]);
+
+    let target = path_to_test_file!("state.data");
+    target.write(&[6, 5, 4, 3])?;
+    state.checkout(&first, &target, false)?;
+    assert_eq!(target.read_bytes()?, vec![0, 1, 2, 3, 4, 5, 6]);
+
+    target.write(&[0xFF])?;
+    assert_eq!(
+        state.checkout(&latest, &target, false).err().expect("error").variant(),
+        "CheckoutError"
+    );
+    assert_eq!(target.read_bytes()?, vec![0xFF]);
+
+    state.checkout(&latest, &target, true)?;
+    assert_eq!(target.read_bytes()?, vec![6, 5, 4, 3]);
+    Ok(())
+}
+
+#[test]
+fn test_state_migrate_legacy_diff_commits() -> Result<()> {
+    let legacy = Path::new(file!()).with_filename("legacy-diff.ofvr");
+    let path = path_to_test_file!("legacy.ofvr");
+    path.write(&legacy.read_bytes()?)?;
+
+    let first = seq_bytes(100);
+    let mut second = first.clone();
+    second[10..14].copy_from_slice(b"OFVR");
+    let mut third = second[..60].to_vec();
+    third.extend(b"tail");
+
+    let mut state = OFVRState::from_path(&path)?;
+    assert_eq!(state.commits().len(), 3);
+    assert_eq!(state.version_at(0)?, first);
+    assert_eq!(state.version_at(1)?, second);
+    assert_eq!(state.version_at(2)?, third);
+    assert_eq!(state.latest_commit().expect("latest").data(&state)?.message(), "third version");
+
+    state.store()?;
+    let mut stored = OFVRState::from_path(&path)?;
+    assert_eq!(stored.migrate()?, 0);
+    assert_eq!(stored, state);
+    assert!(path.read_bytes()?.len() < legacy.read_bytes()?.len() / 4);
+    Ok(())
+}
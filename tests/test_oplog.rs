@@ -0,0 +1,182 @@
+use iocore_test::path_to_test_file;
+use ofvr::errors::Result;
+use ofvr::models::author::Author;
+use ofvr::oplog::oplog_path;
+use ofvr::state::OFVRState;
+use ofvr::OperationKind;
+
+mod common;
+use common::empty;
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+fn kinds(state: &OFVRState) -> Result<Vec<OperationKind>> {
+    Ok(state.operations()?.iter().map(|operation| operation.kind).collect())
+}
+
+#[test]
+fn test_mutating_operations_are_logged() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("logged.ofvr");
+    let mut state = empty(&path, &author)?;
+    let first = state.commit_blob(b"version 1", &author, "Commit 1")?;
+    state.create_branch("feature", &first)?;
+    state.add_tag("v1", &first, None, None)?;
+    state.commit_blob(b"version 2", &author, "Commit 2")?;
+    state.delete_tag("v1")?;
+    state.switch_branch("feature")?;
+    assert_eq!(
+        kinds(&state)?,
+        vec![
+            OperationKind::Commit,
+            OperationKind::BranchCreate,
+            OperationKind::TagAdd,
+            OperationKind::Commit,
+            OperationKind::TagDelete,
+            OperationKind::BranchSwitch,
+        ]
+    );
+
+    let operations = state.operations()?;
+    assert_eq!(operations[0].length, 0);
+    assert_eq!(operations[0].head, None);
+    assert_eq!(operations[0].author, Some(author.clone()));
+    assert_eq!(operations[3].head, Some(first.id.to_string()));
+    assert_eq!(operations[3].commits, 1);
+    assert_eq!(operations[5].branch, "main");
+    assert!(operations.iter().all(|operation| operation.backup.is_none()));
+    assert!(operations.windows(2).all(|pair| pair[0].length < pair[1].length));
+
+    state.amend(Some("Commit 1, amended"), None)?;
+    let operations = OFVRState::from_path(&path)?.operations()?;
+    assert_eq!(operations.len(), 7);
+    assert_eq!(operations[6].kind, OperationKind::Amend);
+    assert!(operations[6].backup.is_some());
+    Ok(())
+}
+
+#[test]
+fn test_undo_restores_earlier_states() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("undo.ofvr");
+    let mut state = empty(&path, &author)?;
+    state.commit_blob(b"version 1", &author, "Commit 1")?;
+    let second = state.commit_blob(b"version 2", &author, "Commit 2")?;
+    state.add_tag("v2", &second, None, None)?;
+    state.commit_blob(b"version 3", &author, "Commit 3")?;
+
+    // undoing appended operations truncates the journal
+    let before = path.read_bytes()?;
+    let backup = state.undo(2)?;
+    assert_eq!(backup.read_bytes()?, before);
+    assert_eq!(state.commits().len(), 2);
+    assert_eq!(state.latest_version()?, b"version 2".to_vec());
+    assert!(state.tags().is_empty());
+    assert_eq!(OFVRState::from_path(&path)?, state);
+
+    // undoing the undo restores its backup
+    let last = state.operations()?.len() - 1;
+    assert_eq!(state.operations()?[last].kind, OperationKind::Undo);
+    state.undo(last)?;
+    assert_eq!(state.commits().len(), 3);
+    assert_eq!(state.latest_version()?, b"version 3".to_vec());
+    assert_eq!(state.resolve("v2")?, second);
+
+    let error = state.undo(0).err().expect("error");
+    assert_eq!(error.variant(), "StateError");
+    let error = state.undo(100).err().expect("error");
+    assert_eq!(error.variant(), "StateError");
+    Ok(())
+}
+
+#[test]
+fn test_undo_across_history_rewriting() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("undo_rewrite.ofvr");
+    let mut state = empty(&path, &author)?;
+    for index in 1..=3 {
+        state.commit_blob(format!("version {}", index).as_bytes(), &author, "Commit")?;
+    }
+    let head = state.latest_commit().expect("head");
+    state.reset(&state.resolve("r1")?)?;
+    state.commit_blob(b"version 4", &author, "Commit 4")?;
+    state.switch_branch("main")?;
+    assert_eq!(state.commits().len(), 2);
+
+    // the commit after the reset is the prefix of the current journal
+    state.undo(4)?;
+    assert_eq!(state.commits().len(), 1);
+    assert_eq!(state.latest_version()?, b"version 1".to_vec());
+
+    // the reset is undone from its backup
+    state.undo(3)?;
+    assert_eq!(state.commits().len(), 3);
+    assert_eq!(state.latest_commit(), Some(head));
+
+    // the second commit is a prefix of the backup of the reset
+    state.undo(1)?;
+    assert_eq!(state.commits().len(), 1);
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded, state);
+    assert_eq!(reloaded.verify(), Vec::new());
+    assert_eq!(
+        kinds(&reloaded)?.iter().filter(|kind| **kind == OperationKind::Undo).count(),
+        3
+    );
+    Ok(())
+}
+
+#[test]
+fn test_compaction_keeps_a_backup() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("undo_compact.ofvr");
+    let mut state = empty(&path, &author)?;
+    for index in 1..=16 {
+        state.commit_blob(format!("version {}", index).as_bytes(), &author, "Commit")?;
+    }
+    let journal = path.read_bytes()?;
+    let (before, after) = state.compact()?;
+    assert_eq!(before, journal.len() as u64);
+    assert!(after < before);
+
+    let operations = state.operations()?;
+    let compaction = operations.last().expect("compaction");
+    assert_eq!(compaction.kind, OperationKind::Compact);
+    assert!(compaction.replaced);
+    let backup = path.with_filename(compaction.backup.clone().expect("backup"));
+    assert_eq!(backup.read_bytes()?, journal);
+
+    // the journal before the compaction is restored from its backup
+    state.commit_blob(b"version 17", &author, "Commit")?;
+    state.undo(operations.len() - 2)?;
+    assert_eq!(state.commits().len(), 15);
+    assert_eq!(OFVRState::from_path(&path)?.latest_version()?, b"version 15".to_vec());
+    Ok(())
+}
+
+#[test]
+fn test_operation_log_damage_is_not_skipped() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("oplog_damage.ofvr");
+    let mut state = empty(&path, &author)?;
+    state.commit_blob(b"version 1", &author, "Commit 1")?;
+    state.commit_blob(b"version 2", &author, "Commit 2")?;
+    let oplog = oplog_path(&path);
+    let logged = oplog.read_bytes()?;
+
+    // the remains of an interrupted append are ignored and overwritten
+    oplog.write(&[&logged[..], b"{\"kind\":\"com"].concat())?;
+    assert_eq!(state.operations()?.len(), 2);
+    state.commit_blob(b"version 3", &author, "Commit 3")?;
+    assert_eq!(kinds(&state)?, vec![OperationKind::Commit; 3]);
+
+    // a damaged line before the last one shifts later operations
+    let mut damaged = oplog.read_bytes()?;
+    damaged[1] = b'#';
+    oplog.write(&damaged)?;
+    assert_eq!(state.operations().err().expect("error").variant(), "StateError");
+    assert_eq!(state.undo(2).err().expect("error").variant(), "StateError");
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use iocore::Path;
+use iocore_test::path_to_test_file;
+use ofvr::errors::Result;
+use ofvr::models::author::Author;
+use ofvr::models::commit::Commit;
+use ofvr::models::commit_data::CommitData;
+use ofvr::models::delta::Delta;
+use ofvr::query::LogQuery;
+use ofvr::revision::{parse_date, parse_until};
+use ofvr::state::OFVRState;
+
+fn date(yhre: u16, mnat: u8, tag: u8) -> t16::Data {
+    t16::Data {
+        mnat,
+        min: 0,
+        sec: 0,
+        stun: 12,
+        tag,
+        yhre,
+        nano: 0,
+    }
+}
+
+fn state() -> Result<OFVRState> {
+    let alice = Author::new("Alice Doe", "alice@example.com");
+    let bob = Author::new("Bob Roe", "bob@builds.example.com");
+    let path = path_to_test_file!("query.ofvr");
+    let mut state = OFVRState::empty(&path, &alice)?;
+    state.add_author(&bob)?;
+    for (index, (author, date, message)) in [
+        (&alice, date(2026, 1, 1), "initial firmware"),
+        (&bob, date(2026, 1, 2), "nightly build"),
+        (&alice, date(2026, 1, 3), "fix bootloader"),
+        (&bob, date(2026, 1, 4), "nightly build"),
+    ]
+    .iter()
+    .enumerate()
+    {
+        let delta = Delta::new(&[], &[index as u8; 4]);
+        let commit_data = CommitData::new(date, delta, author.id(), message, &Path::new(file!()))?;
+        state.add_commit(Commit::new(commit_data, &state)?)?;
+    }
+    Ok(state)
+}
+
+fn messages(state: &OFVRState, query: &LogQuery) -> Result<Vec<String>> {
+    Ok(state
+        .log(query)?
+        .iter()
+        .map(|commit| commit.data(state).map(|data| data.message()))
+        .collect::<Result<Vec<String>>>()?)
+}
+
+#[test]
+fn test_log_query_default_lists_every_commit() -> Result<()> {
+    let state = state()?;
+    assert_eq!(state.log(&LogQuery::new())?, state.commits().to_vec());
+    Ok(())
+}
+
+#[test]
+fn test_log_query_max_count_and_reverse() -> Result<()> {
+    let state = state()?;
+    let query = LogQuery {
+        max_count: Some(2),
+        ..LogQuery::new()
+    };
+    assert_eq!(messages(&state, &query)?, vec!["fix bootloader", "nightly build"]);
+
+    let query = LogQuery {
+        max_count: Some(3),
+        reverse: true,
+        ..LogQuery::new()
+    };
+    assert_eq!(
+        messages(&state, &query)?,
+        vec!["nightly build", "fix bootloader", "nightly build"]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_log_query_filters() -> Result<()> {
+    let state = state()?;
+    let query = LogQuery {
+        author: Some(String::from("BUILDS.example")),
+        ..LogQuery::new()
+    };
+    assert_eq!(messages(&state, &query)?, vec!["nightly build", "nightly build"]);
+
+    let query = LogQuery {
+        author: Some(String::from("alice")),
+        ..LogQuery::new()
+    };
+    assert_eq!(messages(&state, &query)?, vec!["initial firmware", "fix bootloader"]);
+
+    let query = LogQuery {
+        since: Some(parse_date("2026-01-02")?),
+        until: Some(parse_date("2026-01-03 23:59:59")?),
+        ..LogQuery::new()
+    };
+    assert_eq!(messages(&state, &query)?, vec!["nightly build", "fix bootloader"]);
+
+    // a date without a time includes the whole day it names
+    let query = LogQuery {
+        since: Some(parse_date("2026-01-02")?),
+        until: Some(parse_until("2026-01-03")?),
+        ..LogQuery::new()
+    };
+    assert_eq!(messages(&state, &query)?, vec!["nightly build", "fix bootloader"]);
+    assert_eq!(parse_until("2026-01-03 12:00:00")?, parse_date("2026-01-03 12:00:00")?);
+
+    let query = LogQuery {
+        grep: Some(String::from("boot")),
+        ..LogQuery::new()
+    };
+    assert_eq!(messages(&state, &query)?, vec!["fix bootloader"]);
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use iocore_test::path_to_test_file;
+use ofvr::errors::Result;
+use ofvr::models::author::Author;
+use ofvr::state::OFVRState;
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+#[test]
+fn test_revert_restores_the_parent_version() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("revert.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    state.commit_blob(b"good firmware", &author, "Commit 1")?;
+    let bad = state.commit_blob(b"bad firmware!", &author, "Commit 2")?;
+
+    let commit = state.revert(&bad, &author)?;
+    assert_eq!(state.commits().len(), 3);
+    assert_eq!(state.latest_version()?, b"good firmware".to_vec());
+    assert_eq!(commit.data(&state)?.message(), format!("Revert commit {}: Commit 2", bad.id));
+    assert_eq!(commit.data(&state)?.parents(), vec![bad.id.clone()]);
+    assert_eq!(state.version(&bad)?, b"bad firmware!".to_vec());
+
+    let error = state.revert(&bad, &author).err().expect("error");
+    assert_eq!(error.variant(), "CommitError");
+    let error = state.revert(&state.resolve("r1")?, &author).err().expect("error");
+    assert_eq!(error.variant(), "CommitError");
+    assert_eq!(OFVRState::from_path(&path)?, state);
+    Ok(())
+}
+
+#[test]
+fn test_revert_to_restores_the_target_version() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("revert_to.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    let first = state.commit_blob(b"version 1", &author, "Commit 1")?;
+    state.commit_blob(b"version 2", &author, "Commit 2")?;
+    state.commit_blob(b"version 3, longer", &author, "Commit 3")?;
+
+    let commit = state.revert_to(&first, &author)?;
+    assert_eq!(state.latest_version()?, b"version 1".to_vec());
+    assert_eq!(
+        commit.data(&state)?.message(),
+        format!("Revert to commit {}: Commit 1", first.id)
+    );
+    assert_eq!(state.history("main")?.len(), 4);
+    assert_eq!(state.revert_to(&first, &author).err().expect("error").variant(), "CommitError");
+    Ok(())
+}
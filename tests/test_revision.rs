@@ -0,0 +1,108 @@
+use iocore::Path;
+use iocore_test::path_to_test_file;
+use ofvr::errors::Result;
+use ofvr::models::author::Author;
+use ofvr::models::commit::Commit;
+use ofvr::models::commit_data::CommitData;
+use ofvr::models::delta::Delta;
+use ofvr::revision::Revision;
+use ofvr::state::OFVRState;
+use ofvr::traits::PlainBytes;
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+fn date(yhre: u16, mnat: u8, tag: u8) -> t16::Data {
+    t16::Data {
+        mnat,
+        min: 0,
+        sec: 0,
+        stun: 12,
+        tag,
+        yhre,
+        nano: 0,
+    }
+}
+
+fn state_with_dated_commits() -> Result<OFVRState> {
+    let author = author();
+    let path = path_to_test_file!("revision.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    for (index, date) in [date(2025, 12, 1), date(2026, 1, 15), date(2026, 2, 1)].iter().enumerate()
+    {
+        let delta = Delta::new(&[], &[index as u8; 8]);
+        let message = format!("commit {}", index + 1);
+        let commit_data = CommitData::new(date, delta, author.id(), &message, &Path::new(file!()))?;
+        state.add_commit(Commit::new(commit_data, &state)?)?;
+    }
+    Ok(state)
+}
+
+#[test]
+fn test_revision_parse() -> Result<()> {
+    assert_eq!(Revision::parse("HEAD")?, Revision::Head(0));
+    assert_eq!(Revision::parse("HEAD~2")?, Revision::Head(2));
+    assert_eq!(Revision::parse("r3")?, Revision::Ordinal(3));
+    assert_eq!(Revision::parse("BEEF")?, Revision::Hex(String::from("beef")));
+    assert_eq!(Revision::parse("@{2026-01-01}")?.to_string(), "@{2026-01-01T00:00:00+00:00}");
+
+    assert_eq!(Revision::parse("r0").err().expect("error").variant(), "RevisionError");
+    assert_eq!(Revision::parse("abc")?, Revision::Name(String::from("abc")));
+    assert_eq!(Revision::parse("release/1.x")?, Revision::Name(String::from("release/1.x")));
+    assert_eq!(Revision::parse("a b").err().expect("error").variant(), "RevisionError");
+    assert_eq!(Revision::parse("-abc").err().expect("error").variant(), "RevisionError");
+    assert_eq!(Revision::parse("HEAD~x").err().expect("error").variant(), "RevisionError");
+    assert_eq!(
+        Revision::parse("@{yesterday}").err().expect("error").variant(),
+        "RevisionError"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_revision_resolve() -> Result<()> {
+    let state = state_with_dated_commits()?;
+    let commits = state.commits().to_vec();
+
+    assert_eq!(state.resolve("HEAD")?, commits[2]);
+    assert_eq!(state.resolve("HEAD~2")?, commits[0]);
+    assert_eq!(state.resolve("r2")?, commits[1]);
+    assert_eq!(state.resolve(&commits[1].id.to_hex()[..8])?, commits[1]);
+    assert_eq!(state.resolve("@{2026-01-01}")?, commits[0]);
+    assert_eq!(state.resolve("@{2026-01-20 00:00:00}")?, commits[1]);
+
+    assert_eq!(state.resolve("HEAD~3").err().expect("error").variant(), "RevisionError");
+    assert_eq!(state.resolve("r4").err().expect("error").variant(), "RevisionError");
+    assert_eq!(state.resolve("@{2025-01-01}").err().expect("error").variant(), "RevisionError");
+    Ok(())
+}
+
+#[test]
+fn test_revision_resolve_ambiguous_or_unknown_prefix() -> Result<()> {
+    // commits are linked to their parent so the same commit can only
+    // appear twice in a state that was not built through `add_commit`
+    let mut value = serde_json::to_value(state_with_dated_commits()?)?;
+    let commits = value["commits"].as_array_mut().expect("commits");
+    commits.push(commits[2].clone());
+    let state: OFVRState = serde_json::from_value(value)?;
+    let latest = state.latest_commit().expect("latest commit");
+
+    let error = state.resolve(&latest.id.to_hex()[..6]).err().expect("error");
+    assert_eq!(error.variant(), "RevisionError");
+    assert!(error.to_string().contains("ambiguous revision"));
+
+    let unknown = state.commits().iter().map(|commit| commit.id.to_hex()).fold(
+        String::from("0000"),
+        |prefix, id| {
+            if id.starts_with(&prefix) {
+                String::from("ffff")
+            } else {
+                prefix
+            }
+        },
+    );
+    let error = state.resolve(&unknown).err().expect("error");
+    assert_eq!(error.to_string(), format!("RevisionErrorunknown revision {}", unknown));
+    Ok(())
+}
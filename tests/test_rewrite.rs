@@ -0,0 +1,148 @@
+use iocore::Path;
+use iocore_test::path_to_test_file;
+use ofvr::errors::Result;
+use ofvr::models::author::Author;
+use ofvr::state::OFVRState;
+
+mod common;
+use common::empty;
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+fn messages(state: &OFVRState) -> Result<Vec<String>> {
+    state
+        .history(&state.current_branch())?
+        .iter()
+        .map(|commit| Ok(commit.data(state)?.message()))
+        .collect()
+}
+
+fn versioned(path: &Path, count: usize) -> Result<OFVRState> {
+    let author = author();
+    let mut state = empty(path, &author)?;
+    for index in 1..=count {
+        state.commit_blob(
+            format!("version {}", index).as_bytes(),
+            &author,
+            &format!("Commit {}", index),
+        )?;
+    }
+    Ok(state)
+}
+
+#[test]
+fn test_amend_replaces_message_and_author() -> Result<()> {
+    let path = path_to_test_file!("amend.ofvr");
+    let mut state = versioned(&path, 2)?;
+    let before = path.read_bytes()?;
+    let head = state.latest_commit().expect("head");
+
+    let (amended, backup) = state.amend(Some("Commit 2, fixed"), None)?;
+    assert_ne!(amended.id, head.id);
+    assert_eq!(amended.data(&state)?.parents(), head.data(&state)?.parents());
+    assert_eq!(amended.data(&state)?.date(), head.data(&state)?.date());
+    assert_eq!(messages(&state)?, vec!["Commit 1", "Commit 2, fixed"]);
+    assert_eq!(state.commits().len(), 2);
+    assert_eq!(state.latest_version()?, b"version 2".to_vec());
+    assert_eq!(backup.read_bytes()?, before);
+    assert_eq!(OFVRState::from_path(&backup)?.latest_commit(), Some(head));
+
+    let reviewer = Author::new("Reviewer", "reviewer@example.com");
+    let (amended, _) = state.amend(None, Some(&reviewer))?;
+    assert_eq!(amended.author(&state)?, reviewer);
+    assert_eq!(
+        state.amend(None, Some(&reviewer)).err().expect("error").variant(),
+        "CommitError"
+    );
+
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded, state);
+    assert_eq!(reloaded.verify(), Vec::new());
+    Ok(())
+}
+
+#[test]
+fn test_reset_drops_commits_after_revision() -> Result<()> {
+    let path = path_to_test_file!("reset.ofvr");
+    let mut state = versioned(&path, 4)?;
+    let second = state.resolve("r2")?;
+    state.add_tag("v3", &state.resolve("r3")?, None, None)?;
+
+    let backup = state.reset(&second)?;
+    assert_eq!(messages(&state)?, vec!["Commit 1", "Commit 2"]);
+    assert_eq!(state.latest_version()?, b"version 2".to_vec());
+    // the tagged commit is kept, the commit after it is dropped
+    assert_eq!(state.commits().len(), 3);
+    assert_eq!(state.version(&state.resolve("v3")?)?, b"version 3".to_vec());
+    assert_eq!(OFVRState::from_path(&backup)?.commits().len(), 4);
+
+    assert_eq!(state.reset(&second).err().expect("error").variant(), "CommitError");
+    let tagged = state.resolve("v3")?;
+    assert_eq!(state.reset(&tagged).err().expect("error").variant(), "CommitError");
+
+    let mut state = OFVRState::from_path(&path)?;
+    state.commit_blob(b"version 5", &author(), "Commit 5")?;
+    assert_eq!(messages(&state)?, vec!["Commit 1", "Commit 2", "Commit 5"]);
+    assert_eq!(state.verify(), Vec::new());
+    Ok(())
+}
+
+#[test]
+fn test_squash_collapses_range_into_one_commit() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("squash.ofvr");
+    let mut state = versioned(&path, 5)?;
+    let first = state.resolve("r1")?;
+    let fourth = state.resolve("r4")?;
+
+    let (squashed, backup) = state.squash(&first, &fourth, &author, "Commits 2 to 4")?;
+    assert_eq!(messages(&state)?, vec!["Commit 1", "Commits 2 to 4", "Commit 5"]);
+    assert_eq!(squashed.data(&state)?.parents(), vec![first.id.clone()]);
+    assert_eq!(state.version(&squashed)?, b"version 4".to_vec());
+    assert_eq!(state.latest_version()?, b"version 5".to_vec());
+    assert_eq!(state.resolve("HEAD~1")?, squashed);
+    assert_eq!(state.commits().len(), 3);
+    assert_eq!(OFVRState::from_path(&backup)?.commits().len(), 5);
+
+    let head = state.latest_commit().expect("head");
+    let (squashed, _) = state.squash(&first, &head, &author, "Everything")?;
+    assert_eq!(messages(&state)?, vec!["Commit 1", "Everything"]);
+    assert_eq!(state.latest_commit(), Some(squashed));
+
+    let error = state.squash(&first, &state.latest_commit().expect("head"), &author, "Nothing");
+    assert_eq!(error.err().expect("error").variant(), "CommitError");
+
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded, state);
+    assert_eq!(reloaded.latest_version()?, b"version 5".to_vec());
+    assert_eq!(reloaded.verify(), Vec::new());
+    Ok(())
+}
+
+#[test]
+fn test_rewriting_keeps_commits_of_other_branches() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("branches.ofvr");
+    let mut state = versioned(&path, 3)?;
+    let second = state.resolve("r2")?;
+    let third = state.resolve("r3")?;
+    state.create_branch("feature", &third)?;
+    state.commit_blob_on("feature", b"feature", &author, "Feature")?;
+
+    state.reset(&second)?;
+    assert_eq!(state.commits().len(), 4);
+    assert_eq!(state.history("feature")?.len(), 4);
+    assert_eq!(state.latest_version()?, b"version 2".to_vec());
+
+    let range = ofvr::revision::parse_range("r1..")?;
+    let (from, to) = (range.0.resolve(&state)?, range.1.resolve(&state)?);
+    assert_eq!((from.clone(), to.clone()), (state.resolve("r1")?, second.clone()));
+    assert_eq!(
+        state.squash(&from, &to, &author, "One").err().expect("error").variant(),
+        "CommitError"
+    );
+    assert_eq!(state.verify(), Vec::new());
+    Ok(())
+}
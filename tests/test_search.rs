@@ -0,0 +1,85 @@
+use iocore_test::path_to_test_file;
+use ofvr::errors::Result;
+use ofvr::models::author::Author;
+use ofvr::state::OFVRState;
+use ofvr::Pattern;
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+#[test]
+fn test_search_finds_commits_changing_occurrences() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("search.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    let mut image = vec![0u8; 64];
+    state.commit_blob(&image, &author, "Blank")?;
+    image[8..12].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+    let added = state.commit_blob(&image, &author, "Magic")?;
+    image[60] = 1;
+    state.commit_blob(&image, &author, "Unrelated")?;
+    image[8..12].copy_from_slice(&[0; 4]);
+    image[32..36].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+    // moving an occurrence keeps their amount
+    state.commit_blob(&image, &author, "Moved")?;
+    image[40..44].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+    let second = state.commit_blob(&image, &author, "Second magic")?;
+    image[32..44].copy_from_slice(&[0; 12]);
+    let removed = state.commit_blob(&image, &author, "No magic")?;
+
+    let hits = state.search(&Pattern::hex("0xDEAD BEEF")?)?;
+    let summary = hits
+        .iter()
+        .map(|hit| {
+            (
+                hit.commit.clone(),
+                hit.before,
+                hit.after,
+                hit.added.clone(),
+                hit.removed.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (added, 0, 1, vec![8], vec![]),
+            (second, 1, 2, vec![40], vec![]),
+            (removed, 2, 0, vec![], vec![32, 40]),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_search_text_and_regex() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("search_text.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    let first = state.commit_blob(b"user=admin", &author, "Commit 1")?;
+    let second = state.commit_blob(b"user=admin password=hunter2", &author, "Commit 2")?;
+    state.commit_blob(b"user=admin password=hunter3", &author, "Commit 3")?;
+    let fourth = state.commit_blob(b"user=admin", &author, "Commit 4")?;
+
+    let hits = state.search(&Pattern::text("hunter2")?)?;
+    assert_eq!(
+        hits.iter().map(|hit| hit.commit.clone()).collect::<Vec<_>>(),
+        vec![second.clone(), state.resolve("r3")?]
+    );
+    assert_eq!(hits[0].added, vec![20]);
+
+    let hits = state.search(&Pattern::regex(r"password=\w+")?)?;
+    assert_eq!(
+        hits.iter().map(|hit| (hit.commit.clone(), hit.after)).collect::<Vec<_>>(),
+        vec![(second, 1), (fourth, 0)]
+    );
+    let hits = state.search(&Pattern::text("user=")?)?;
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].commit, first);
+
+    assert_eq!(Pattern::hex("zz").err().expect("error").variant(), "HexDecodeError");
+    assert_eq!(Pattern::hex("").err().expect("error").variant(), "SearchError");
+    assert_eq!(Pattern::regex("(").err().expect("error").variant(), "SearchError");
+    Ok(())
+}
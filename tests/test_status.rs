@@ -0,0 +1,61 @@
+use iocore_test::path_to_test_file;
+use ofvr::errors::Result;
+use ofvr::models::author::Author;
+use ofvr::state::OFVRState;
+use ofvr::status::{changed_bytes, Status, StatusReport};
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+#[test]
+fn test_changed_bytes() {
+    assert_eq!(changed_bytes(&[1, 2, 3], &[1, 2, 3]), 0);
+    assert_eq!(changed_bytes(&[1, 2, 3], &[1, 0, 3]), 1);
+    assert_eq!(changed_bytes(&[1, 2, 3], &[1, 0, 3, 4, 5]), 3);
+    assert_eq!(changed_bytes(&[1, 2, 3, 4], &[]), 4);
+}
+
+#[test]
+fn test_status() -> Result<()> {
+    let author = author();
+    let from_file = path_to_test_file!("status.data");
+    let state_path = path_to_test_file!("status.ofvr");
+    from_file.write(&[1, 2, 3, 4])?;
+
+    let report = StatusReport::new(&from_file, &state_path, None)?;
+    assert_eq!(report.status, Status::MissingState);
+    assert_eq!(report.status.exit_code(), 4);
+
+    let mut state = OFVRState::empty(&state_path, &author)?;
+    state.store()?;
+    assert_eq!(StatusReport::new(&from_file, &state_path, None)?.status, Status::Untracked);
+
+    state.commit(&from_file, &author, "Commit 1")?;
+    let report = StatusReport::new(&from_file, &state_path, None)?;
+    assert_eq!(report.status, Status::Clean);
+    assert_eq!(report.status.exit_code(), 0);
+    assert_eq!(report.changed_bytes, 0);
+
+    from_file.write(&[1, 2, 0, 4, 5])?;
+    let report = StatusReport::new(&from_file, &state_path, None)?;
+    assert_eq!(report.status, Status::Modified);
+    assert_eq!(report.status.exit_code(), 2);
+    assert_eq!(report.changed_bytes, 2);
+    assert_eq!(report.committed_length, 4);
+    assert_eq!(report.working_length, 5);
+
+    state.commit(&from_file, &author, "Commit 2")?;
+    assert_eq!(StatusReport::new(&from_file, &state_path, None)?.status, Status::Clean);
+    assert_eq!(
+        StatusReport::new(&from_file, &state_path, Some("r1"))?.status,
+        Status::Modified
+    );
+
+    from_file.write(&[1, 0])?;
+    let report = StatusReport::new(&from_file, &state_path, None)?;
+    assert_eq!(report.status, Status::Modified);
+    assert_eq!(report.changed_bytes, 4);
+    assert_eq!((report.committed_length, report.working_length), (5, 2));
+    Ok(())
+}
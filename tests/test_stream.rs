@@ -0,0 +1,266 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use iocore_test::path_to_test_file;
+use ofvr::chunk::{split, ChunkReader};
+use ofvr::errors::Result;
+use ofvr::hash::{Digest, Keccak256};
+use ofvr::models::author::Author;
+use ofvr::pack::{pack_path, ENTRY_OVERHEAD, STREAM_THRESHOLD};
+use ofvr::state::OFVRState;
+use ofvr::status::{changed_bytes, Status, StatusReport};
+use ofvr::{keccak256, Storage};
+
+mod common;
+use common::empty;
+
+/// `Counting` tracks the bytes allocated by the tests of this file
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+/// serializes the tests so that they do not count each other's allocations
+static SERIAL: Mutex<()> = Mutex::new(());
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+        PEAK.fetch_max(current, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// `measure` returns the result of `operation` along with the most
+/// bytes it held at once
+fn measure<T>(operation: impl FnOnce() -> T) -> (T, usize) {
+    let baseline = CURRENT.load(Ordering::SeqCst);
+    PEAK.store(baseline, Ordering::SeqCst);
+    let result = operation();
+    (result, PEAK.load(Ordering::SeqCst) - baseline)
+}
+
+/// most bytes streaming a version in or out may hold at once besides
+/// what it holds per chunk, see [`cap`]
+const MEMORY_CAP: usize = 1 << 20;
+/// most bytes streaming a version may hold per chunk of it, see [`ofvr::pack`]
+const CHUNK_COST: usize = 512;
+/// versions this long are streamed whatever the storage of the state
+const IMAGE_LENGTH: usize = STREAM_THRESHOLD;
+
+/// `cap` returns the most bytes streaming a version of the given
+/// amount of chunks in or out may hold at once
+fn cap(chunks: usize) -> usize {
+    MEMORY_CAP + chunks * CHUNK_COST
+}
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+/// `Image` reads `length` bytes that do not repeat, as firmware images
+/// would, without holding them
+struct Image {
+    length: usize,
+    offset: usize,
+    seed: u64,
+}
+impl Image {
+    fn new(length: usize) -> Image {
+        Image {
+            length,
+            offset: 0,
+            seed: 0x6F66_7672,
+        }
+    }
+}
+impl Read for Image {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let count = buffer.len().min(self.length - self.offset);
+        for byte in buffer[..count].iter_mut() {
+            // xorshift64
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 7;
+            self.seed ^= self.seed << 17;
+            *byte = self.seed as u8;
+        }
+        self.offset += count;
+        Ok(count)
+    }
+}
+
+/// `Hashing` hashes the bytes written into it
+struct Hashing(Keccak256, usize);
+impl Write for Hashing {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.update(bytes);
+        self.1 += bytes.len();
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn digest(reader: impl Read) -> Result<Vec<u8>> {
+    ofvr::keccak256_reader(reader)
+}
+
+#[test]
+fn test_stream_versions_larger_than_the_memory_cap() -> Result<()> {
+    let _serial = SERIAL.lock().unwrap_or_else(|error| error.into_inner());
+    let author = author();
+    let path = path_to_test_file!("stream.ofvr");
+    let image = path_to_test_file!("stream.img");
+    std::io::copy(&mut Image::new(IMAGE_LENGTH), &mut std::fs::File::create(image.to_path_buf())?)?;
+    let mut state = empty(&path, &author)?;
+    assert_eq!(state.storage(), Storage::Deltas);
+    let (commit, peak) = measure(|| state.commit(&image, &author, "Firmware image"));
+    let commit = commit?;
+    let chunks = state.manifests()[&commit.id].len();
+    assert!(cap(chunks) < IMAGE_LENGTH / 8);
+    assert!(peak < cap(chunks), "committing held {} bytes", peak);
+    assert_eq!(state.digest(&commit)?, digest(Image::new(IMAGE_LENGTH))?);
+    assert_eq!(state.pack()?.len(), chunks);
+
+    // the delta from a version streamed into the pack is not computed
+    let (patched, peak) = measure(|| state.commit_blob(b"bootloader", &author, "Patched"));
+    let patched = patched?;
+    assert!(peak < cap(chunks), "committing on top held {} bytes", peak);
+    assert_eq!(state.manifests()[&patched.id].len(), 1);
+
+    let reloaded = OFVRState::from_path(&path)?;
+    let (written, peak) = measure(|| {
+        let mut hashing = Hashing(Keccak256::new(), 0);
+        reloaded
+            .write_version(&commit, &mut hashing)
+            .map(|length| (length, hashing.1, hashing.0.finalize().to_vec()))
+    });
+    let (length, hashed, hash) = written?;
+    assert!(peak < cap(chunks), "writing held {} bytes", peak);
+    assert_eq!((length, hashed), (IMAGE_LENGTH as u64, IMAGE_LENGTH));
+    assert_eq!(hash, reloaded.digest(&commit)?);
+
+    let target = path_to_test_file!("stream.bin");
+    let (length, peak) = measure(|| reloaded.checkout_to(&commit, &target, false));
+    assert!(peak < cap(chunks), "checking out held {} bytes", peak);
+    assert_eq!(length?, IMAGE_LENGTH as u64);
+    assert_eq!(digest(std::fs::File::open(target.to_path_buf())?)?, reloaded.digest(&commit)?);
+    assert_eq!(reloaded.version(&patched)?, b"bootloader".to_vec());
+
+    let mut working =
+        std::fs::OpenOptions::new().read(true).write(true).open(target.to_path_buf())?;
+    let mut original = [0u8; 7];
+    working.seek(SeekFrom::Start(IMAGE_LENGTH as u64 / 2))?;
+    working.read_exact(&mut original)?;
+    working.seek(SeekFrom::Start(IMAGE_LENGTH as u64 / 2))?;
+    working.write_all(b"patched")?;
+    drop(working);
+    let (report, peak) = measure(|| StatusReport::new(&target, &path, Some("r1")));
+    let report = report?;
+    assert!(peak < cap(chunks), "comparing held {} bytes", peak);
+    assert_eq!(report.status, Status::Modified);
+    assert_eq!((report.committed_length, report.working_length), (IMAGE_LENGTH, IMAGE_LENGTH));
+    assert_eq!(report.changed_bytes, changed_bytes(&original, b"patched"));
+    Ok(())
+}
+
+#[test]
+fn test_streamed_versions_match_blobs() -> Result<()> {
+    let _serial = SERIAL.lock().unwrap_or_else(|error| error.into_inner());
+    let author = author();
+    let path = path_to_test_file!("stream_blob.ofvr");
+    let mut state = empty(&path, &author)?;
+    let mut image = Vec::<u8>::new();
+    Image::new(300 << 10).read_to_end(&mut image)?;
+    let chunks = ChunkReader::new(image.as_slice()).collect::<Result<Vec<_>>>()?;
+    assert_eq!(chunks, split(&image));
+
+    let first = state.commit_blob(b"bootloader", &author, "Commit 1")?;
+    let streamed = state.commit_stream(image.as_slice(), &author, "Streamed")?;
+    let blob = state.commit_blob(&image, &author, "Committed again")?;
+    assert_eq!(state.version(&streamed)?, image);
+    assert_eq!(state.objects()[&keccak256(&image)].commit(), streamed.id);
+    assert!(blob.data(&state)?.delta().hunks().is_empty());
+    assert_eq!(state.version(&blob)?, image);
+    assert_eq!(state.delta(&streamed)?.apply(b"bootloader"), image);
+    assert_eq!(state.dedup_stats()?.chunks, chunks.len());
+
+    // the pack survives rewriting the state
+    state.reset(&first)?;
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded.version(&first)?, b"bootloader".to_vec());
+    assert!(reloaded.get_commit(&streamed.id).is_err());
+    assert_eq!(reloaded.pack()?.len(), chunks.len());
+    assert_eq!(reloaded.verify(), Vec::new());
+    Ok(())
+}
+
+#[test]
+fn test_interrupted_pack_appends_are_overwritten() -> Result<()> {
+    let _serial = SERIAL.lock().unwrap_or_else(|error| error.into_inner());
+    let author = author();
+    let path = path_to_test_file!("stream_interrupted.ofvr");
+    let mut state = empty(&path, &author)?;
+    let first = state.commit_stream(Image::new(100 << 10), &author, "Commit 1")?;
+    let length = std::fs::metadata(pack_path(&path).to_path_buf())?.len();
+
+    // the header of a chunk whose bytes never made it to the pack
+    let mut pack = std::fs::OpenOptions::new().append(true).open(pack_path(&path).to_path_buf())?;
+    pack.write_all(&[0xAA; ENTRY_OVERHEAD])?;
+    pack.write_all(b"bootloader")?;
+    drop(pack);
+    assert_eq!(OFVRState::from_path(&path)?.verify(), Vec::new());
+
+    let mut image = Vec::<u8>::new();
+    Image::new(100 << 10).read_to_end(&mut image)?;
+    image.extend(b"patched");
+    let second = state.commit_stream(image.as_slice(), &author, "Commit 2")?;
+    let packed = split(&image[..100 << 10]);
+    let appended = split(&image)
+        .into_iter()
+        .filter(|chunk| !packed.contains(chunk))
+        .map(|chunk| (ENTRY_OVERHEAD + chunk.len()) as u64)
+        .sum::<u64>();
+    assert_eq!(std::fs::metadata(pack_path(&path).to_path_buf())?.len(), length + appended);
+
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded.digest(&first)?, digest(Image::new(100 << 10))?);
+    assert_eq!(reloaded.version(&second)?, image);
+    assert_eq!(reloaded.verify(), Vec::new());
+    Ok(())
+}
+
+#[test]
+fn test_versions_stored_as_deltas_are_shorter_than_the_threshold() -> Result<()> {
+    let _serial = SERIAL.lock().unwrap_or_else(|error| error.into_inner());
+    let author = author();
+    let path = path_to_test_file!("stream_delta.ofvr");
+    let image = path_to_test_file!("stream_delta.img");
+    let length = IMAGE_LENGTH - 1;
+    std::io::copy(&mut Image::new(length), &mut std::fs::File::create(image.to_path_buf())?)?;
+    let mut state = empty(&path, &author)?;
+    let commit = state.commit(&image, &author, "Firmware image")?;
+    assert!(!state.manifests().contains_key(&commit.id));
+
+    // versions stored as deltas are rebuilt in memory before written
+    let reloaded = OFVRState::from_path(&path)?;
+    let (written, peak) = measure(|| {
+        let mut hashing = Hashing(Keccak256::new(), 0);
+        reloaded.write_version(&commit, &mut hashing).map(|length| (length, hashing.1))
+    });
+    assert_eq!(written?, (length as u64, length));
+    assert!(peak >= length, "writing held {} bytes", peak);
+    assert!(peak < 4 * IMAGE_LENGTH, "writing held {} bytes", peak);
+    Ok(())
+}
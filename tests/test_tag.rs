@@ -0,0 +1,73 @@
+use iocore_test::path_to_test_file;
+use ofvr::errors::Result;
+use ofvr::models::author::Author;
+use ofvr::state::OFVRState;
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+#[test]
+fn test_tags_name_commits() -> Result<()> {
+    let author = author();
+    let releaser = Author::new("Release Bot", "release@example.com");
+    let path = path_to_test_file!("tags.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    let first = state.commit_blob(b"firmware 1.4.1", &author, "Commit 1")?;
+    let second = state.commit_blob(b"firmware 1.4.2", &author, "Commit 2")?;
+
+    let lightweight = state.add_tag("v1.4.1", &first, None, None)?;
+    assert_eq!(lightweight.commit(), first.id);
+    assert_eq!(lightweight.message(), None);
+    assert_eq!(lightweight.tagger(&state)?, None);
+
+    let annotated = state.add_tag("v1.4.2", &second, Some("shipped"), Some(&releaser))?;
+    assert_eq!(annotated.message(), Some(String::from("shipped")));
+    assert_eq!(annotated.tagger(&state)?, Some(releaser.clone()));
+    state.add_tag("stable", &second, None, None)?;
+
+    assert_eq!(state.resolve("v1.4.1")?, first);
+    assert_eq!(state.resolve("v1.4.2")?, second);
+    assert_eq!(state.version(&state.resolve("v1.4.1")?)?, b"firmware 1.4.1".to_vec());
+    assert_eq!(
+        state.tags_of(&second).iter().map(|tag| tag.name()).collect::<Vec<String>>(),
+        vec!["stable", "v1.4.2"]
+    );
+    assert!(second.log(&state)?.contains("Tags: stable, v1.4.2"));
+    let third = state.commit_blob(b"firmware 1.5.0", &author, "Commit 3")?;
+    assert!(!third.log(&state)?.contains("Tags"));
+
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded, state);
+    assert_eq!(reloaded.get_tag("v1.4.2")?, annotated);
+    assert_eq!(reloaded.get_author(releaser.id())?, releaser);
+    Ok(())
+}
+
+#[test]
+fn test_tags_are_immutable() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("immutable.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    let first = state.commit_blob(b"first", &author, "Commit 1")?;
+    let second = state.commit_blob(b"second", &author, "Commit 2")?;
+    state.add_tag("v1", &first, None, None)?;
+
+    for name in ["v1", "main", "HEAD", "r1", "cafe", "v1..2", "-v2"] {
+        let error = state.add_tag(name, &second, None, None).err().expect("error");
+        assert_eq!(error.variant(), "TagError", "{}", name);
+    }
+    let error = state.create_branch("v1", &second).err().expect("error");
+    assert_eq!(error.variant(), "BranchError");
+    assert_eq!(state.resolve("v1")?, first);
+
+    assert_eq!(state.delete_tag("v1")?.commit(), first.id);
+    assert_eq!(state.delete_tag("v1").err().expect("error").variant(), "TagError");
+    assert_eq!(state.resolve("v1").err().expect("error").variant(), "RevisionError");
+    state.add_tag("v1", &second, None, None)?;
+
+    let reloaded = OFVRState::from_path(&path)?;
+    assert_eq!(reloaded.resolve("v1")?, second);
+    assert_eq!(reloaded.commits().len(), 2);
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use iocore::Path;
+use iocore_test::{path_to_test_file, seq_bytes};
+use ofvr::errors::Result;
+use ofvr::models::author::Author;
+use ofvr::models::commit::Commit;
+use ofvr::models::commit_data::CommitData;
+use ofvr::models::delta::Delta;
+use ofvr::models::id::ID;
+use ofvr::state::OFVRState;
+use ofvr::traits::PlainBytes;
+use ofvr::{FsckReport, ProblemKind};
+
+fn author() -> Author {
+    Author::new("Gabriel DeMoura", "gabrielteratos@gmail.com")
+}
+
+#[test]
+fn test_verify_clean_state() -> Result<()> {
+    let path = path_to_test_file!("clean.ofvr");
+    let mut state = OFVRState::empty(&path, &author())?;
+    for index in 0..4 {
+        state.commit_blob(&seq_bytes(128 + index), &author(), "Commit")?;
+    }
+    assert_eq!(state.verify(), Vec::new());
+
+    let report = FsckReport::new(&path)?;
+    assert!(report.is_ok());
+    assert_eq!(report.commits, 4);
+    assert_eq!(report.exit_code(), 0);
+    Ok(())
+}
+
+#[test]
+fn test_verify_reports_every_problem_with_its_index() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("corrupted.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    state.commit_blob(b"first", &author, "Commit")?;
+
+    let data = CommitData::new(
+        &t16::Data::now(),
+        Delta::new(b"first", b"second"),
+        author.id(),
+        "Tampered",
+        &Path::new(file!()),
+    )?;
+    let mut tampered = Commit::new(data, &state)?;
+    tampered.id = ID::new(vec![0u8; 32]);
+    state.add_commit(tampered)?;
+
+    let mut bytes = Delta::new(b"second", b"third!").to_plain_bytes();
+    bytes[..8].copy_from_slice(&2u64.to_le_bytes());
+    let broken = CommitData::new(
+        &t16::Data::now(),
+        Delta::from_plain_bytes(&bytes)?,
+        author.id(),
+        "Broken",
+        &Path::new(file!()),
+    )?;
+    state.add_commit(Commit::new(broken, &state)?)?;
+    assert_eq!(state.latest_version().err().expect("error").variant(), "DiffError");
+
+    let problems = OFVRState::from_path(&path)?.verify();
+    assert_eq!(
+        problems
+            .iter()
+            .map(|problem| (problem.index, problem.kind))
+            .collect::<Vec<_>>(),
+        vec![(1, ProblemKind::IdMismatch), (2, ProblemKind::BrokenDelta)]
+    );
+    assert_eq!(problems[0].commit, ID::new(vec![0u8; 32]).to_hex());
+
+    state.remove_author(author.id())?;
+    let unknown = state
+        .verify()
+        .into_iter()
+        .filter(|problem| problem.kind == ProblemKind::UnknownAuthor)
+        .map(|problem| problem.index)
+        .collect::<Vec<usize>>();
+    assert_eq!(unknown, vec![0, 1, 2]);
+
+    let report = FsckReport::new(&path)?;
+    assert_eq!(report.problems.len(), 2);
+    assert_eq!(report.exit_code(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_commits_are_chained_to_their_parent() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("chained.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    for index in 0..4 {
+        state.commit_blob(&seq_bytes(32 + index), &author, "Commit")?;
+    }
+    let commits = state.commits().to_vec();
+    assert_eq!(commits[0].data(&state)?.parents(), Vec::new());
+    for index in 1..4 {
+        assert_eq!(commits[index].data(&state)?.parents(), vec![commits[index - 1].id.clone()]);
+    }
+
+    let mut value = serde_json::to_value(&state)?;
+    value["commits"].as_array_mut().expect("commits").remove(1);
+    let removed: OFVRState = serde_json::from_value(value)?;
+    let problems = removed.verify();
+    assert_eq!(problems.len(), 1);
+    assert_eq!((problems[0].index, problems[0].kind), (1, ProblemKind::BrokenChain));
+
+    let mut value = serde_json::to_value(&state)?;
+    value["commits"].as_array_mut().expect("commits").swap(2, 3);
+    let reordered: OFVRState = serde_json::from_value(value)?;
+    assert_eq!(
+        reordered.verify().iter().map(|problem| problem.index).collect::<Vec<usize>>(),
+        vec![2]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_add_commit_refuses_commit_not_linked_to_latest_commit() -> Result<()> {
+    let author = author();
+    let path = path_to_test_file!("stale.ofvr");
+    let mut state = OFVRState::empty(&path, &author)?;
+    state.commit_blob(b"first", &author, "Commit")?;
+
+    let data = CommitData::new(
+        &t16::Data::now(),
+        Delta::new(b"first", b"second"),
+        author.id(),
+        "Stale",
+        &Path::new(file!()),
+    )?;
+    let stale = Commit::new(data, &state)?;
+    let mut other = OFVRState::from_path(&path)?;
+    other.commit_blob(b"concurrent", &author, "Commit")?;
+
+    let error = state.add_commit(stale).err().expect("error");
+    assert_eq!(error.variant(), "CommitError");
+    assert_eq!(state.commits().len(), 2);
+    assert_eq!(state.verify(), Vec::new());
+    Ok(())
+}